@@ -0,0 +1,568 @@
+/// Turns a Factorio data-raw dump into the recipes.json file that WikiBot reads.
+/// Make the dump with `factorio --dump-data`, which writes script-output/data-raw-dump.json.
+///
+/// Usage: import_recipes <data-raw-dump.json> [locale .cfg files or folders...] [--output recipes.json]
+/// Locale files are usually found at data/base/locale/en/ in the game folder.
+
+extern crate json;
+extern crate wikibot;
+
+use json::JsonValue;
+
+use std::collections::BTreeMap;
+use std::env;
+use std::fs::{self, File};
+use std::io::prelude::*;
+use std::path::Path;
+use std::process;
+
+use wikibot::recipe_book::{Recipe, RecipeBook, RecipeCost, Technology};
+
+/// Locale strings, by section and then key, such as "item-name" then "iron-plate".
+type Locale = BTreeMap<String, BTreeMap<String, String>>;
+/// How many times references in locale strings are expanded, in case they refer to each other.
+const MAX_LOCALE_EXPANSIONS: usize = 10;
+
+/// Main function. {{{1
+fn main() {
+    let mut args = env::args().skip(1);
+    let mut dump_path = None;
+    let mut locale_paths = Vec::new();
+    let mut output = String::from("recipes.json");
+
+    while let Some(arg) = args.next() {
+        if arg == "--output" {
+            match args.next() {
+                Some(path) => output = path,
+                None => fail("Expected a path after --output."),
+            }
+        } else if dump_path.is_none() {
+            dump_path = Some(arg);
+        } else {
+            locale_paths.push(arg);
+        }
+    }
+
+    let dump_path = match dump_path {
+        Some(path) => path,
+        None => {
+            fail("Usage: import_recipes <data-raw-dump.json> [locale .cfg files or folders...] [--output recipes.json]")
+        }
+    };
+
+    let data_raw = match json::parse(&read_file(Path::new(&dump_path))) {
+        Ok(parsed) => parsed,
+        Err(error) => fail(&format!("Couldn't parse {}: {}", dump_path, error)),
+    };
+
+    let mut locale = Locale::new();
+    for path in locale_paths.iter() {
+        read_locale(Path::new(path), &mut locale);
+    }
+    if locale.is_empty() {
+        println!("No locale files given, pretty names will be made from internal names.");
+    }
+
+    let book = import_recipe_book(&data_raw, &locale);
+    for problem in book.validate() {
+        println!("Warning: {}", problem);
+    }
+
+    match File::create(&output) {
+        Ok(mut file) => {
+            if let Err(error) = file.write_all(book.to_json_string().as_bytes()) {
+                fail(&format!("Couldn't write to {}: {}", output, error));
+            }
+        }
+        Err(error) => fail(&format!("Couldn't create {}: {}", output, error)),
+    }
+    println!("Wrote {} recipes and {} resources to {}.",
+             book.recipes.len(),
+             book.resources.len(),
+             output);
+}
+
+// Functions {{{1
+/// Prints an error and exits. {{{2
+fn fail(reason: &str) -> ! {
+    eprintln!("{}", reason);
+    process::exit(1);
+}
+
+/// Reads a whole file into a string, exiting if it can't. {{{2
+fn read_file(path: &Path) -> String {
+    let mut contents = String::new();
+    match File::open(path) {
+        Ok(mut file) => {
+            if let Err(error) = file.read_to_string(&mut contents) {
+                fail(&format!("Couldn't read {}: {}", path.display(), error));
+            }
+        }
+        Err(error) => fail(&format!("Couldn't open {}: {}", path.display(), error)),
+    }
+    contents
+}
+
+/// Reads a locale .cfg file, or every .cfg file in a folder, into the locale. {{{2
+fn read_locale(path: &Path, locale: &mut Locale) {
+    if path.is_dir() {
+        match fs::read_dir(path) {
+            Ok(entries) => {
+                for entry in entries.filter_map(|entry| entry.ok()) {
+                    let entry_path = entry.path();
+                    if entry_path.extension().map(|ext| ext == "cfg").unwrap_or(false) {
+                        parse_locale(&read_file(&entry_path), locale);
+                    }
+                }
+            }
+            Err(error) => fail(&format!("Couldn't read folder {}: {}", path.display(), error)),
+        }
+    } else {
+        parse_locale(&read_file(path), locale);
+    }
+}
+
+/// Parses the contents of a locale .cfg file, made of [sections] and key=value lines. {{{2
+fn parse_locale(source: &str, locale: &mut Locale) {
+    let mut section = String::new();
+    for line in source.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with(';') || line.starts_with('#') {
+            continue;
+        }
+        if line.starts_with('[') && line.ends_with(']') {
+            section = line[1..line.len() - 1].to_owned();
+        } else if let Some(index) = line.find('=') {
+            locale
+                .entry(section.clone())
+                .or_insert_with(BTreeMap::new)
+                .insert(line[..index].trim().to_owned(), line[index + 1..].trim().to_owned());
+        }
+    }
+}
+
+/// Looks up a pretty name in the given locale sections, falling back to the {{{2
+/// internal name with dashes made into spaces.
+fn locale_name(locale: &Locale, id: &str, sections: &[&str]) -> String {
+    expand_locale_references(locale, &unexpanded_locale_name(locale, id, sections))
+}
+
+/// Looks up a name like locale_name, leaving any references in it. {{{2
+fn unexpanded_locale_name(locale: &Locale, id: &str, sections: &[&str]) -> String {
+    for section in sections.iter() {
+        if let Some(name) = locale.get(*section).and_then(|names| names.get(id)) {
+            return name.clone();
+        }
+    }
+
+    let name = id.replace("-", " ");
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => name,
+    }
+}
+
+/// Replaces references such as __ITEM__iron-plate__ inside a locale string. {{{2
+/// Names can refer to other names, so this repeats until nothing changes, but
+/// gives up on names that refer to each other in a loop, leaving them as they are.
+fn expand_locale_references(locale: &Locale, text: &str) -> String {
+    let mut result = text.to_owned();
+    for _ in 0..MAX_LOCALE_EXPANSIONS {
+        let expanded = expand_locale_references_once(locale, &result);
+        if expanded == result {
+            break;
+        }
+        result = expanded;
+    }
+    result
+}
+
+/// Replaces each reference in a locale string once, without looking at what they're replaced with. {{{2
+fn expand_locale_references_once(locale: &Locale, text: &str) -> String {
+    let mut result = text.to_owned();
+    for &(marker, section) in [("__ITEM__", "item-name"),
+                               ("__FLUID__", "fluid-name"),
+                               ("__ENTITY__", "entity-name")]
+                .iter() {
+        let mut expanded = String::new();
+        {
+            let mut rest = result.as_str();
+            while let Some(start) = rest.find(marker) {
+                let after = &rest[start + marker.len()..];
+                let end = match after.find("__") {
+                    Some(end) => end,
+                    None => break,
+                };
+                expanded.push_str(&rest[..start]);
+                expanded.push_str(&unexpanded_locale_name(locale, &after[..end], &[section]));
+                rest = &after[end + 2..];
+            }
+            expanded.push_str(rest);
+        }
+        result = expanded;
+    }
+    result
+}
+
+/// Resolves a prototype's localised_name, which is either a key like {{{2
+/// "recipe-name.fill-barrel", or an array of a key and its parameters.
+fn resolve_localised_name(locale: &Locale, value: &JsonValue) -> Option<String> {
+    let (key, parameters): (&str, Vec<String>) = if let Some(key) = value.as_str() {
+        (key, Vec::new())
+    } else if value.is_array() && value[0].is_string() {
+        let mut parameters = Vec::new();
+        for parameter in value.members().skip(1) {
+            match resolve_localised_name(locale, parameter) {
+                Some(resolved) => parameters.push(resolved),
+                None => return None,
+            }
+        }
+        (value[0].as_str().unwrap(), parameters)
+    } else {
+        return None;
+    };
+
+    // An empty key joins the parameters together
+    if key.is_empty() {
+        return Some(parameters.concat());
+    }
+
+    let dot = match key.find('.') {
+        Some(dot) => dot,
+        None => return None,
+    };
+    let template = match locale.get(&key[..dot]).and_then(|names| names.get(&key[dot + 1..])) {
+        Some(template) => template.clone(),
+        None => return None,
+    };
+
+    let mut result = template;
+    for (index, parameter) in parameters.iter().enumerate() {
+        result = result.replace(&format!("__{}__", index + 1), parameter);
+    }
+    Some(expand_locale_references(locale, &result))
+}
+
+/// Turns a list of ingredients or results into a map of item amounts. {{{2
+/// Entries are either [name, amount] or {name, amount}, and results can
+/// have an amount range or a probability, which are averaged out.
+fn import_item_amounts(list: &JsonValue) -> BTreeMap<String, f64> {
+    let mut amounts = BTreeMap::new();
+    for entry in list.members() {
+        let (name, amount) = if entry.is_array() {
+            (entry[0].as_str(), entry[1].as_f64().unwrap_or(1.0))
+        } else {
+            let amount = match entry["amount"].as_f64() {
+                Some(amount) => amount,
+                None => {
+                    (entry["amount_min"].as_f64().unwrap_or(1.0) + entry["amount_max"].as_f64().unwrap_or(1.0)) / 2.0
+                }
+            };
+            (entry["name"].as_str(), amount * entry["probability"].as_f64().unwrap_or(1.0))
+        };
+        if let Some(name) = name {
+            *amounts.entry(name.to_owned()).or_insert(0.0) += amount;
+        }
+    }
+    amounts
+}
+
+/// Reads the crafting time, ingredients and results from a recipe prototype, {{{2
+/// or from one of its "normal" and "expensive" difficulty objects.
+fn import_recipe_cost(data: &JsonValue) -> RecipeCost {
+    let outputs = if let Some(result) = data["result"].as_str() {
+        let mut outputs = BTreeMap::new();
+        outputs.insert(result.to_owned(), data["result_count"].as_f64().unwrap_or(1.0));
+        outputs
+    } else {
+        import_item_amounts(&data["results"])
+    };
+
+    RecipeCost {
+        energy: data["energy_required"].as_f64().unwrap_or(0.5),
+        inputs: import_item_amounts(&data["ingredients"]),
+        outputs,
+    }
+}
+
+/// Turns one recipe prototype into a Recipe. Recipes with separate {{{2
+/// difficulties keep their expensive one, if it differs from normal.
+fn import_recipe(id: &str, prototype: &JsonValue, locale: &Locale) -> Recipe {
+    let normal = if prototype["normal"].is_object() {
+        import_recipe_cost(&prototype["normal"])
+    } else {
+        import_recipe_cost(prototype)
+    };
+    let expensive = if prototype["expensive"].is_object() {
+        Some(import_recipe_cost(&prototype["expensive"]))
+    } else {
+        None
+    };
+
+    // Recipes are usually named after what they make, so fall back on that name
+    let name = match resolve_localised_name(locale, &prototype["localised_name"]) {
+        Some(name) => name,
+        None => {
+            let product = if normal.outputs.len() == 1 {
+                normal.outputs.keys().next().unwrap().clone()
+            } else {
+                id.to_owned()
+            };
+            if locale.get("recipe-name").map(|names| names.contains_key(id)).unwrap_or(false) {
+                locale_name(locale, id, &["recipe-name"])
+            } else {
+                locale_name(locale, &product, &["item-name", "fluid-name", "entity-name", "equipment-name"])
+            }
+        }
+    };
+
+    Recipe {
+        id: id.to_owned(),
+        name,
+        category: prototype["category"].as_str().unwrap_or("crafting").to_owned(),
+        expensive: expensive.and_then(|expensive| if expensive == normal { None } else { Some(expensive) }),
+        energy: normal.energy,
+        inputs: normal.inputs,
+        outputs: normal.outputs,
+    }
+}
+
+/// Turns one technology prototype into a Technology, or None if its cost is {{{2
+/// a formula, as infinite technologies have. Technologies with separate
+/// difficulties use their normal difficulty.
+fn import_technology(id: &str, prototype: &JsonValue, locale: &Locale) -> Option<Technology> {
+    let data = if prototype["normal"].is_object() {
+        &prototype["normal"]
+    } else {
+        prototype
+    };
+    let count = match data["unit"]["count"].as_f64() {
+        Some(count) => count,
+        None => return None,
+    };
+
+    // Upgrades like "automation-2" share a locale entry with the first level, and show the level after it
+    let name = match resolve_localised_name(locale, &prototype["localised_name"]) {
+        Some(name) => name,
+        None => {
+            let has_entry = |id: &str| locale.get("technology-name").map(|names| names.contains_key(id)).unwrap_or(false);
+            match id.rfind('-') {
+                Some(dash) if !has_entry(id) && id[dash + 1..].parse::<u32>().is_ok() && has_entry(&id[..dash]) => {
+                    format!("{} {}", locale_name(locale, &id[..dash], &["technology-name"]), &id[dash + 1..])
+                }
+                _ => locale_name(locale, id, &["technology-name"]),
+            }
+        }
+    };
+
+    Some(Technology {
+        id: id.to_owned(),
+        name,
+        count,
+        time: data["unit"]["time"].as_f64().unwrap_or(1.0),
+        ingredients: import_item_amounts(&data["unit"]["ingredients"]),
+        prerequisites: data["prerequisites"]
+            .members()
+            .filter_map(|prerequisite| prerequisite.as_str().map(String::from))
+            .collect(),
+        unlocks: data["effects"]
+            .members()
+            .filter(|effect| effect["type"] == "unlock-recipe")
+            .filter_map(|effect| effect["recipe"].as_str().map(String::from))
+            .collect(),
+    })
+}
+
+/// Builds a recipe book from every recipe and technology in a data-raw dump. {{{2
+/// Hidden recipes are skipped, and ingredients no recipe makes are added as resources.
+fn import_recipe_book(data_raw: &JsonValue, locale: &Locale) -> RecipeBook {
+    let mut book = RecipeBook::default();
+
+    for (id, prototype) in data_raw["recipe"].entries() {
+        let hidden = prototype["hidden"].as_bool().unwrap_or(false) ||
+                     prototype["normal"]["hidden"].as_bool().unwrap_or(false);
+        if !hidden {
+            book.recipes.insert(id.to_owned(), import_recipe(id, prototype, locale));
+        }
+    }
+
+    let mut made: Vec<&str> = Vec::new();
+    for recipe in book.recipes.values() {
+        made.extend(recipe.outputs.keys().map(String::as_str));
+    }
+    let mut resources = BTreeMap::new();
+    for recipe in book.recipes.values() {
+        for input in recipe.inputs.keys() {
+            if !made.contains(&input.as_str()) {
+                resources.insert(input.clone(),
+                                 locale_name(locale, input, &["item-name", "fluid-name", "entity-name"]));
+            }
+        }
+    }
+    book.resources = resources;
+
+    for (id, prototype) in data_raw["technology"].entries() {
+        let hidden = prototype["hidden"].as_bool().unwrap_or(false) ||
+                     prototype["normal"]["hidden"].as_bool().unwrap_or(false);
+        if hidden {
+            continue;
+        }
+        if let Some(technology) = import_technology(id, prototype, locale) {
+            book.technologies.insert(id.to_owned(), technology);
+        }
+    }
+
+    // Don't point at recipes that were skipped for being hidden
+    let recipe_ids: Vec<String> = book.recipes.keys().cloned().collect();
+    for technology in book.technologies.values_mut() {
+        technology.unlocks.retain(|recipe| recipe_ids.contains(recipe));
+    }
+    book
+}
+
+// Tests {{{1
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEST_LOCALE: &'static str = "
+[item-name]
+iron-plate=Iron plate
+iron-ore=Iron ore
+empty-barrel=Empty barrel
+
+[fluid-name]
+water=Water
+
+[recipe-name]
+fill-barrel=Fill __1__ barrel
+
+[technology-name]
+automation=Automation
+";
+
+    fn test_locale() -> Locale {
+        let mut locale = Locale::new();
+        parse_locale(TEST_LOCALE, &mut locale);
+        locale
+    }
+
+    #[test]
+    fn can_parse_locale() {
+        let locale = test_locale();
+        assert_eq!(locale["item-name"]["iron-plate"], "Iron plate");
+        assert_eq!(locale_name(&locale, "iron-gear-wheel", &["item-name"]), "Iron gear wheel");
+    }
+
+    #[test]
+    fn can_expand_locale_references() {
+        let mut locale = test_locale();
+        parse_locale("[item-name]\nfilled-barrel=__FLUID__water__ in a __ITEM__empty-barrel__\n\
+                      a=Loops to __ITEM__b__\nb=Loops to __ITEM__a__", &mut locale);
+        assert_eq!(locale_name(&locale, "filled-barrel", &["item-name"]), "Water in a Empty barrel");
+        assert!(locale_name(&locale, "a", &["item-name"]).contains("__ITEM__"));
+    }
+
+    #[test]
+    fn can_import_recipes() {
+        let data_raw = json::parse(r#"{
+            "recipe": {
+                "iron-plate": {
+                    "type": "recipe", "name": "iron-plate", "category": "smelting",
+                    "energy_required": 3.5, "ingredients": [["iron-ore", 1]], "result": "iron-plate"
+                },
+                "fill-water-barrel": {
+                    "type": "recipe", "name": "fill-water-barrel", "category": "crafting-with-fluid",
+                    "localised_name": ["recipe-name.fill-barrel", ["fluid-name.water"]],
+                    "ingredients": [{"type": "fluid", "name": "water", "amount": 250}, {"type": "item", "name": "empty-barrel", "amount": 1}],
+                    "results": [{"type": "item", "name": "water-barrel", "amount": 1}]
+                },
+                "iron-gear-wheel": {
+                    "type": "recipe", "name": "iron-gear-wheel",
+                    "normal": {"ingredients": [["iron-plate", 2]], "result": "iron-gear-wheel"},
+                    "expensive": {"ingredients": [["iron-plate", 4]], "result": "iron-gear-wheel"}
+                },
+                "loader": {
+                    "type": "recipe", "name": "loader", "hidden": true,
+                    "ingredients": [["iron-plate", 5]], "result": "loader"
+                }
+            }
+        }"#).unwrap();
+        let book = import_recipe_book(&data_raw, &test_locale());
+
+        let plate = &book.recipes["iron-plate"];
+        assert_eq!(plate.name, "Iron plate");
+        assert_eq!(plate.category, "smelting");
+        assert_eq!(plate.energy, 3.5);
+        assert_eq!(plate.inputs["iron-ore"], 1.0);
+
+        let barrel = &book.recipes["fill-water-barrel"];
+        assert_eq!(barrel.name, "Fill Water barrel");
+        assert_eq!(barrel.energy, 0.5);
+        assert_eq!(barrel.inputs["water"], 250.0);
+        assert_eq!(barrel.outputs["water-barrel"], 1.0);
+
+        let gear = &book.recipes["iron-gear-wheel"];
+        assert_eq!(gear.inputs["iron-plate"], 2.0);
+        assert_eq!(gear.expensive.as_ref().unwrap().inputs["iron-plate"], 4.0);
+        assert_eq!(plate.expensive, None);
+        assert!(!book.recipes.contains_key("loader"));
+
+        assert_eq!(book.resources["iron-ore"], "Iron ore");
+        assert_eq!(book.resources["water"], "Water");
+        assert_eq!(book.resources["empty-barrel"], "Empty barrel");
+        assert!(book.validate().is_empty());
+    }
+
+    #[test]
+    fn can_import_technologies() {
+        let data_raw = json::parse(r#"{
+            "recipe": {
+                "assembling-machine-1": {
+                    "type": "recipe", "name": "assembling-machine-1",
+                    "ingredients": [["iron-plate", 9]], "result": "assembling-machine-1"
+                }
+            },
+            "technology": {
+                "automation": {
+                    "type": "technology", "name": "automation",
+                    "effects": [{"type": "unlock-recipe", "recipe": "assembling-machine-1"}, {"type": "unlock-recipe", "recipe": "loader"}],
+                    "unit": {"count": 10, "ingredients": [["science-pack-1", 1]], "time": 10}
+                },
+                "automation-2": {
+                    "type": "technology", "name": "automation-2", "prerequisites": ["automation"],
+                    "unit": {"count": 40, "ingredients": [["science-pack-1", 1], ["science-pack-2", 1]], "time": 15}
+                },
+                "mining-productivity-4": {
+                    "type": "technology", "name": "mining-productivity-4",
+                    "unit": {"count_formula": "2500*(L-3)", "ingredients": [["science-pack-1", 1]], "time": 60}
+                }
+            }
+        }"#).unwrap();
+        let book = import_recipe_book(&data_raw, &test_locale());
+
+        let automation = &book.technologies["automation"];
+        assert_eq!(automation.name, "Automation");
+        assert_eq!(automation.unlocks, vec![String::from("assembling-machine-1")]);
+
+        let automation_2 = &book.technologies["automation-2"];
+        assert_eq!(automation_2.name, "Automation 2");
+        assert_eq!(automation_2.count, 40.0);
+        assert_eq!(automation_2.ingredients["science-pack-2"], 1.0);
+        assert_eq!(automation_2.prerequisites, vec![String::from("automation")]);
+
+        assert!(!book.technologies.contains_key("mining-productivity-4"));
+    }
+
+    #[test]
+    fn averages_random_results() {
+        let results = json::parse(r#"[
+            {"name": "uranium-235", "amount": 1, "probability": 0.007},
+            {"name": "stone", "amount_min": 1, "amount_max": 3}
+        ]"#).unwrap();
+        let amounts = import_item_amounts(&results);
+        assert_eq!(amounts["uranium-235"], 0.007);
+        assert_eq!(amounts["stone"], 2.0);
+    }
+}
@@ -7,12 +7,15 @@ use serenity::model::*;
 use serenity::model::event::*;
 use serenity::framework::standard::*;
 
+use std::env;
 use std::fs::File;
 use std::io::prelude::*;
 
 use wikibot::commands::*;
 use wikibot::common_funcs::*;
 use wikibot::constants::{self, install_prefixes};
+use wikibot::{handle_search_reaction, install_mod_watches, install_portal_cache, install_recipes, watch_mod_updates,
+              watch_recipes};
 
 //Eventhandler setup {{{1
 struct Handler;
@@ -33,6 +36,11 @@ impl EventHandler for Handler {
         log_status!("Resumed after a disconnect.");
         ctx.set_game_name(format!("@{} help for help!", constants::BOT_NAME).as_str());
     }
+
+    // Reaction handlers {{{2
+    fn on_reaction_add(&self, _ctx: Context, reaction: Reaction) {
+        handle_search_reaction(&reaction);
+    }
 }
 
 /// Main function. {{{1
@@ -59,6 +67,19 @@ fn main() {
     install_prefixes();
     log_init!("Configured prefixes from file.");
 
+    install_recipes();
+    // Pass --watch-recipes to reload recipes.json whenever it's changed
+    if env::args().any(|arg| arg == "--watch-recipes") {
+        watch_recipes();
+    }
+
+    // Pass --cache-portal to keep mod portal responses in a file between restarts
+    if env::args().any(|arg| arg == "--cache-portal") {
+        install_portal_cache();
+    }
+    install_mod_watches();
+    watch_mod_updates();
+
     // Configure client with framework {{{2
     client.with_framework(StandardFramework::new()
             .simple_bucket("slowly", 5)
@@ -112,6 +133,11 @@ fn main() {
                             .help_available(false)
                             .owners_only(true)
                             .exec(stop_process))
+                   .command("reload-data", |c| c
+                            .desc("Reloads recipes.json without restarting. If it can't be loaded, the previous recipes are kept. Can only be used by the owner.")
+                            .help_available(false)
+                            .owners_only(true)
+                            .exec(reload_data))
                    .command("host", |c| c
                             .desc("Prints out info about the host's uptime, and system info.")
                             .bucket("super-slowly")
@@ -155,11 +181,13 @@ fn main() {
                           .command("linkmod", |c| c
                                    .desc("When provided with the name of a mod, it will return an embed of all the data on that mod.
                                          \nWhen provided with anything else, preforms a search.
+                                         \nReact to the search results with the arrows to see more, or a number to open that mod.
+                                         \nSearches can be narrowed with `--version 0.16` and `--tag logistics`, and ordered with `--sort downloads`, `--sort updated` or `--sort created`.
                                          \nFails if that search doesn't return any results.
                                          \nThe embed also provides popularity evaluation based on the download counts.
                                          \nThis command supports pipe syntax.")
                                    .help_available(true)
-                                   .example("Achiever")
+                                   .example("belts --version 0.16 --sort downloads")
                                    .known_as("mod")
                                    .bucket("slowly")
                                    .exec(linkmod))
@@ -171,8 +199,30 @@ fn main() {
                                          \nThis command also supports pipe syntax.")
                                    .help_available(true)
                                    .example("Gangsir")
-                                   .bucket("super-slowly")
+                                   .bucket("slowly")
                                    .exec(modder))
+                          .command("moddeps", |c| c
+                                   .desc("When provided with the internal name of a mod, resolves its required dependencies on the mod portal, and theirs in turn, into a tree.
+                                         \nAlso lists optional and incompatible mods, and versions that are needed but can't be had.
+                                         \nGive a game version after the mod name to check the mods are made for it.")
+                                   .help_available(true)
+                                   .example("bobwarfare 0.16")
+                                   .bucket("super-slowly")
+                                   .exec(moddeps))
+                          .command("modcompare", |c| c
+                                   .desc("When provided with two or more mods, compares them side by side: downloads, latest version, Factorio version, last update, dependencies and author.
+                                         \nSeparate the mods with commas if their names have spaces. Up to 6 can be compared at once.")
+                                   .help_available(true)
+                                   .example("bobores, angelsrefining")
+                                   .bucket("super-slowly")
+                                   .exec(modcompare))
+                          .command("modchangelog", |c| c
+                                   .desc("When provided with a mod, lists its releases with when they came out and what version of Factorio they're for, along with what changed in the latest one.
+                                         \nGive a version after the mod name to see what changed in that release instead.")
+                                   .help_available(true)
+                                   .example("bobores 0.16.0")
+                                   .bucket("slowly")
+                                   .exec(modchangelog))
                           .command("version", |c| c
                                    .desc("Returns the number of the latest version for stable and experimental. Due to expensive operations, can only be used once every 30 seconds.")
                                    .help_available(true)
@@ -189,14 +239,151 @@ fn main() {
                           .command("recipe", |c| c
                                    .desc("Returns a recipe of an item, machine, or process.
                                          \nIf the name of the object isn't internal, it will be autocorrected to the closest match.
+                                         \nAdd `--tree` to expand every ingredient down to raw materials, with a total of the raw materials needed.
+                                         \nAdd `--expensive` to see the recipe as it is on expensive difficulty.
+                                         \nShows the craft time, output, and power draw in an assembling machine 2 or steel furnace. Change the machine with `--assembler 1/2/3` or `--furnace stone/steel/electric`, add modules with `--modules speed3,2xprod3`, and beacons with `--beacons 8` or `--beacons 8xspeed2`.
                                          \nThis command uses fancy algorithms to correct names and run, and so can only be used every few seconds.
                                          \nThis command also supports typing after the command, end the command with two pipes, ||.")
                                    .help_available(true)
                                    .bucket("super-slowly")
-                                   .usage("<some item, process....>")
+                                   .usage("[--tree] [--expensive] [--assembler tier] [--modules list] [--beacons count] <some item, process....>")
                                    .example("beacon")
                                    .exec(recipe))
+                          .command("ratio", |c| c
+                                   .desc("Calculates the machines and raw resources needed to make an item at a given rate.
+                                         \nProvide the item, then the rate per second or minute, such as `1/s` or `30/m`.
+                                         \nAssumes assembling machine 2s and steel furnaces, which can be changed with `--assembler 1/2/3` and `--furnace stone/steel/electric`.
+                                         \nAdd `--expensive` to use expensive difficulty recipes.
+                                         \nThis command also supports typing after the command, end the command with two pipes, ||.")
+                                   .help_available(true)
+                                   .bucket("super-slowly")
+                                   .usage("<item> <rate> [--assembler tier] [--furnace tier] [--expensive]")
+                                   .example("red science 1/s")
+                                   .exec(ratio))
+                          .command("uses", |c| c
+                                   .desc("Lists every recipe that uses an item as an ingredient, and every recipe that makes it.
+                                         \nUseful for finding out what to do with byproducts, like heavy oil.
+                                         \nIf the name of the item isn't internal, it will be autocorrected to the closest match.
+                                         \nThis command also supports typing after the command, end the command with two pipes, ||.")
+                                   .help_available(true)
+                                   .known_as("usedin")
+                                   .bucket("super-slowly")
+                                   .usage("<some item>")
+                                   .example("heavy oil")
+                                   .exec(uses))
+                          .command("tech", |c| c
+                                   .desc("Shows the research cost of a technology, its prerequisites, and the recipes it unlocks.
+                                         \nAlso lists the full chain of technologies needed before it, and the science packs they all take.
+                                         \nIf the name of the technology isn't internal, it will be autocorrected to the closest match.
+                                         \nThis command also supports typing after the command, end the command with two pipes, ||.")
+                                   .help_available(true)
+                                   .bucket("super-slowly")
+                                   .usage("<some technology>")
+                                   .example("automation 2")
+                                   .exec(tech))
+                          .command("recipe-search", |c| c
+                                   .desc("Lists every recipe matching some criteria, 15 at a time.
+                                         \nSearch by ingredients with `uses`, by products with `makes`, by machine with `in`, and by crafting time with `takes more than` or `takes less than`.
+                                         \nAdd `page <number>` to see more results.
+                                         \nThis command also supports typing after the command, end the command with two pipes, ||.")
+                                   .help_available(true)
+                                   .bucket("super-slowly")
+                                   .usage("<criteria...> [page <number>]")
+                                   .example("uses sulfuric acid in chemical plant")
+                                   .exec(recipe_search))
                           )
+                          // BLUEPRINTS AND MAPS GROUP ---------- {{{3
+                          .group("Blueprints and Maps", |g| g
+                                 .command("blueprint", |c| c
+                                          .desc("Decodes a blueprint string, listing what it places and the items it takes to build, with a picture of the layout.
+                                                \nPaste the string after the command, or attach it as a .txt file if it's too long for a message.")
+                                          .help_available(true)
+                                          .bucket("slowly")
+                                          .usage("<blueprint string>")
+                                          .exec(blueprint))
+                                 .command("blueprint-book", |c| c
+                                          .desc("Lists every blueprint in a blueprint book, with its icons and size.
+                                                \nAdd the number of an entry after the string to look inside it, like `3`, or `2.1` for a book inside the book.")
+                                          .help_available(true)
+                                          .bucket("slowly")
+                                          .usage("<blueprint book string> [entry number]")
+                                          .exec(blueprint_book))
+                                 .command("blueprint-upgrade", |c| c
+                                          .desc("Swaps one kind of entity in a blueprint for another, and gives back the new string.
+                                                \nBelt colours (yellow, red, blue) swap every belt, underground and splitter of that colour, otherwise give internal entity names.")
+                                          .help_available(true)
+                                          .bucket("slowly")
+                                          .usage("<blueprint string> <from> <to>")
+                                          .example("<blueprint string> yellow red")
+                                          .exec(blueprint_upgrade))
+                                 .command("blueprint-mirror", |c| c
+                                          .desc("Flips a blueprint left to right, and gives back the new string.
+                                                \nBlueprints with oil refineries, chemical plants or train stops can't be flipped, since they'd build differently.")
+                                          .help_available(true)
+                                          .bucket("slowly")
+                                          .usage("<blueprint string>")
+                                          .exec(blueprint_mirror))
+                                 .command("blueprint-rotate", |c| c
+                                          .desc("Turns a blueprint clockwise by 90, 180 or 270 degrees, and gives back the new string.")
+                                          .help_available(true)
+                                          .bucket("slowly")
+                                          .usage("<blueprint string> [90|180|270]")
+                                          .exec(blueprint_rotate))
+                                 .command("mapstring", |c| c
+                                          .desc("Decodes a map exchange string, listing the resource, terrain, pollution and enemy settings the map was made with.")
+                                          .help_available(true)
+                                          .bucket("slowly")
+                                          .usage("<map exchange string>")
+                                          .exec(mapstring))
+                                )
+                          // TROUBLESHOOTING GROUP -------------- {{{3
+                          .group("Troubleshooting", |g| g
+                                 .command("log", |c| c
+                                          .desc("Reads an attached factorio-current.log or factorio-previous.log, reporting the game version, mods, the first error and which mod it points to, with advice for common problems.")
+                                          .help_available(true)
+                                          .bucket("slowly")
+                                          .usage("with the log attached")
+                                          .exec(crash_log))
+                                 .command("modlist", |c| c
+                                          .desc("Reads an attached mod-list.json, listing which mods are enabled with their latest versions on the mod portal.
+                                                \nGive your game version to find mods that aren't made for it.")
+                                          .help_available(true)
+                                          .bucket("super-slowly")
+                                          .usage("[game version], with mod-list.json attached")
+                                          .example("0.16")
+                                          .exec(modlist))
+                                 .command("save", |c| c
+                                          .desc("Reads an attached save .zip, reporting the game version and scenario it was made with, whether it's likely multiplayer, and its mods compared to the mod portal.")
+                                          .help_available(true)
+                                          .bucket("super-slowly")
+                                          .usage("with the save .zip attached")
+                                          .exec(save))
+                                )
+                          // MOD UPDATES GROUP ------------------ {{{3
+                          .group("Mod Updates", |g| g
+                                 .command("watch-mod", |c| c
+                                          .desc("Watches a mod, or every mod by a modder, announcing new versions with their changes in the channel mentioned, or this one.
+                                  \nThe mod portal is checked every 10 minutes. Can only be used by moderators.")
+                                          .required_permissions(is_powerful_perms)
+                                          .guild_only(true)
+                                          .help_available(true)
+                                          .bucket("super-slowly")
+                                          .usage("<mod or modder> [#channel]")
+                                          .example("bobores #mod-updates")
+                                          .exec(watch_mod))
+                                 .command("unwatch-mod", |c| c
+                                          .desc("Stops watching a mod or modder. Can only be used by moderators.")
+                                          .required_permissions(is_powerful_perms)
+                                          .guild_only(true)
+                                          .help_available(true)
+                                          .usage("<mod or modder>")
+                                          .exec(unwatch_mod))
+                                 .command("mod-watches", |c| c
+                                          .desc("Lists the mods and modders this server watches for new versions.")
+                                          .guild_only(true)
+                                          .help_available(true)
+                                          .exec(mod_watches))
+                                )
                           // FAQ GROUP -------------------------- {{{3
                           .group("FAQ System", |g| g
                                  .command("faq-list", |c|
@@ -0,0 +1,150 @@
+/// Reads the little endian binary data the game writes, such as in map exchange strings.
+/// Rather than failing on every read, running out of data is remembered, and zeroes are
+/// read from then on, so whole structures can be read before checking.
+
+use byteorder::{LittleEndian, ReadBytesExt};
+
+use std::io::{self, Cursor, Read};
+
+/// A reader over binary data held in memory. {{{1
+pub struct BinaryReader<'a> {
+    cursor: Cursor<&'a [u8]>,
+    truncated: bool,
+}
+
+impl<'a> BinaryReader<'a> {
+    /// Makes a reader starting at the beginning of the data. {{{2
+    pub fn new(data: &'a [u8]) -> BinaryReader<'a> {
+        BinaryReader {
+            cursor: Cursor::new(data),
+            truncated: false,
+        }
+    }
+
+    /// Returns true if the data ran out part way through a read. {{{2
+    pub fn is_truncated(&self) -> bool {
+        self.truncated
+    }
+
+    /// Returns how many bytes haven't been read yet. {{{2
+    pub fn remaining(&self) -> usize {
+        let length = self.cursor.get_ref().len() as u64;
+        (length - self.cursor.position().min(length)) as usize
+    }
+
+    /// Unwraps the result of a read, remembering if the data ran out. {{{2
+    fn check<T: Default>(&mut self, result: io::Result<T>) -> T {
+        match result {
+            Ok(value) => value,
+            Err(_) => {
+                self.truncated = true;
+                T::default()
+            }
+        }
+    }
+
+    pub fn u8(&mut self) -> u8 {
+        let result = self.cursor.read_u8();
+        self.check(result)
+    }
+
+    pub fn bool(&mut self) -> bool {
+        self.u8() != 0
+    }
+
+    pub fn u16(&mut self) -> u16 {
+        let result = self.cursor.read_u16::<LittleEndian>();
+        self.check(result)
+    }
+
+    pub fn u32(&mut self) -> u32 {
+        let result = self.cursor.read_u32::<LittleEndian>();
+        self.check(result)
+    }
+
+    pub fn i32(&mut self) -> i32 {
+        let result = self.cursor.read_i32::<LittleEndian>();
+        self.check(result)
+    }
+
+    pub fn f32(&mut self) -> f32 {
+        let result = self.cursor.read_f32::<LittleEndian>();
+        self.check(result)
+    }
+
+    pub fn f64(&mut self) -> f64 {
+        let result = self.cursor.read_f64::<LittleEndian>();
+        self.check(result)
+    }
+
+    /// Reads a count, which takes one byte if it's under 255, or the byte 255 {{{2
+    /// then four more bytes if not.
+    pub fn count(&mut self) -> u32 {
+        match self.u8() {
+            255 => self.u32(),
+            count => count as u32,
+        }
+    }
+
+    /// Reads a number that takes one byte if it's under 255, or the byte 255 {{{2
+    /// then two more bytes if not.
+    pub fn optimized_u16(&mut self) -> u16 {
+        match self.u8() {
+            255 => self.u16(),
+            number => number as u16,
+        }
+    }
+
+    /// Reads a string, written as its length in bytes then the text itself. {{{2
+    pub fn string(&mut self) -> String {
+        let length = self.count() as usize;
+        // Don't trust a length longer than what's left, it would only fail after a huge allocation
+        if length > self.remaining() {
+            self.truncated = true;
+            return String::new();
+        }
+        let mut bytes = vec![0; length];
+        let result = self.cursor.read_exact(&mut bytes);
+        self.check(result);
+        String::from_utf8_lossy(&bytes).into_owned()
+    }
+
+    /// Skips over some bytes without reading them. {{{2
+    pub fn skip(&mut self, length: usize) {
+        if length > self.remaining() {
+            self.truncated = true;
+        }
+        let position = self.cursor.position() + length as u64;
+        self.cursor.set_position(position);
+    }
+}
+
+// Tests {{{1
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_read_binary_data() {
+        let data = [7, 255, 44, 1, 0, 0, 255, 44, 1, 4, b'c', b'o', b'a', b'l', 1, 0x34, 0x12];
+        let mut reader = BinaryReader::new(&data);
+        assert_eq!(reader.count(), 7);
+        assert_eq!(reader.count(), 300);
+        assert_eq!(reader.optimized_u16(), 300);
+        assert_eq!(reader.string(), "coal");
+        assert!(reader.bool());
+        assert_eq!(reader.u16(), 0x1234);
+        assert!(!reader.is_truncated());
+
+        assert_eq!(reader.u32(), 0);
+        assert!(reader.is_truncated());
+    }
+
+    #[test]
+    fn stops_at_long_strings() {
+        let data = [200, b'a', b'b'];
+        let mut reader = BinaryReader::new(&data);
+        assert_eq!(reader.string(), "");
+        assert!(reader.is_truncated());
+    }
+}
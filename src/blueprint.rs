@@ -0,0 +1,631 @@
+/// Holds the decoding of Factorio blueprint strings, and a typed view of what's in them.
+/// A blueprint string is a version byte, then base64 of zlib compressed json.
+
+use base64;
+use flate2::Compression;
+use flate2::read::ZlibDecoder;
+use flate2::write::ZlibEncoder;
+use json::{self, JsonValue};
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io::{Read, Write};
+
+/// The only version byte the game has used so far.
+const VERSION_BYTE: char = '0';
+/// Entities whose fluid connections or rail side aren't the same both ways round,
+/// and which the game has no flipped version of.
+const UNMIRRORABLE: [&'static str; 3] = ["oil-refinery", "chemical-plant", "train-stop"];
+/// The most json a blueprint string is decompressed into, in bytes, so a small string
+/// can't be made to decompress into gigabytes. Even huge books stay well under this.
+const MAX_DECOMPRESSED: u64 = 20_000_000;
+
+/// A single entity placed by a blueprint. {{{1
+#[derive(Debug, Clone, PartialEq)]
+pub struct Entity {
+    pub name: String,
+    pub x: f64,
+    pub y: f64,
+    pub direction: u8, //0 is north, counting clockwise in eighths
+    pub items: BTreeMap<String, f64>, //Modules and such inserted into it
+}
+
+/// A single tile placed by a blueprint. {{{1
+#[derive(Debug, Clone, PartialEq)]
+pub struct Tile {
+    pub name: String,
+    pub x: f64,
+    pub y: f64,
+}
+
+/// A decoded blueprint. {{{1
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Blueprint {
+    pub label: String,
+    pub icons: Vec<String>, //Signal names, in order
+    pub entities: Vec<Entity>,
+    pub tiles: Vec<Tile>,
+    pub version: Option<u64>, //Game version it was made in, packed into 16 bit parts
+}
+
+/// A decoded blueprint book. {{{1
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BlueprintBook {
+    pub label: String,
+    pub icons: Vec<String>,
+    pub entries: Vec<BookEntry>, //Sorted by their slot in the book
+    pub version: Option<u64>,
+}
+
+/// Something in a slot of a blueprint book. {{{1
+#[derive(Debug, Clone, PartialEq)]
+pub struct BookEntry {
+    pub index: u64, //Slot in the book, counted from 0
+    pub item: BookItem,
+}
+
+/// What can be kept in a blueprint book. Books can hold other books, and {{{1
+/// planners, which are only listed by their item name.
+#[derive(Debug, Clone, PartialEq)]
+pub enum BookItem {
+    Blueprint(Blueprint),
+    Book(BlueprintBook),
+    Planner(String),
+}
+
+/// Changes that can be made to a blueprint string. {{{1
+#[derive(Debug, Clone, PartialEq)]
+pub enum Transform {
+    Replace(BTreeMap<String, String>), //Entity names, and what to swap them for
+    Mirror, //Flips it left to right
+    Rotate, //Turns it a quarter clockwise
+}
+
+/// Reasons a blueprint string couldn't be decoded. {{{1
+#[derive(Debug, Clone, PartialEq)]
+pub enum BlueprintError {
+    Empty,
+    UnknownVersion(char),
+    Base64(String),
+    Zlib(String),
+    TooLarge,
+    Json(String),
+    CantMirror(String),
+    IsBook,
+    NotBook,
+    NotBlueprint,
+}
+
+impl fmt::Display for BlueprintError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            BlueprintError::Empty => write!(f, "The blueprint string is empty."),
+            BlueprintError::UnknownVersion(version) => {
+                write!(f, "The blueprint string starts with `{}`, which isn't a version I know.", version)
+            }
+            BlueprintError::Base64(ref reason) => write!(f, "The blueprint string isn't valid base64: {}", reason),
+            BlueprintError::Zlib(ref reason) => write!(f, "The blueprint string couldn't be decompressed: {}", reason),
+            BlueprintError::TooLarge => {
+                write!(f, "The blueprint string decompresses to over {} megabytes, which is too big to read.",
+                       MAX_DECOMPRESSED / 1_000_000)
+            }
+            BlueprintError::Json(ref reason) => write!(f, "The blueprint string doesn't hold valid json: {}", reason),
+            BlueprintError::CantMirror(ref name) => {
+                write!(f, "It has a `{}` in it, which the game can't flip, so it would come out built differently.", name)
+            }
+            BlueprintError::IsBook => write!(f, "That's a blueprint book, not a single blueprint."),
+            BlueprintError::NotBook => write!(f, "That's a single blueprint, not a blueprint book."),
+            BlueprintError::NotBlueprint => write!(f, "The blueprint string doesn't hold a blueprint."),
+        }
+    }
+}
+
+impl Blueprint {
+    /// Decodes a blueprint string holding a single blueprint. {{{2
+    pub fn decode(string: &str) -> Result<Blueprint, BlueprintError> {
+        let root = match decode_json(string) {
+            Ok(root) => root,
+            Err(error) => return Err(error),
+        };
+        if root["blueprint"].is_object() {
+            Ok(Blueprint::from_json(&root["blueprint"]))
+        } else if root["blueprint_book"].is_object() {
+            Err(BlueprintError::IsBook)
+        } else {
+            Err(BlueprintError::NotBlueprint)
+        }
+    }
+
+    /// Reads a blueprint from the object under a "blueprint" key. {{{2
+    /// Anything missing is left empty, since the game leaves out empty lists.
+    pub fn from_json(value: &JsonValue) -> Blueprint {
+        let entities = value["entities"]
+            .members()
+            .filter_map(|entity| {
+                entity["name"].as_str().map(|name| {
+                    Entity {
+                        name: name.to_owned(),
+                        x: entity["position"]["x"].as_f64().unwrap_or(0.0),
+                        y: entity["position"]["y"].as_f64().unwrap_or(0.0),
+                        direction: entity["direction"].as_u8().unwrap_or(0),
+                        items: entity["items"]
+                            .entries()
+                            .filter_map(|(item, count)| count.as_f64().map(|count| (item.to_owned(), count)))
+                            .collect(),
+                    }
+                })
+            })
+            .collect();
+        let tiles = value["tiles"]
+            .members()
+            .filter_map(|tile| {
+                tile["name"].as_str().map(|name| {
+                    Tile {
+                        name: name.to_owned(),
+                        x: tile["position"]["x"].as_f64().unwrap_or(0.0),
+                        y: tile["position"]["y"].as_f64().unwrap_or(0.0),
+                    }
+                })
+            })
+            .collect();
+
+        Blueprint {
+            label: value["label"].as_str().unwrap_or("").to_owned(),
+            icons: parse_icons(&value["icons"]),
+            entities,
+            tiles,
+            version: value["version"].as_u64(),
+        }
+    }
+
+    /// Counts how many of each entity the blueprint places. {{{2
+    pub fn entity_counts(&self) -> BTreeMap<String, usize> {
+        let mut counts = BTreeMap::new();
+        for entity in self.entities.iter() {
+            *counts.entry(entity.name.clone()).or_insert(0) += 1;
+        }
+        counts
+    }
+
+    /// Adds up the items needed to build the blueprint, including anything {{{2
+    /// inserted into the entities, like modules.
+    pub fn bill_of_materials(&self) -> BTreeMap<String, f64> {
+        let mut materials = BTreeMap::new();
+        for entity in self.entities.iter() {
+            let (item, count) = item_to_place(&entity.name);
+            *materials.entry(item.to_owned()).or_insert(0.0) += count;
+            for (inserted, count) in entity.items.iter() {
+                *materials.entry(inserted.clone()).or_insert(0.0) += *count;
+            }
+        }
+        for tile in self.tiles.iter() {
+            let (item, count) = item_to_place(&tile.name);
+            *materials.entry(item.to_owned()).or_insert(0.0) += count;
+        }
+        materials
+    }
+}
+
+impl BlueprintBook {
+    /// Decodes a blueprint string holding a blueprint book. {{{2
+    pub fn decode(string: &str) -> Result<BlueprintBook, BlueprintError> {
+        let root = match decode_json(string) {
+            Ok(root) => root,
+            Err(error) => return Err(error),
+        };
+        if root["blueprint_book"].is_object() {
+            Ok(BlueprintBook::from_json(&root["blueprint_book"]))
+        } else if root["blueprint"].is_object() {
+            Err(BlueprintError::NotBook)
+        } else {
+            Err(BlueprintError::NotBlueprint)
+        }
+    }
+
+    /// Reads a book from the object under a "blueprint_book" key. {{{2
+    pub fn from_json(value: &JsonValue) -> BlueprintBook {
+        let mut entries: Vec<BookEntry> = value["blueprints"]
+            .members()
+            .filter_map(|entry| {
+                let item = if entry["blueprint"].is_object() {
+                    BookItem::Blueprint(Blueprint::from_json(&entry["blueprint"]))
+                } else if entry["blueprint_book"].is_object() {
+                    BookItem::Book(BlueprintBook::from_json(&entry["blueprint_book"]))
+                } else {
+                    // Planners are keyed by their kind, like "upgrade_planner"
+                    match entry.entries().map(|(key, _)| key).find(|key| *key != "index") {
+                        Some(kind) => BookItem::Planner(kind.replace("_", "-")),
+                        None => return None,
+                    }
+                };
+                Some(BookEntry {
+                    index: entry["index"].as_u64().unwrap_or(0),
+                    item,
+                })
+            })
+            .collect();
+        entries.sort_by_key(|entry| entry.index);
+
+        BlueprintBook {
+            label: value["label"].as_str().unwrap_or("").to_owned(),
+            icons: parse_icons(&value["icons"]),
+            entries,
+            version: value["version"].as_u64(),
+        }
+    }
+
+    /// Finds an entry by its position in the list, counted from 1, following {{{2
+    /// each position into a book inside the last.
+    pub fn find(&self, path: &[usize]) -> Option<&BookItem> {
+        let entry = match path.first() {
+            Some(&position) if position >= 1 => self.entries.get(position - 1),
+            _ => None,
+        };
+        match entry {
+            Some(entry) if path.len() == 1 => Some(&entry.item),
+            Some(&BookEntry { item: BookItem::Book(ref book), .. }) => book.find(&path[1..]),
+            _ => None,
+        }
+    }
+}
+
+// Functions {{{1
+/// Turns a blueprint string back into the json it was made from. {{{2
+pub fn decode_json(string: &str) -> Result<JsonValue, BlueprintError> {
+    // Strings pasted into chat often pick up line breaks
+    let string: String = string.chars().filter(|c| !c.is_whitespace()).collect();
+    let mut chars = string.chars();
+    match chars.next() {
+        Some(VERSION_BYTE) => {}
+        Some(other) => return Err(BlueprintError::UnknownVersion(other)),
+        None => return Err(BlueprintError::Empty),
+    }
+
+    let compressed = match base64::decode(chars.as_str()) {
+        Ok(compressed) => compressed,
+        Err(error) => return Err(BlueprintError::Base64(format!("{}", error))),
+    };
+    // Read one byte past the limit, to tell if there was more
+    let mut decompressed = Vec::new();
+    if let Err(error) = ZlibDecoder::new(&compressed[..])
+        .take(MAX_DECOMPRESSED + 1)
+        .read_to_end(&mut decompressed) {
+        return Err(BlueprintError::Zlib(format!("{}", error)));
+    }
+    if decompressed.len() as u64 > MAX_DECOMPRESSED {
+        return Err(BlueprintError::TooLarge);
+    }
+    let decompressed = match String::from_utf8(decompressed) {
+        Ok(decompressed) => decompressed,
+        Err(error) => return Err(BlueprintError::Json(format!("{}", error))),
+    };
+    match json::parse(&decompressed) {
+        Ok(parsed) => Ok(parsed),
+        Err(error) => Err(BlueprintError::Json(format!("{}", error))),
+    }
+}
+
+/// Turns json back into a blueprint string, the way the game makes them. {{{2
+pub fn encode_json(value: &JsonValue) -> String {
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::Best);
+    // Writing into memory can't fail
+    encoder.write_all(value.dump().as_bytes()).unwrap();
+    let compressed = encoder.finish().unwrap();
+    format!("{}{}", VERSION_BYTE, base64::encode(&compressed))
+}
+
+/// Makes a change to every blueprint in a blueprint string, including those in {{{2
+/// books, then encodes it again. Everything else in the string is kept as it was.
+/// Returns the new string, and how many entities and tiles were changed.
+pub fn transform(string: &str, transform: &Transform) -> Result<(String, usize), BlueprintError> {
+    let mut root = match decode_json(string) {
+        Ok(root) => root,
+        Err(error) => return Err(error),
+    };
+    let changed = if root["blueprint"].is_object() {
+        transform_blueprint(&mut root["blueprint"], transform)
+    } else if root["blueprint_book"].is_object() {
+        transform_book(&mut root["blueprint_book"], transform)
+    } else {
+        return Err(BlueprintError::NotBlueprint);
+    };
+    match changed {
+        Ok(changed) => Ok((encode_json(&root), changed)),
+        Err(error) => Err(error),
+    }
+}
+
+/// Transforms every blueprint in a book, and in books inside it. {{{2
+fn transform_book(book: &mut JsonValue, transform: &Transform) -> Result<usize, BlueprintError> {
+    let mut changed = 0;
+    for entry in book["blueprints"].members_mut() {
+        let result = if entry["blueprint"].is_object() {
+            transform_blueprint(&mut entry["blueprint"], transform)
+        } else if entry["blueprint_book"].is_object() {
+            transform_book(&mut entry["blueprint_book"], transform)
+        } else {
+            continue;
+        };
+        match result {
+            Ok(count) => changed += count,
+            Err(error) => return Err(error),
+        }
+    }
+    Ok(changed)
+}
+
+/// Transforms the object under a "blueprint" key, returning how many entities {{{2
+/// and tiles changed. Mirroring fails if anything in it can't be flipped.
+fn transform_blueprint(blueprint: &mut JsonValue, transform: &Transform) -> Result<usize, BlueprintError> {
+    if *transform == Transform::Mirror {
+        let unmirrorable = blueprint["entities"]
+            .members()
+            .filter_map(|entity| entity["name"].as_str())
+            .find(|name| UNMIRRORABLE.contains(name));
+        if let Some(name) = unmirrorable {
+            return Err(BlueprintError::CantMirror(name.to_owned()));
+        }
+    }
+
+    let mut changed = 0;
+    match *transform {
+        Transform::Replace(ref replacements) => {
+            for entity in blueprint["entities"].members_mut() {
+                let replacement = entity["name"].as_str().and_then(|name| replacements.get(name)).cloned();
+                if let Some(replacement) = replacement {
+                    entity["name"] = replacement.into();
+                    changed += 1;
+                }
+            }
+            // Keep the icons matching what's in it
+            for icon in blueprint["icons"].members_mut() {
+                let replacement = icon["signal"]["name"].as_str().and_then(|name| replacements.get(name)).cloned();
+                if let Some(replacement) = replacement {
+                    icon["signal"]["name"] = replacement.into();
+                }
+            }
+        }
+        Transform::Mirror | Transform::Rotate => {
+            let mirror = *transform == Transform::Mirror;
+            for entity in blueprint["entities"].members_mut() {
+                let x = entity["position"]["x"].as_f64().unwrap_or(0.0);
+                let y = entity["position"]["y"].as_f64().unwrap_or(0.0);
+                let direction = entity["direction"].as_u8().unwrap_or(0);
+                // Adding 0 turns -0 into 0, so it isn't written with a sign
+                let (x, y, direction) = if mirror {
+                    let direction = if entity["name"] == "curved-rail" {
+                        (9 - direction) % 8
+                    } else {
+                        (8 - direction) % 8
+                    };
+                    (-x + 0.0, y, direction)
+                } else {
+                    (-y + 0.0, x, (direction + 2) % 8)
+                };
+                entity["position"]["x"] = x.into();
+                entity["position"]["y"] = y.into();
+                // The game leaves the direction out when facing north
+                if direction == 0 {
+                    entity.remove("direction");
+                } else {
+                    entity["direction"] = direction.into();
+                }
+                if mirror {
+                    for key in &["input_priority", "output_priority"] {
+                        let side = match entity[*key].as_str() {
+                            Some("left") => "right",
+                            Some("right") => "left",
+                            _ => continue,
+                        };
+                        entity[*key] = side.into();
+                    }
+                }
+                changed += 1;
+            }
+            // Tiles are positioned by their top left corner, rather than their middle
+            for tile in blueprint["tiles"].members_mut() {
+                let x = tile["position"]["x"].as_f64().unwrap_or(0.0);
+                let y = tile["position"]["y"].as_f64().unwrap_or(0.0);
+                let (x, y) = if mirror { (-x - 1.0, y) } else { (-y - 1.0, x) };
+                tile["position"]["x"] = x.into();
+                tile["position"]["y"] = y.into();
+                changed += 1;
+                // Either way round, the diagonal stripes of hazard concrete swap direction
+                let swapped = match tile["name"].as_str() {
+                    Some("hazard-concrete-left") => "hazard-concrete-right",
+                    Some("hazard-concrete-right") => "hazard-concrete-left",
+                    Some("refined-hazard-concrete-left") => "refined-hazard-concrete-right",
+                    Some("refined-hazard-concrete-right") => "refined-hazard-concrete-left",
+                    _ => continue,
+                };
+                tile["name"] = swapped.into();
+            }
+        }
+    }
+    Ok(changed)
+}
+
+/// Reads the icons of a blueprint or book, sorted by their index. {{{2
+pub fn parse_icons(value: &JsonValue) -> Vec<String> {
+    let mut icons: Vec<(u64, String)> = value
+        .members()
+        .filter_map(|icon| {
+            icon["signal"]["name"]
+                .as_str()
+                .map(|name| (icon["index"].as_u64().unwrap_or(0), name.to_owned()))
+        })
+        .collect();
+    icons.sort();
+    icons.into_iter().map(|(_, name)| name).collect()
+}
+
+/// Returns the item, and how many of it, that places an entity or tile. {{{2
+/// Most are placed by an item of the same name.
+fn item_to_place(name: &str) -> (&str, f64) {
+    match name {
+        "straight-rail" => ("rail", 1.0),
+        "curved-rail" => ("rail", 4.0),
+        "stone-path" => ("stone-brick", 1.0),
+        "hazard-concrete-left" | "hazard-concrete-right" => ("hazard-concrete", 1.0),
+        _ => (name, 1.0),
+    }
+}
+
+/// Formats a packed game version, such as 0.15.34. {{{2
+pub fn format_version(version: u64) -> String {
+    format!("{}.{}.{}", version >> 48, (version >> 32) & 0xffff, (version >> 16) & 0xffff)
+}
+
+// Tests {{{1
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Two belts, an assembler with two speed modules, and a curved rail, labelled "Test build".
+    const TEST_BLUEPRINT: &'static str = "0eNqNkt1OxCAQhV/FzDUYl61s0ufwzhhD20l3En4aoKtNw7sLNDFVd9UrMjPMd4YzrNDpGSdPNkK7AvXOBmifVwg0WqVLLi4TQgsU0QADq0yJVAhoOk125Eb1Z7LIBSQGZAd8h1Yk9iciemXD5HzkHeq4az6kFwZoI0XCbZgaLK92Nh36fOEWg8HkQm5ztqhm1MP9I4OlnqmM9A0k/g067EAMBvLYb1VxBXv83aabcFHhxaVQKmFCHLhxw6yxCkFWpc1J7ywfUXn+dkbUcGWI5nOIfvaXDPKK9E9tWZXl10fVDdRttbv/wUCr7E7OPWGId91MesjJC/pQu2TTCHk6nqRsUvoAXF7HVQ==";
+
+    /// A book holding the test blueprint, a book with one furnace in it, and a deconstruction planner.
+    const TEST_BOOK: &'static str = "0eNqdk9FOhDAQRX/FzHMxwiKb8Ac+65sxpMC4O7G0pC2rhPDvtl1dcGVd4hPpQM+de2cYoBQdtpqkLUql3iAfpoqB/HkAkjV+QJ4wqLFS0ljdVZaULFrBpUTtr5DFBvKzD6LvDxgYtJbkzgGHcRzZCXrHJrXA8feDqqGd5MLXbN+iYwcJBpI3/sSNwaYUjhk1vNqTxCiBkZ2a9RpXEFZzaVqlbVSisLPL8fjCAKUlS3hsJhz6QnZN6f3GlxgMWmXIe/eq3t/tPYM+PIPtM1CyGhTPQG4QpLE6vk0WsJu/Y7oITwLcp+QHBaZFrKNG1Z3AIAROlY5JajfeHXIdve8RBSw0kZ6aqDp9cCDNSfzWzoJy9tNUmMDXTk37wUBwl46rPaGxN2VHonbFA2oTbmVpmmTbzTbL0vmOxWz1jp+v47olMFa5XF87LXmFC+kGh/F4xdOD/1WumFogRMHRhHls3P64ebsSd2kesJi8/Rsa8hZUaq779eBPS/d2Ow==";
+
+    #[test]
+    fn can_decode_blueprint() {
+        let blueprint = Blueprint::decode(TEST_BLUEPRINT).unwrap();
+        assert_eq!(blueprint.label, "Test build");
+        assert_eq!(blueprint.icons, vec!["transport-belt", "assembling-machine-2"]);
+        assert_eq!(blueprint.entities.len(), 4);
+        assert_eq!(blueprint.entities[1].direction, 2);
+        assert_eq!(format_version(blueprint.version.unwrap()), "0.15.34");
+
+        let counts = blueprint.entity_counts();
+        assert_eq!(counts["transport-belt"], 2);
+        assert_eq!(counts["curved-rail"], 1);
+    }
+
+    #[test]
+    fn can_total_bill_of_materials() {
+        let materials = Blueprint::decode(TEST_BLUEPRINT).unwrap().bill_of_materials();
+        assert_eq!(materials["transport-belt"], 2.0);
+        assert_eq!(materials["assembling-machine-2"], 1.0);
+        assert_eq!(materials["speed-module"], 2.0);
+        assert_eq!(materials["rail"], 4.0);
+        assert!(!materials.contains_key("curved-rail"));
+    }
+
+    #[test]
+    fn can_decode_blueprint_book() {
+        let book = BlueprintBook::decode(TEST_BOOK).unwrap();
+        assert_eq!(book.label, "Test library");
+        assert_eq!(book.entries.len(), 3);
+        assert_eq!(book.entries[0].index, 0);
+
+        match book.find(&[1]) {
+            Some(&BookItem::Blueprint(ref blueprint)) => assert_eq!(blueprint.label, "Test build"),
+            other => panic!("Expected a blueprint, got {:?}", other),
+        }
+        match book.find(&[2, 1]) {
+            Some(&BookItem::Blueprint(ref blueprint)) => assert_eq!(blueprint.label, "Inner build"),
+            other => panic!("Expected a blueprint, got {:?}", other),
+        }
+        assert_eq!(book.find(&[3]), Some(&BookItem::Planner(String::from("deconstruction-planner"))));
+        assert_eq!(book.find(&[4]), None);
+        assert_eq!(book.find(&[0]), None);
+        assert_eq!(book.find(&[1, 1]), None);
+
+        assert_eq!(Blueprint::decode(TEST_BOOK), Err(BlueprintError::IsBook));
+        assert_eq!(BlueprintBook::decode(TEST_BLUEPRINT), Err(BlueprintError::NotBook));
+    }
+
+    #[test]
+    fn rejects_bad_strings() {
+        assert_eq!(Blueprint::decode(""), Err(BlueprintError::Empty));
+        assert_eq!(Blueprint::decode("1abc"), Err(BlueprintError::UnknownVersion('1')));
+        match Blueprint::decode("0!!!") {
+            Err(BlueprintError::Base64(_)) => {}
+            other => panic!("Expected a base64 error, got {:?}", other),
+        }
+
+        // Spaces compress down to almost nothing
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::Best);
+        encoder.write_all(&vec![b' '; MAX_DECOMPRESSED as usize + 1]).unwrap();
+        let bomb = format!("{}{}", VERSION_BYTE, base64::encode(&encoder.finish().unwrap()));
+        assert_eq!(decode_json(&bomb), Err(BlueprintError::TooLarge));
+    }
+
+    #[test]
+    fn can_encode_blueprint() {
+        let json = decode_json(TEST_BLUEPRINT).unwrap();
+        let encoded = encode_json(&json);
+        assert!(encoded.starts_with('0'));
+        assert_eq!(decode_json(&encoded).unwrap(), json);
+    }
+
+    #[test]
+    fn can_replace_entities() {
+        let mut replacements = BTreeMap::new();
+        replacements.insert(String::from("transport-belt"), String::from("fast-transport-belt"));
+        replacements.insert(String::from("assembling-machine-2"), String::from("assembling-machine-3"));
+        let (string, changed) = transform(TEST_BLUEPRINT, &Transform::Replace(replacements.clone())).unwrap();
+        assert_eq!(changed, 3);
+
+        let json = decode_json(&string).unwrap();
+        assert_eq!(json["blueprint"]["entities"][2]["recipe"], "iron-gear-wheel");
+        let blueprint = Blueprint::from_json(&json["blueprint"]);
+        assert_eq!(blueprint.entity_counts()["fast-transport-belt"], 2);
+        assert_eq!(blueprint.entities[2].items["speed-module"], 2.0);
+        assert_eq!(blueprint.icons, vec!["fast-transport-belt", "assembling-machine-3"]);
+
+        let (string, changed) = transform(TEST_BOOK, &Transform::Replace(replacements)).unwrap();
+        assert_eq!(changed, 3);
+        match BlueprintBook::decode(&string).unwrap().find(&[1]) {
+            Some(&BookItem::Blueprint(ref blueprint)) => assert_eq!(blueprint.entities[0].name, "fast-transport-belt"),
+            other => panic!("Expected a blueprint, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn can_mirror_blueprint() {
+        let (string, changed) = transform(TEST_BLUEPRINT, &Transform::Mirror).unwrap();
+        assert_eq!(changed, 4);
+        let blueprint = Blueprint::decode(&string).unwrap();
+        assert_eq!((blueprint.entities[0].x, blueprint.entities[0].y), (-0.5, 0.5));
+        assert_eq!((blueprint.entities[1].x, blueprint.entities[1].direction), (-1.5, 6));
+        assert_eq!(blueprint.entities[3].direction, 0);
+
+        let (string, _) = transform(&string, &Transform::Mirror).unwrap();
+        assert_eq!(Blueprint::decode(&string), Blueprint::decode(TEST_BLUEPRINT));
+
+        let mut refinery = JsonValue::new_object();
+        refinery["blueprint"]["entities"][0]["name"] = "oil-refinery".into();
+        refinery["blueprint"]["entities"][0]["position"]["x"] = 1.5.into();
+        refinery["blueprint"]["entities"][0]["position"]["y"] = 1.5.into();
+        assert_eq!(transform(&encode_json(&refinery), &Transform::Mirror),
+                   Err(BlueprintError::CantMirror(String::from("oil-refinery"))));
+        assert!(transform(&encode_json(&refinery), &Transform::Rotate).is_ok());
+    }
+
+    #[test]
+    fn counts_moved_tiles() {
+        let mut tiles = JsonValue::new_object();
+        for index in 0..3 {
+            tiles["blueprint"]["tiles"][index]["name"] = "concrete".into();
+            tiles["blueprint"]["tiles"][index]["position"]["x"] = index.into();
+            tiles["blueprint"]["tiles"][index]["position"]["y"] = 0.into();
+        }
+        assert_eq!(transform(&encode_json(&tiles), &Transform::Mirror).unwrap().1, 3);
+        assert_eq!(transform(&encode_json(&tiles), &Transform::Rotate).unwrap().1, 3);
+    }
+
+    #[test]
+    fn can_rotate_blueprint() {
+        let (string, _) = transform(TEST_BLUEPRINT, &Transform::Rotate).unwrap();
+        let blueprint = Blueprint::decode(&string).unwrap();
+        assert_eq!((blueprint.entities[1].x, blueprint.entities[1].y, blueprint.entities[1].direction), (-0.5, 1.5, 4));
+        assert_eq!(blueprint.entities[3].direction, 3);
+
+        let mut string = String::from(TEST_BLUEPRINT);
+        for _ in 0..4 {
+            string = transform(&string, &Transform::Rotate).unwrap().0;
+        }
+        assert_eq!(Blueprint::decode(&string), Blueprint::decode(TEST_BLUEPRINT));
+    }
+}
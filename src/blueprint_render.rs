@@ -0,0 +1,339 @@
+/// Draws decoded blueprints as top-down schematics, saved as PNG images.
+/// Entities are drawn as rectangles coloured by what they do, over a grid of the tiles they sit on.
+
+use blueprint::{Blueprint, Entity};
+use png::{self, HasParameters};
+
+/// How many pixels wide each tile is drawn, when the blueprint is small enough.
+const TILE_PIXELS: usize = 16;
+/// The most pixels wide or high an image can be, bigger blueprints get smaller tiles.
+const MAX_IMAGE_SIZE: usize = 1024;
+/// The fewest pixels a tile can be drawn with, before the blueprint is too big to draw.
+const MIN_TILE_PIXELS: usize = 2;
+/// Empty tiles drawn around the blueprint.
+const MARGIN: f64 = 1.0;
+
+const BACKGROUND: [u8; 3] = [32, 32, 32];
+const GRID: [u8; 3] = [48, 48, 48];
+const FLOOR: [u8; 3] = [72, 72, 64];
+const ARROW: [u8; 3] = [16, 16, 16];
+
+/// The kinds of entity, which are each drawn in their own colour. {{{1
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Category {
+    Belt,
+    Inserter,
+    Production,
+    Mining,
+    Power,
+    Fluid,
+    Storage,
+    Rail,
+    Defense,
+    Other,
+}
+
+impl Category {
+    /// Works out the category of an entity from its internal name. {{{2
+    pub fn of(name: &str) -> Category {
+        if name.contains("transport-belt") || name.contains("underground-belt") || name.contains("splitter") {
+            Category::Belt
+        } else if name.contains("inserter") {
+            Category::Inserter
+        } else if name.contains("assembling-machine") || name.contains("furnace") || name == "chemical-plant" ||
+                  name == "oil-refinery" || name == "centrifuge" || name == "lab" || name == "rocket-silo" ||
+                  name == "beacon" {
+            Category::Production
+        } else if name.contains("mining-drill") || name == "pumpjack" {
+            Category::Mining
+        } else if name.contains("electric-pole") || name == "substation" || name == "boiler" || name == "steam-engine" ||
+                  name == "steam-turbine" || name == "solar-panel" || name == "accumulator" ||
+                  name == "nuclear-reactor" || name == "heat-exchanger" || name == "heat-pipe" {
+            Category::Power
+        } else if name.contains("pipe") || name.contains("pump") || name == "storage-tank" {
+            Category::Fluid
+        } else if name.contains("chest") {
+            Category::Storage
+        } else if name.contains("rail") || name == "train-stop" {
+            Category::Rail
+        } else if name.contains("wall") || name == "gate" || name.contains("turret") {
+            Category::Defense
+        } else {
+            Category::Other
+        }
+    }
+
+    /// The colour entities of this category are filled with. {{{2
+    pub fn colour(&self) -> [u8; 3] {
+        match *self {
+            Category::Belt => [230, 190, 40],
+            Category::Inserter => [90, 160, 230],
+            Category::Production => [140, 140, 150],
+            Category::Mining => [160, 110, 60],
+            Category::Power => [220, 90, 60],
+            Category::Fluid => [70, 200, 190],
+            Category::Storage => [170, 120, 200],
+            Category::Rail => [120, 120, 100],
+            Category::Defense => [110, 170, 90],
+            Category::Other => [200, 200, 200],
+        }
+    }
+}
+
+/// An RGB image being drawn on, one byte per channel. {{{1
+struct Canvas {
+    width: usize,
+    height: usize,
+    pixels: Vec<u8>,
+}
+
+impl Canvas {
+    fn new(width: usize, height: usize, colour: [u8; 3]) -> Canvas {
+        let mut pixels = Vec::with_capacity(width * height * 3);
+        for _ in 0..width * height {
+            pixels.extend_from_slice(&colour);
+        }
+        Canvas { width, height, pixels }
+    }
+
+    fn set(&mut self, x: usize, y: usize, colour: [u8; 3]) {
+        if x < self.width && y < self.height {
+            let start = (y * self.width + x) * 3;
+            self.pixels[start..start + 3].copy_from_slice(&colour);
+        }
+    }
+
+    fn fill(&mut self, x: usize, y: usize, width: usize, height: usize, colour: [u8; 3]) {
+        for row in y..y + height {
+            for column in x..x + width {
+                self.set(column, row, colour);
+            }
+        }
+    }
+
+    /// Encodes the canvas as a PNG image.
+    fn to_png(&self) -> Result<Vec<u8>, String> {
+        let mut data = Vec::new();
+        {
+            let mut encoder = png::Encoder::new(&mut data, self.width as u32, self.height as u32);
+            encoder.set(png::ColorType::RGB).set(png::BitDepth::Eight);
+            let mut writer = match encoder.write_header() {
+                Ok(writer) => writer,
+                Err(error) => return Err(error.to_string()),
+            };
+            if let Err(error) = writer.write_image_data(&self.pixels) {
+                return Err(error.to_string());
+            }
+        }
+        Ok(data)
+    }
+}
+
+// Functions {{{1
+/// Renders a blueprint to a PNG image. Fails if there's nothing in it, or it's {{{2
+/// too big to draw at a readable size.
+pub fn render(blueprint: &Blueprint) -> Result<Vec<u8>, String> {
+    if blueprint.entities.is_empty() && blueprint.tiles.is_empty() {
+        return Err(String::from("That blueprint is empty, so there's nothing to draw."));
+    }
+
+    // Find the area covered by the blueprint, in tiles
+    let mut left = ::std::f64::MAX;
+    let mut top = ::std::f64::MAX;
+    let mut right = ::std::f64::MIN;
+    let mut bottom = ::std::f64::MIN;
+    for entity in blueprint.entities.iter() {
+        let (x, y, width, height) = entity_area(entity);
+        left = left.min(x);
+        top = top.min(y);
+        right = right.max(x + width);
+        bottom = bottom.max(y + height);
+    }
+    for tile in blueprint.tiles.iter() {
+        left = left.min(tile.x);
+        top = top.min(tile.y);
+        right = right.max(tile.x + 1.0);
+        bottom = bottom.max(tile.y + 1.0);
+    }
+    let left = left.floor() - MARGIN;
+    let top = top.floor() - MARGIN;
+    let columns = (right.ceil() + MARGIN - left) as usize;
+    let rows = (bottom.ceil() + MARGIN - top) as usize;
+
+    let scale = TILE_PIXELS.min(MAX_IMAGE_SIZE / columns.max(rows));
+    if scale < MIN_TILE_PIXELS {
+        return Err(format!("That blueprint is {} by {} tiles, which is too big to draw.", columns, rows));
+    }
+    let to_pixels = |tiles: f64| (tiles * scale as f64).round() as usize;
+
+    let mut canvas = Canvas::new(columns * scale, rows * scale, BACKGROUND);
+    if scale >= 4 {
+        for column in 0..columns {
+            canvas.fill(column * scale, 0, 1, rows * scale, GRID);
+        }
+        for row in 0..rows {
+            canvas.fill(0, row * scale, columns * scale, 1, GRID);
+        }
+    }
+
+    for tile in blueprint.tiles.iter() {
+        canvas.fill(to_pixels(tile.x - left), to_pixels(tile.y - top), scale, scale, FLOOR);
+    }
+
+    // Leave a gap between neighbours when there's room, so rows of the same entity stay apart
+    let gap = if scale >= 4 { 1 } else { 0 };
+    for entity in blueprint.entities.iter() {
+        let (x, y, width, height) = entity_area(entity);
+        let x = to_pixels(x - left) + gap;
+        let y = to_pixels(y - top) + gap;
+        let width = to_pixels(width).saturating_sub(gap * 2).max(1);
+        let height = to_pixels(height).saturating_sub(gap * 2).max(1);
+        let category = Category::of(&entity.name);
+        canvas.fill(x, y, width, height, category.colour());
+        if category == Category::Belt && scale >= 8 {
+            draw_arrow(&mut canvas, x, y, width, height, entity.direction);
+        }
+    }
+
+    canvas.to_png()
+}
+
+/// Gets the area an entity covers, as its top left corner then its width and {{{2
+/// height, in tiles. Turning an entity sideways swaps its width and height.
+pub fn entity_area(entity: &Entity) -> (f64, f64, f64, f64) {
+    let (width, height) = entity_size(&entity.name);
+    let (width, height) = if entity.direction == 2 || entity.direction == 6 {
+        (height, width)
+    } else {
+        (width, height)
+    };
+    (entity.x - width / 2.0, entity.y - height / 2.0, width, height)
+}
+
+/// Gets the size of an entity facing north, in tiles. Anything unknown is {{{2
+/// drawn as a single tile.
+fn entity_size(name: &str) -> (f64, f64) {
+    match name {
+        "splitter" | "fast-splitter" | "express-splitter" => (2.0, 1.0),
+        "assembling-machine-1" | "assembling-machine-2" | "assembling-machine-3" | "chemical-plant" |
+        "electric-furnace" | "centrifuge" | "lab" | "beacon" | "radar" | "solar-panel" | "storage-tank" |
+        "pumpjack" | "electric-mining-drill" | "artillery-turret" => (3.0, 3.0),
+        "stone-furnace" | "steel-furnace" | "burner-mining-drill" | "accumulator" | "substation" |
+        "big-electric-pole" | "gun-turret" | "laser-turret" | "train-stop" | "straight-rail" => (2.0, 2.0),
+        "boiler" | "heat-exchanger" => (3.0, 2.0),
+        "steam-engine" | "steam-turbine" => (3.0, 5.0),
+        "oil-refinery" | "nuclear-reactor" => (5.0, 5.0),
+        "flamethrower-turret" => (2.0, 3.0),
+        "offshore-pump" | "pump" => (1.0, 2.0),
+        "roboport" => (4.0, 4.0),
+        "curved-rail" => (4.0, 4.0), //Only roughly, the real shape isn't a box
+        "rocket-silo" => (9.0, 9.0),
+        _ => (1.0, 1.0),
+    }
+}
+
+/// Draws an arrow pointing the way an entity faces, filling the middle of its area. {{{2
+fn draw_arrow(canvas: &mut Canvas, x: usize, y: usize, width: usize, height: usize, direction: u8) {
+    for row in 0..height {
+        for column in 0..width {
+            // How far along the arrow the pixel is from its tail, and how far off its middle
+            let across_x = (column as f64 + 0.5) / width as f64 - 0.5;
+            let across_y = (row as f64 + 0.5) / height as f64 - 0.5;
+            let (along, across) = match direction {
+                2 => (across_x + 0.5, across_y),
+                4 => (across_y + 0.5, across_x),
+                6 => (0.5 - across_x, across_y),
+                _ => (0.5 - across_y, across_x),
+            };
+            if along >= 0.25 && along <= 0.75 && across.abs() <= (0.75 - along) * 0.6 {
+                canvas.set(x + column, y + row, ARROW);
+            }
+        }
+    }
+}
+
+// Tests {{{1
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Three belts heading east, an inserter, an assembling machine 2, a medium
+    // electric pole, and a stone path tile
+    const TEST_BLUEPRINT: &'static str = "0eNqVktFqwzAMRX9l6Dkea5KmkM/YaxnDSUUrsOVgq9tKyL9PcUspazvaJyPZ91wZ3RE6t8chEgu0I1AfOEG7HiHRlq2be3IYEFogQQ8FsPVzZVNC3znirfG23xGjKWEqgHiDP9Aupo8CkIWE8MjLxeGT977DqA/OJImW0xCimA6dqMMQksoCz96KentdFnDIp/I3FLE/3pZTcYUtH8YunsFWD2PLZ7D1GUucMIr27s65+AusbwCX/+/nLrzKcEXTcdUxsNmijeZ7h+jghlNzdvK4ob036HSwSL0ZgsNrp+riG3M2hNwpGCdMkqAzDlZ21+I6S+sszDFsL1JbgLO6C+29o4YvvgimufuFMWVEU9dls6pWTaOEX3kk958=";
+
+    fn decode_png(data: &[u8]) -> (usize, usize, Vec<u8>) {
+        let decoder = png::Decoder::new(data);
+        let (info, mut reader) = decoder.read_info().unwrap();
+        let mut pixels = vec![0; info.buffer_size()];
+        reader.next_frame(&mut pixels).unwrap();
+        (info.width as usize, info.height as usize, pixels)
+    }
+
+    fn pixel_at(image: &(usize, usize, Vec<u8>), x: usize, y: usize) -> [u8; 3] {
+        let start = (y * image.0 + x) * 3;
+        [image.2[start], image.2[start + 1], image.2[start + 2]]
+    }
+
+    #[test]
+    fn can_categorize_entities() {
+        assert_eq!(Category::of("express-transport-belt"), Category::Belt);
+        assert_eq!(Category::of("fast-splitter"), Category::Belt);
+        assert_eq!(Category::of("stack-filter-inserter"), Category::Inserter);
+        assert_eq!(Category::of("steel-furnace"), Category::Production);
+        assert_eq!(Category::of("pipe-to-ground"), Category::Fluid);
+        assert_eq!(Category::of("logistic-chest-requester"), Category::Storage);
+        assert_eq!(Category::of("medium-electric-pole"), Category::Power);
+        assert_eq!(Category::of("car"), Category::Other);
+    }
+
+    #[test]
+    fn can_turn_entity_areas() {
+        let mut engine = Entity {
+            name: String::from("steam-engine"),
+            x: 1.5,
+            y: 2.5,
+            direction: 0,
+            items: Default::default(),
+        };
+        assert_eq!(entity_area(&engine), (0.0, 0.0, 3.0, 5.0));
+        engine.direction = 2;
+        assert_eq!(entity_area(&engine), (-1.0, 1.0, 5.0, 3.0));
+    }
+
+    #[test]
+    fn can_render_blueprint() {
+        let blueprint = Blueprint::decode(TEST_BLUEPRINT).unwrap();
+        let image = decode_png(&render(&blueprint).unwrap());
+
+        // The blueprint covers 5 by 5 tiles, with a tile of margin around it
+        assert_eq!((image.0, image.1), (7 * TILE_PIXELS, 7 * TILE_PIXELS));
+        // Corner of the first belt, then the middle of it which has an arrow
+        assert_eq!(pixel_at(&image, TILE_PIXELS + 2, TILE_PIXELS + 2), Category::Belt.colour());
+        assert_eq!(pixel_at(&image, TILE_PIXELS * 3 / 2, TILE_PIXELS * 3 / 2), ARROW);
+        assert_eq!(pixel_at(&image, TILE_PIXELS * 5 / 2, TILE_PIXELS * 5 / 2), Category::Inserter.colour());
+        assert_eq!(pixel_at(&image, TILE_PIXELS * 5 / 2, TILE_PIXELS * 4), Category::Production.colour());
+        assert_eq!(pixel_at(&image, TILE_PIXELS * 9 / 2, TILE_PIXELS * 5 / 2), Category::Power.colour());
+        assert_eq!(pixel_at(&image, TILE_PIXELS * 11 / 2, TILE_PIXELS * 11 / 2), FLOOR);
+        assert_eq!(pixel_at(&image, 2, 2), BACKGROUND);
+    }
+
+    #[test]
+    fn shrinks_big_blueprints() {
+        let mut blueprint = Blueprint::default();
+        for x in 0..200 {
+            blueprint.entities.push(Entity {
+                name: String::from("stone-wall"),
+                x: x as f64 + 0.5,
+                y: 0.5,
+                direction: 0,
+                items: Default::default(),
+            });
+        }
+        let image = decode_png(&render(&blueprint).unwrap());
+        assert_eq!(image.0, 202 * 5);
+
+        blueprint.entities[199].x = 1000.5;
+        assert!(render(&blueprint).is_err());
+        assert!(render(&Blueprint::default()).is_err());
+    }
+}
@@ -0,0 +1,590 @@
+use serenity;
+use serenity::builder::CreateEmbed;
+use serenity::http;
+use serenity::model::Message;
+use serenity::utils::Colour;
+use serenity::framework::standard::CommandError;
+
+use serde_json::{Map, Value};
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use blueprint::*;
+use blueprint_render::render;
+use common_funcs::*;
+use recipe_book::RecipeBook;
+use recipe_system::current_recipes;
+
+/// Attachments bigger than this aren't downloaded, in bytes. Even huge blueprints are well under it.
+const MAX_ATTACHMENT_SIZE: u64 = 1_000_000;
+/// The longest blueprint string sent back in an embed, longer ones are sent as a file.
+const MAX_EMBEDDED_STRING: usize = 2000;
+
+/// Each colour of belt, and the belt, underground belt and splitter of that colour.
+const BELT_TIERS: [(&'static str, [&'static str; 3]); 3] = [
+    ("yellow", ["transport-belt", "underground-belt", "splitter"]),
+    ("red", ["fast-transport-belt", "fast-underground-belt", "fast-splitter"]),
+    ("blue", ["express-transport-belt", "express-underground-belt", "express-splitter"]),
+];
+
+/// Decodes a blueprint string, and lists what's in it and what it takes to build. {{{1
+/// Should be called with the blueprint string, or with it attached as a .txt file.
+command!(blueprint(_context, message) {
+    let _ = message.channel_id.broadcast_typing();
+
+    let (request, _) = match read_blueprint_request(&message, "blueprint", 0, |_| false) {
+        Ok(request) => request,
+        Err(reason) => {
+            send_error_embed_or_say(&message, &reason);
+            return Err(CommandError::from("Missing blueprint string, failed."));
+        }
+    };
+
+    let decoded = match Blueprint::decode(&request) {
+        Ok(decoded) => decoded,
+        Err(BlueprintError::IsBook) => {
+            send_error_embed_or_say(&message, "That's a blueprint book, try the blueprint-book command to look inside it.");
+            return Err(CommandError::from("Got a blueprint book, failed."));
+        }
+        Err(error) => {
+            send_error_embed_or_say(&message, &format!("Sorry, I couldn't read that blueprint. {}", error));
+            return Err(CommandError::from("Couldn't decode blueprint."));
+        }
+    };
+
+    send_blueprint_embed(&message, &decoded);
+});
+
+/// Lists everything in a blueprint book, or shows one blueprint from it. {{{1
+/// Should be called with the blueprint string, or with it attached as a .txt file,
+/// then optionally the number of an entry, like `3`, or `2.1` for an entry of a book in the book.
+command!(blueprint_book(_context, message) {
+    let _ = message.channel_id.broadcast_typing();
+
+    let (request, arguments) = match read_blueprint_request(&message, "blueprint-book", 1, |word| parse_book_path(word).is_some()) {
+        Ok(request) => request,
+        Err(reason) => {
+            send_error_embed_or_say(&message, &reason);
+            return Err(CommandError::from("Missing blueprint string, failed."));
+        }
+    };
+
+    let path = match arguments.first() {
+        Some(argument) => {
+            match parse_book_path(argument) {
+                Some(path) => Some(path),
+                None => {
+                    send_error_embed_or_say(&message, "Sorry, I didn't understand that entry. Give its number from the list, like `3`, or `2.1` for a book inside the book.");
+                    return Err(CommandError::from("Couldn't parse book entry."));
+                }
+            }
+        }
+        None => None,
+    };
+
+    let book = match BlueprintBook::decode(&request) {
+        Ok(book) => book,
+        Err(error) => {
+            send_error_embed_or_say(&message, &format!("Sorry, I couldn't read that blueprint book. {}", error));
+            return Err(CommandError::from("Couldn't decode blueprint book."));
+        }
+    };
+
+    match path {
+        None => send_book_embed(&message, &book),
+        Some(path) => {
+            match book.find(&path) {
+                Some(&BookItem::Blueprint(ref blueprint)) => send_blueprint_embed(&message, blueprint),
+                Some(&BookItem::Book(ref inner)) => send_book_embed(&message, inner),
+                Some(&BookItem::Planner(ref planner)) => {
+                    send_error_embed_or_say(&message, &format!("That entry is a {}, which doesn't have anything to show.", planner.replace("-", " ")));
+                }
+                None => {
+                    send_error_embed_or_say(&message, "Sorry, that book doesn't have an entry with that number.");
+                    return Err(CommandError::from("Missing book entry, failed."));
+                }
+            }
+        }
+    }
+});
+
+/// Swaps one kind of entity in a blueprint for another, and sends back the new string. {{{1
+/// Should be called with the blueprint string, then what to swap and what to swap it for.
+/// Each can be a belt colour, which swaps the belts, undergrounds and splitters, or an entity name.
+command!(blueprint_upgrade(_context, message) {
+    let _ = message.channel_id.broadcast_typing();
+
+    let (request, arguments) = match read_blueprint_request(&message, "blueprint-upgrade", 2, is_upgrade_name) {
+        Ok(request) => request,
+        Err(reason) => {
+            send_error_embed_or_say(&message, &reason);
+            return Err(CommandError::from("Missing blueprint string, failed."));
+        }
+    };
+    if arguments.len() != 2 {
+        send_error_embed_or_say(&message, "You must say what to swap and what to swap it for after the string, like `yellow red` or `assembling-machine-2 assembling-machine-3`.");
+        return Err(CommandError::from("Missing upgrade, failed."));
+    }
+
+    let replacements = match parse_replacements(&current_recipes(), &arguments[0], &arguments[1]) {
+        Ok(replacements) => replacements,
+        Err(reason) => {
+            send_error_embed_or_say(&message, &reason);
+            return Err(CommandError::from("Couldn't parse upgrade."));
+        }
+    };
+
+    if let Err(reason) = send_transformed(&message, &request, &[Transform::Replace(replacements)], "upgraded") {
+        send_error_embed_or_say(&message, &reason);
+        return Err(CommandError::from("Couldn't upgrade blueprint."));
+    }
+});
+
+/// Flips a blueprint left to right, and sends back the new string. {{{1
+/// Should be called with the blueprint string.
+command!(blueprint_mirror(_context, message) {
+    let _ = message.channel_id.broadcast_typing();
+
+    let (request, _) = match read_blueprint_request(&message, "blueprint-mirror", 0, |_| false) {
+        Ok(request) => request,
+        Err(reason) => {
+            send_error_embed_or_say(&message, &reason);
+            return Err(CommandError::from("Missing blueprint string, failed."));
+        }
+    };
+
+    if let Err(reason) = send_transformed(&message, &request, &[Transform::Mirror], "mirrored") {
+        send_error_embed_or_say(&message, &reason);
+        return Err(CommandError::from("Couldn't mirror blueprint."));
+    }
+});
+
+/// Turns a blueprint clockwise, and sends back the new string. {{{1
+/// Should be called with the blueprint string, then optionally how far to turn it,
+/// either 90, 180 or 270 degrees. It's turned 90 degrees if not given.
+command!(blueprint_rotate(_context, message) {
+    let _ = message.channel_id.broadcast_typing();
+
+    let (request, arguments) = match read_blueprint_request(&message, "blueprint-rotate", 1, is_rotation) {
+        Ok(request) => request,
+        Err(reason) => {
+            send_error_embed_or_say(&message, &reason);
+            return Err(CommandError::from("Missing blueprint string, failed."));
+        }
+    };
+
+    let turns = match arguments.first().map(|argument| argument.trim_right_matches('°')) {
+        None | Some("90") => 1,
+        Some("180") => 2,
+        Some("270") => 3,
+        Some(_) => {
+            send_error_embed_or_say(&message, "I can only turn blueprints by 90, 180 or 270 degrees.");
+            return Err(CommandError::from("Bad rotation, failed."));
+        }
+    };
+    let transforms = vec![Transform::Rotate; turns];
+
+    if let Err(reason) = send_transformed(&message, &request, &transforms, "rotated") {
+        send_error_embed_or_say(&message, &reason);
+        return Err(CommandError::from("Couldn't rotate blueprint."));
+    }
+});
+
+// Functions {{{1
+/// Sends an embed listing what's in a blueprint and what it takes to build, {{{2
+/// showing a picture of it if one can be drawn.
+fn send_blueprint_embed(message: &Message, decoded: &Blueprint) {
+    // Fall back to internal names if the recipes aren't loaded
+    let recipes = current_recipes();
+    let label = if decoded.label.is_empty() {
+        String::from("Unnamed blueprint")
+    } else {
+        decoded.label.clone()
+    };
+
+    let embed = CreateEmbed::default()
+        .title(&format!("Blueprint: {}", label))
+        .description(&format!("{} entities and {} tiles.", decoded.entities.len(), decoded.tiles.len()))
+        .field(|c| c
+               .name("Entities")
+               .value(&truncate_for_embed(&serialize_entity_counts(&recipes, &decoded.entity_counts()), 1024)))
+        .field(|c| c
+               .name("Bill of materials")
+               .value(&truncate_for_embed(&serialize_materials(&recipes, &decoded.bill_of_materials()), 1024)))
+        .timestamp(message.timestamp.to_rfc3339())
+        .color(Colour::from_rgb(10, 225, 249));
+    let embed = match decoded.version {
+        Some(version) => embed.footer(|c| c.text(&format!("Made in Factorio {}.", format_version(version)))),
+        None => embed,
+    };
+
+    let result = match render(decoded) {
+        Ok(image) => send_embed_with_image(message, embed.attachment("blueprint.png"), &image, "blueprint.png"),
+        Err(reason) => {
+            log_info!("Skipped blueprint preview: {}", reason);
+            message.channel_id.send_message(|a| a.embed(|_| embed))
+        }
+    };
+    if let Err(error) = result {
+        log_error!("Couldn't send blueprint embed: {}", error);
+        say_into_chat(&message, "Sorry, I couldn't make an embed here. Contact an admin.");
+    }
+}
+
+/// Sends an embed with an image attached to the same message, which the embed {{{2
+/// can show with `attachment://` and the file name.
+fn send_embed_with_image(message: &Message, embed: CreateEmbed, image: &[u8], filename: &str) -> serenity::Result<Message> {
+    // Serenity drops embeds from messages with files, but Discord takes them
+    // written out as json in a payload_json field
+    let mut payload = Map::new();
+    payload.insert(String::from("embed"), Value::Object(embed.0));
+    let mut map = Map::new();
+    map.insert(String::from("payload_json"), Value::String(Value::Object(payload).to_string()));
+    http::send_files(message.channel_id.0, vec![(image, filename)], map)
+}
+
+/// Makes changes to a blueprint string, then sends back the new string. Short {{{2
+/// strings are sent in an embed, and long ones as a file since they won't fit.
+/// Returns the reason to give the user if it couldn't be changed.
+fn send_transformed(message: &Message, request: &str, transforms: &[Transform], done: &str) -> Result<(), String> {
+    let mut string = String::from(request);
+    let mut changed = 0;
+    for step in transforms.iter() {
+        match transform(&string, step) {
+            Ok((transformed, count)) => {
+                string = transformed;
+                changed = count;
+            }
+            Err(error) => return Err(format!("Sorry, I couldn't read that blueprint. {}", error)),
+        }
+    }
+    if changed == 0 {
+        return Err(String::from("Nothing in that blueprint needed changing."));
+    }
+
+    let summary = format!("{} entities and tiles {}.", changed, done);
+    if string.len() <= MAX_EMBEDDED_STRING {
+        let result = message.channel_id.send_message(|a| a
+                                                     .embed(|b| b
+                                                            .title(&format!("Blueprint {}", done))
+                                                            .description(&format!("```{}```", string))
+                                                            .footer(|c| c
+                                                                    .text(&summary))
+                                                            .timestamp(message.timestamp.to_rfc3339())
+                                                            .color(Colour::from_rgb(10, 225, 249))
+                                                           ));
+        if let Err(_) = result {
+            say_into_chat(&message, "Sorry, I couldn't make an embed here. Contact an admin.");
+        }
+    } else {
+        let files = vec![(string.as_bytes(), "blueprint.txt")];
+        let content = format!("{} The new string is too long for a message, so here it is as a file.", summary);
+        if let Err(error) = message.channel_id.send_files(files, |m| m.content(&content)) {
+            log_error!("Couldn't upload transformed blueprint: {}", error);
+            say_into_chat(&message, "Sorry, I couldn't upload the new blueprint here. Contact an admin.");
+        }
+    }
+    Ok(())
+}
+
+/// Sends an embed listing every entry in a blueprint book. {{{2
+fn send_book_embed(message: &Message, book: &BlueprintBook) {
+    let recipes = current_recipes();
+    let label = if book.label.is_empty() {
+        String::from("Unnamed book")
+    } else {
+        book.label.clone()
+    };
+
+    let result = message.channel_id.send_message(|a| a
+                                                 .embed(|b| b
+                                                        .title(&format!("Blueprint book: {}", label))
+                                                        .description(&truncate_for_embed(&serialize_book_entries(&recipes, book), 2048))
+                                                        .footer(|c| c
+                                                                .text("Add the number of an entry after the string to look inside it."))
+                                                        .timestamp(message.timestamp.to_rfc3339())
+                                                        .color(Colour::from_rgb(10, 225, 249))
+                                                       ));
+    if let Err(_) = result {
+        say_into_chat(&message, "Sorry, I couldn't make an embed here. Contact an admin.");
+    }
+}
+
+/// Gets the blueprint string from a message, either the words after the command, {{{2
+/// or an attached .txt file, along with any arguments after it. Strings pasted into
+/// chat can be broken over several lines, so only up to `max_arguments` words at the
+/// end that `is_argument` recognises are taken as arguments, the rest is the string.
+/// Returns the reason to give the user if there isn't a string.
+fn read_blueprint_request<F>(message: &Message,
+                             command: &str,
+                             max_arguments: usize,
+                             is_argument: F)
+                             -> Result<(String, Vec<String>), String>
+    where F: Fn(&str) -> bool
+{
+    let request = fix_message(message.content_safe(), command);
+
+    if let Some(attachment) = message.attachments.iter().find(|attachment| attachment.filename.ends_with(".txt")) {
+        if attachment.size > MAX_ATTACHMENT_SIZE {
+            return Err(String::from("That file is too big to be a blueprint."));
+        }
+        // Everything in the message is an argument when the string is attached
+        let words = request.split_whitespace().map(String::from).collect();
+        return match attachment.download() {
+            Ok(bytes) => {
+                match String::from_utf8(bytes) {
+                    Ok(text) => Ok((text.trim().to_owned(), words)),
+                    Err(_) => Err(String::from("That file doesn't hold text, so it can't be a blueprint string.")),
+                }
+            }
+            Err(_) => Err(String::from("Sorry, I couldn't download that file.")),
+        };
+    }
+
+    let (string, arguments) = split_blueprint_arguments(&request, max_arguments, is_argument);
+    if string.is_empty() {
+        return Err(String::from("You must provide a blueprint string, either after the command or attached as a .txt file."));
+    }
+    Ok((string, arguments))
+}
+
+/// Splits recognised arguments off the end of a request, joining the rest {{{2
+/// back together as the blueprint string. Arguments are kept in order.
+fn split_blueprint_arguments<F>(request: &str, max_arguments: usize, is_argument: F) -> (String, Vec<String>)
+    where F: Fn(&str) -> bool
+{
+    let mut words: Vec<&str> = request.split_whitespace().collect();
+    let mut arguments = Vec::new();
+    // The string always comes first, so its first word is never an argument
+    while arguments.len() < max_arguments && words.len() > 1 {
+        match words.last() {
+            Some(word) if is_argument(word) => arguments.insert(0, word.to_string()),
+            _ => break,
+        }
+        words.pop();
+    }
+    (words.concat(), arguments)
+}
+
+/// Checks if a word could be one of the things to swap in an upgrade, either {{{2
+/// a belt colour or an internal name, which are always lowercase.
+fn is_upgrade_name(word: &str) -> bool {
+    BELT_TIERS.iter().any(|&(colour, _)| colour.eq_ignore_ascii_case(word)) ||
+    word.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+}
+
+/// Checks if a word is an amount of degrees to turn a blueprint by, like `90` or `180°`. {{{2
+/// Any number is taken, so turns the game can't do are reported.
+fn is_rotation(word: &str) -> bool {
+    let number = word.trim_right_matches('°');
+    !number.is_empty() && number.len() <= 3 && number.chars().all(|c| c.is_ascii_digit())
+}
+
+/// Parses the number of a book entry, like `3`, or `2.1` for the first entry {{{2
+/// of a book that's second in the book. Returns None if it isn't one.
+fn parse_book_path(argument: &str) -> Option<Vec<usize>> {
+    let mut path = Vec::new();
+    for part in argument.split('.') {
+        match part.parse::<usize>() {
+            Ok(position) if position >= 1 => path.push(position),
+            _ => return None,
+        }
+    }
+    Some(path)
+}
+
+/// Works out which entity names to swap for which. Belt colours swap every kind {{{2
+/// of belt of that colour, otherwise it's a single entity. If the recipes are loaded,
+/// the entity being swapped in has to be one of them.
+fn parse_replacements(recipes: &Option<Arc<RecipeBook>>, from: &str, to: &str) -> Result<BTreeMap<String, String>, String> {
+    let from = from.to_lowercase();
+    let to = to.to_lowercase();
+    if from == to {
+        return Err(String::from("Those are the same thing, so there's nothing to swap."));
+    }
+
+    let mut replacements = BTreeMap::new();
+    let from_belts = BELT_TIERS.iter().find(|&&(colour, _)| colour == from);
+    let to_belts = BELT_TIERS.iter().find(|&&(colour, _)| colour == to);
+    match (from_belts, to_belts) {
+        (Some(&(_, ref from_belts)), Some(&(_, ref to_belts))) => {
+            for (from, to) in from_belts.iter().zip(to_belts.iter()) {
+                replacements.insert(from.to_string(), to.to_string());
+            }
+        }
+        (None, None) => {
+            if let Some(ref recipes) = *recipes {
+                let items = recipes.item_ids();
+                if !items.contains(to.as_str()) {
+                    let items: Vec<&str> = items.into_iter().collect();
+                    let (_, closest) = get_closest_match(&items, &to);
+                    return Err(format!("I don't know of anything called `{}`, did you mean `{}`?", to, closest));
+                }
+            }
+            replacements.insert(from, to);
+        }
+        _ => return Err(String::from("Belt colours can only be swapped for other belt colours.")),
+    }
+    Ok(replacements)
+}
+
+/// Serializes the entries of a book into a numbered list, with the label, {{{2
+/// icons, and size of each.
+fn serialize_book_entries(recipes: &Option<Arc<RecipeBook>>, book: &BlueprintBook) -> String {
+    if book.entries.is_empty() {
+        return String::from("This book is empty.");
+    }
+    let mut result = String::new();
+    for (position, entry) in book.entries.iter().enumerate() {
+        result += &format!("`{}` ", position + 1);
+        result += &match entry.item {
+            BookItem::Blueprint(ref blueprint) => {
+                format!("**{}**{}, {} entities\n",
+                        if blueprint.label.is_empty() { "Unnamed blueprint" } else { &blueprint.label },
+                        serialize_icons(recipes, &blueprint.icons),
+                        blueprint.entities.len())
+            }
+            BookItem::Book(ref inner) => {
+                format!("**{}**{}, a book of {} entries\n",
+                        if inner.label.is_empty() { "Unnamed book" } else { &inner.label },
+                        serialize_icons(recipes, &inner.icons),
+                        inner.entries.len())
+            }
+            BookItem::Planner(ref planner) => format!("{}\n", pretty_name(recipes, planner)),
+        };
+    }
+    result
+}
+
+/// Serializes a list of icons after a label, or nothing if there are none. {{{2
+fn serialize_icons(recipes: &Option<Arc<RecipeBook>>, icons: &[String]) -> String {
+    if icons.is_empty() {
+        return String::new();
+    }
+    let names: Vec<String> = icons.iter().map(|icon| pretty_name(recipes, icon)).collect();
+    format!(" [{}]", names.join(", "))
+}
+
+/// Returns the pretty name of an item or entity, if the recipes are loaded. {{{2
+fn pretty_name(recipes: &Option<Arc<RecipeBook>>, id: &str) -> String {
+    match *recipes {
+        Some(ref recipes) => recipes.name_of(id),
+        None => id.to_owned(),
+    }
+}
+
+/// Serializes entity counts into a list, most common first. {{{2
+fn serialize_entity_counts(recipes: &Option<Arc<RecipeBook>>, counts: &BTreeMap<String, usize>) -> String {
+    if counts.is_empty() {
+        return String::from("None");
+    }
+    let mut sorted: Vec<(&String, &usize)> = counts.iter().collect();
+    sorted.sort_by(|a, b| b.1.cmp(a.1));
+
+    let mut result = String::new();
+    for (name, count) in sorted {
+        result += &format!("{}: {}\n", pretty_name(recipes, name), count);
+    }
+    result
+}
+
+/// Serializes a bill of materials into a list. {{{2
+fn serialize_materials(recipes: &Option<Arc<RecipeBook>>, materials: &BTreeMap<String, f64>) -> String {
+    if materials.is_empty() {
+        return String::from("Nothing.");
+    }
+    let mut result = String::new();
+    for (item, count) in materials.iter() {
+        result += &format!("{} x{}\n", pretty_name(recipes, item), count);
+    }
+    result
+}
+
+// Tests {{{1
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_split_blueprint_arguments() {
+        assert_eq!(split_blueprint_arguments("0eNq abc\nXyZ== yellow red", 2, is_upgrade_name),
+                   (String::from("0eNqabcXyZ=="), vec![String::from("yellow"), String::from("red")]));
+        // A string broken over lines isn't mistaken for arguments
+        assert_eq!(split_blueprint_arguments("0eNqAbC dEf9", 1, |word| parse_book_path(word).is_some()),
+                   (String::from("0eNqAbCdEf9"), Vec::new()));
+        assert_eq!(split_blueprint_arguments("0eNqAbC 2.1", 1, |word| parse_book_path(word).is_some()),
+                   (String::from("0eNqAbC"), vec![String::from("2.1")]));
+        assert_eq!(split_blueprint_arguments("0eNqAbC dEf 180°", 1, is_rotation),
+                   (String::from("0eNqAbCdEf"), vec![String::from("180°")]));
+        assert_eq!(split_blueprint_arguments("90", 1, is_rotation), (String::from("90"), Vec::new()));
+        assert_eq!(split_blueprint_arguments("", 0, |_| false), (String::new(), Vec::new()));
+    }
+
+    #[test]
+    fn can_parse_replacements() {
+        let replacements = parse_replacements(&None, "Yellow", "red").unwrap();
+        assert_eq!(replacements.len(), 3);
+        assert_eq!(replacements["underground-belt"], "fast-underground-belt");
+
+        let replacements = parse_replacements(&None, "assembling-machine-2", "assembling-machine-3").unwrap();
+        assert_eq!(replacements["assembling-machine-2"], "assembling-machine-3");
+
+        assert!(parse_replacements(&None, "red", "red").is_err());
+        assert!(parse_replacements(&None, "yellow", "assembling-machine-3").is_err());
+    }
+
+    #[test]
+    fn can_parse_book_paths() {
+        assert_eq!(parse_book_path("3"), Some(vec![3]));
+        assert_eq!(parse_book_path("2.1"), Some(vec![2, 1]));
+        assert_eq!(parse_book_path("0"), None);
+        assert_eq!(parse_book_path("two"), None);
+    }
+
+    #[test]
+    fn can_serialize_book_entries() {
+        let book = BlueprintBook {
+            label: String::from("Library"),
+            icons: Vec::new(),
+            entries: vec![BookEntry {
+                              index: 0,
+                              item: BookItem::Blueprint(Blueprint {
+                                  label: String::from("Smelter"),
+                                  icons: vec![String::from("stone-furnace")],
+                                  ..Blueprint::default()
+                              }),
+                          },
+                          BookEntry {
+                              index: 1,
+                              item: BookItem::Book(BlueprintBook::default()),
+                          },
+                          BookEntry {
+                              index: 2,
+                              item: BookItem::Planner(String::from("deconstruction-planner")),
+                          }],
+            version: None,
+        };
+        assert_eq!(serialize_book_entries(&None, &book),
+                   "`1` **Smelter** [stone-furnace], 0 entities\n`2` **Unnamed book**, a book of 0 entries\n`3` deconstruction-planner\n");
+    }
+
+    #[test]
+    fn can_serialize_blueprint_contents() {
+        let mut counts = BTreeMap::new();
+        counts.insert(String::from("transport-belt"), 12);
+        counts.insert(String::from("assembling-machine-2"), 3);
+        counts.insert(String::from("mystery-entity"), 20);
+
+        let recipes = Some(Arc::new(RecipeBook::load("recipes.json").unwrap()));
+        assert_eq!(serialize_entity_counts(&recipes, &counts),
+                   "mystery-entity: 20\nTransport belt: 12\nAssembling machine 2: 3\n");
+        assert_eq!(serialize_entity_counts(&None, &counts),
+                   "mystery-entity: 20\ntransport-belt: 12\nassembling-machine-2: 3\n");
+
+        let mut materials = BTreeMap::new();
+        materials.insert(String::from("rail"), 4.0);
+        assert_eq!(serialize_materials(&recipes, &materials), "Rail x4\n");
+        assert_eq!(serialize_materials(&recipes, &BTreeMap::new()), "Nothing.");
+    }
+}
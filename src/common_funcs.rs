@@ -92,10 +92,67 @@ pub fn fix_message(message: String, command: &str) -> String {
 }
 
 
+/// Removes a flag such as `--tree` from a request, returning the request {{{1
+/// without it, and whether the flag was there.
+pub fn take_flag(request: &str, flag: &str) -> (String, bool) {
+    let mut found = false;
+    let words: Vec<&str> = request
+        .split_whitespace()
+        .filter(|word| if word.to_lowercase() == flag {
+            found = true;
+            false
+        } else {
+            true
+        })
+        .collect();
+    (words.join(" "), found)
+}
+
+/// Removes a flag that takes a value, such as `--tag logistics`, from a request, {{{1
+/// returning the request without them, and the value if the flag was there.
+/// The value is empty if the flag was at the end.
+pub fn take_flag_value(request: &str, flag: &str) -> (String, Option<String>) {
+    let words: Vec<&str> = request.split_whitespace().collect();
+    let index = match words.iter().position(|word| word.to_lowercase() == flag) {
+        Some(index) => index,
+        None => return (words.join(" "), None),
+    };
+    let value = words.get(index + 1).map(|value| value.to_string()).unwrap_or_default();
+    let rest: Vec<&str> = words
+        .iter()
+        .enumerate()
+        .filter(|&(position, _)| position != index && position != index + 1)
+        .map(|(_, word)| *word)
+        .collect();
+    (rest.join(" "), Some(value))
+}
+
+/// Cuts text down to fit in an embed field or description, marking where {{{1
+/// it was cut off. Embed fields are limited to 1024 chars, descriptions to 2048.
+pub fn truncate_for_embed(text: &str, limit: usize) -> String {
+    if text.chars().count() <= limit {
+        return text.to_owned();
+    }
+    let marker = "\n...";
+    // Too short to fit the marker, so just cut it
+    if limit <= marker.len() {
+        return text.chars().take(limit).collect();
+    }
+    let mut result: String = text.chars().take(limit.saturating_sub(marker.len())).collect();
+    // Cut at the last full line, unless that would throw away much of the text
+    if let Some(index) = result.rfind('\n') {
+        if index >= result.len() * 4 / 5 {
+            result.truncate(index);
+        }
+    }
+    result.push_str(marker);
+    result
+}
+
 /// Takes a slice of strs and a request, returns the distance and the {{{1
 /// str that is the closest match. Makes the assumption that list will never
 /// be empty.
-pub fn get_closest_match<'a>(list: &[&'a str], request: &'a str) -> (usize, &'a str) {
+pub fn get_closest_match<'a>(list: &[&'a str], request: &str) -> (usize, &'a str) {
     // Short circut if there's an exact match
     if let Some(found) = list.iter().find(|&&p| p == request) {
         return (0, found);
@@ -158,4 +215,28 @@ mod tests {
         let message = String::from("+faqs get || Comprehensive test coverage!");
         assert_eq!("", fix_message(message, "faqs get"));
     }
+
+    #[test]
+    fn taking_flags() {
+        assert_eq!(take_flag("--tree rocket silo", "--tree"), (String::from("rocket silo"), true));
+        assert_eq!(take_flag("rocket silo", "--tree"), (String::from("rocket silo"), false));
+    }
+
+    #[test]
+    fn taking_flag_values() {
+        assert_eq!(take_flag_value("belts --tag Logistics --sort downloads", "--tag"),
+                   (String::from("belts --sort downloads"), Some(String::from("Logistics"))));
+        assert_eq!(take_flag_value("belts --tag", "--tag"), (String::from("belts"), Some(String::new())));
+        assert_eq!(take_flag_value("belts  trains", "--tag"), (String::from("belts trains"), None));
+    }
+
+    #[test]
+    fn truncating_for_embed() {
+        let text = "first line\nsecond line\nthird line";
+        assert_eq!(truncate_for_embed(text, 100), text);
+        assert_eq!(truncate_for_embed(text, 28), "first line\nsecond line\n...");
+        // The only line break is too far back to cut at
+        assert_eq!(truncate_for_embed(text, 20), "first line\nsecon\n...");
+        assert_eq!(truncate_for_embed(text, 2), "fi");
+    }
 }
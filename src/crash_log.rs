@@ -0,0 +1,292 @@
+/// Reads the logs the game writes, factorio-current.log and factorio-previous.log, to find out
+/// what went wrong. Every line of the log starts with the seconds since the game started,
+/// and anything printed over several lines continues on the following lines without one.
+
+/// The most lines of an error or stack trace kept, they can go on for hundreds.
+const MAX_ERROR_LINES: usize = 15;
+
+/// Failures common enough to have advice, and text in the log that gives them away. {{{1
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Problem {
+    Desync,
+    OutOfMemory,
+    Graphics,
+    MissingDependency,
+    ModError,
+}
+
+/// Text found in the log when each problem happens, matched without case.
+/// Graphics ones are kept narrow, since the renderer is named in every log when starting.
+const PROBLEM_SIGNS: [(&'static str, Problem); 12] = [
+    ("desync", Problem::Desync),
+    ("bad_alloc", Problem::OutOfMemory),
+    ("out of memory", Problem::OutOfMemory),
+    ("failed to allocate", Problem::OutOfMemory),
+    ("dxgi_error", Problem::Graphics),
+    ("device removed", Problem::Graphics),
+    ("opengl error", Problem::Graphics),
+    ("out of video memory", Problem::Graphics),
+    ("missing required dependency", Problem::MissingDependency),
+    ("requires dependency", Problem::MissingDependency),
+    ("caused a non-recoverable error", Problem::ModError),
+    ("failed to load mods", Problem::ModError),
+];
+
+impl Problem {
+    /// Returns a short name for the problem. {{{2
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Problem::Desync => "Desync",
+            Problem::OutOfMemory => "Out of memory",
+            Problem::Graphics => "Graphics driver",
+            Problem::MissingDependency => "Missing dependency",
+            Problem::ModError => "Mod error",
+        }
+    }
+
+    /// Returns what usually fixes the problem. {{{2
+    pub fn advice(&self) -> &'static str {
+        match *self {
+            Problem::Desync => {
+                "The game fell out of sync with the server. Make sure everyone has exactly the same mods and versions. \
+                 If it keeps happening, a mod is likely doing something that differs between players, try without them, \
+                 and report it with the desync report from the game folder."
+            }
+            Problem::OutOfMemory => {
+                "The game ran out of memory. Close other programs, lower the sprite resolution and video memory usage \
+                 in the graphics settings, and make sure the page file or swap isn't disabled."
+            }
+            Problem::Graphics => {
+                "The graphics card or its driver failed. Update the graphics driver, then try setting video memory usage \
+                 to low, or turning on low VRAM mode in the graphics settings."
+            }
+            Problem::MissingDependency => {
+                "A mod needs another mod that isn't installed or enabled. Install the mods it names, or disable it."
+            }
+            Problem::ModError => {
+                "A mod broke. Update it, or disable it and report the error to its author, with this log."
+            }
+        }
+    }
+}
+
+/// What was found in a log. {{{1
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CrashLog {
+    pub version: Option<String>, //Game version, like 0.16.28
+    pub build: Option<String>, //Build number, platform and where it's from, like build 33803, win64, steam
+    pub mods: Vec<(String, String)>, //Name and version of each loaded mod, in load order
+    pub error: Option<String>, //First error or stack trace, cut down to its first lines
+    pub culprit: Option<String>, //Mod the error points to
+    pub problems: Vec<Problem>, //Known failures, in the order found
+}
+
+impl CrashLog {
+    /// Reads a log. Never fails, since anything not understood is left out. {{{2
+    pub fn parse(text: &str) -> CrashLog {
+        let mut log = CrashLog::default();
+        let lines: Vec<&str> = text.lines().collect();
+
+        for (number, line) in lines.iter().enumerate() {
+            let lowercase = line.to_lowercase();
+            for &(sign, problem) in PROBLEM_SIGNS.iter() {
+                if lowercase.contains(sign) && !log.problems.contains(&problem) {
+                    log.problems.push(problem);
+                }
+            }
+
+            let message = match strip_timestamp(line) {
+                Some(message) => message,
+                None => {
+                    // The stack trace of a crash doesn't have timestamps
+                    if log.error.is_none() && line.contains("Stack trace:") {
+                        log.error = Some(collect_error(&lines[number..], true));
+                    }
+                    continue;
+                }
+            };
+
+            if log.version.is_none() {
+                if let Some(start) = message.find("Factorio ") {
+                    let (version, build) = parse_version(&message[start + "Factorio ".len()..]);
+                    log.version = Some(version);
+                    log.build = build;
+                }
+            }
+
+            if message.starts_with("Loading mod ") {
+                let mut parts = message["Loading mod ".len()..].split_whitespace();
+                if let (Some(name), Some(version)) = (parts.next(), parts.next()) {
+                    // Each mod is loaded once per stage of loading
+                    if !log.mods.iter().any(|&(ref loaded, _)| loaded == name) {
+                        log.mods.push((name.to_owned(), version.to_owned()));
+                    }
+                }
+            }
+
+            if log.error.is_none() && message.starts_with("Error ") {
+                log.error = Some(collect_error(&lines[number..], false));
+            }
+        }
+
+        log.culprit = log.error.as_ref().and_then(|error| find_culprit(error));
+        log
+    }
+
+    /// Returns the version of a loaded mod, if it was loaded. {{{2
+    pub fn mod_version(&self, name: &str) -> Option<&str> {
+        self.mods
+            .iter()
+            .find(|&&(ref loaded, _)| loaded == name)
+            .map(|&(_, ref version)| version.as_str())
+    }
+}
+
+// Functions {{{1
+/// Removes the timestamp from the start of a line, returning the rest. {{{2
+/// Returns None if the line doesn't start with one.
+fn strip_timestamp(line: &str) -> Option<&str> {
+    let line = line.trim_left();
+    let end = match line.find(' ') {
+        Some(end) => end,
+        None => return None,
+    };
+    let timestamp = &line[..end];
+    if !timestamp.is_empty() && timestamp.contains('.') &&
+       timestamp.chars().all(|c| c.is_digit(10) || c == '.') {
+        Some(line[end..].trim_left())
+    } else {
+        None
+    }
+}
+
+/// Splits the text after "Factorio " in the first line into the version, {{{2
+/// and what's in the brackets after it.
+fn parse_version(text: &str) -> (String, Option<String>) {
+    let version = text.split_whitespace().next().unwrap_or("").to_owned();
+    let build = match (text.find('('), text.find(')')) {
+        (Some(start), Some(end)) if start < end => Some(text[start + 1..end].to_owned()),
+        _ => None,
+    };
+    (version, build)
+}
+
+/// Takes the first lines of an error, which carries on until the next line {{{2
+/// with a timestamp. Stack traces also end at a blank line.
+fn collect_error(lines: &[&str], is_stack_trace: bool) -> String {
+    let mut error = vec![lines[0].trim()];
+    for line in lines[1..].iter() {
+        if strip_timestamp(line).is_some() || error.len() >= MAX_ERROR_LINES {
+            break;
+        }
+        if line.trim().is_empty() {
+            if is_stack_trace {
+                break;
+            }
+        } else {
+            error.push(line.trim());
+        }
+    }
+    error.join("\n")
+}
+
+/// Finds the mod an error points to, either by name, or by a path to one of its {{{2
+/// files, which look like __modname__/control.lua. The base game and core aren't mods.
+pub fn find_culprit(error: &str) -> Option<String> {
+    let mut rest = error;
+    while let Some(start) = rest.find("__") {
+        rest = &rest[start + 2..];
+        let end = match rest.find("__") {
+            Some(end) => end,
+            None => break,
+        };
+        let name = &rest[..end];
+        rest = &rest[end + 2..];
+        if !name.is_empty() && name != "base" && name != "core" && !name.contains(char::is_whitespace) {
+            return Some(name.to_owned());
+        }
+    }
+    // Otherwise the error may name the mod, by its title rather than its name
+    if let Some(start) = error.find("Failed to load mod \"") {
+        let rest = &error[start + "Failed to load mod \"".len()..];
+        if let Some(end) = rest.find('"') {
+            return Some(rest[..end].to_owned());
+        }
+    }
+    if let Some(start) = error.find("The mod ") {
+        let rest = &error[start + "The mod ".len()..];
+        if let Some(end) = rest.find(" (") {
+            return Some(rest[..end].to_owned());
+        }
+    }
+    None
+}
+
+// Tests {{{1
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MOD_CRASH: &'static str = "   0.000 2018-03-04 12:00:00; Factorio 0.16.28 (build 33803, win64, steam)
+   0.001 Operating system: Windows 10 (build 16299)
+   0.512 Loading mod core 0.0.0 (data.lua)
+   0.600 Loading mod base 0.16.28 (data.lua)
+   0.700 Loading mod bobores 0.16.0 (data.lua)
+   0.800 Loading mod base 0.16.28 (data-updates.lua)
+   0.900 Loading mod bobores 0.16.0 (data-updates.lua)
+ 120.512 Error MainLoop.cpp:1016: Exception at tick 4521: The mod Bob's Ores (0.16.0) caused a non-recoverable error.
+Please report this error to the mod author.
+
+Error while running event bobores::on_tick (ID 0)
+__bobores__/control.lua:42: attempt to index local 'entity' (a nil value)
+ 120.600 Goodbye";
+
+    const SEGFAULT: &'static str = "   0.000 2018-03-04 12:00:00; Factorio 0.16.28 (build 33803, linux64, headless)
+   0.600 Loading mod base 0.16.28 (data.lua)
+Factorio crashed. Generating symbolized stacktrace, please wait ...
+Stack trace:
+/usr/bin/factorio(+0x1234)
+/usr/bin/factorio(+0x5678)
+
+Received SIGSEGV. std::bad_alloc";
+
+    #[test]
+    fn can_parse_mod_crash() {
+        let log = CrashLog::parse(MOD_CRASH);
+        assert_eq!(log.version, Some(String::from("0.16.28")));
+        assert_eq!(log.build, Some(String::from("build 33803, win64, steam")));
+        assert_eq!(log.mods.len(), 3);
+        assert_eq!(log.mod_version("bobores"), Some("0.16.0"));
+        assert_eq!(log.error.as_ref().unwrap().lines().count(), 4);
+        assert!(log.error.as_ref().unwrap().contains("Exception at tick 4521"));
+        assert_eq!(log.culprit, Some(String::from("bobores")));
+        assert_eq!(log.problems, vec![Problem::ModError]);
+    }
+
+    #[test]
+    fn can_parse_stack_trace() {
+        let log = CrashLog::parse(SEGFAULT);
+        assert_eq!(log.build, Some(String::from("build 33803, linux64, headless")));
+        assert_eq!(log.error, Some(String::from("Stack trace:\n/usr/bin/factorio(+0x1234)\n/usr/bin/factorio(+0x5678)")));
+        assert_eq!(log.culprit, None);
+        assert_eq!(log.problems, vec![Problem::OutOfMemory]);
+    }
+
+    #[test]
+    fn can_spot_known_problems() {
+        let log = CrashLog::parse("   1.000 Info Renderer: OpenGL 4.5
+  50.000 Error DeviceD3D11.cpp:100: DXGI_ERROR_DEVICE_REMOVED
+  51.000 Info MultiplayerManager.cpp:200: Desync detected");
+        assert_eq!(log.problems, vec![Problem::Graphics, Problem::Desync]);
+    }
+
+    #[test]
+    fn can_find_culprits() {
+        assert_eq!(find_culprit("__base__/prototypes/item.lua:5: __angelsrefining__/data.lua:12: bad"),
+                   Some(String::from("angelsrefining")));
+        assert_eq!(find_culprit("Failed to load mod \"FARL\": data.lua:1"), Some(String::from("FARL")));
+        assert_eq!(find_culprit("The mod Bob's Ores (0.16.0) caused a non-recoverable error."),
+                   Some(String::from("Bob's Ores")));
+        assert_eq!(find_culprit("__base__/data.lua:1"), None);
+    }
+}
@@ -0,0 +1,129 @@
+use serenity::model::Message;
+use serenity::utils::Colour;
+use serenity::framework::standard::CommandError;
+
+use common_funcs::*;
+use crash_log::*;
+
+/// Attachments bigger than this aren't downloaded, in bytes. Discord doesn't allow bigger uploads anyway.
+const MAX_LOG_SIZE: u64 = 8_000_000;
+
+/// Reads an attached factorio-current.log or factorio-previous.log, and reports {{{1
+/// the game version, loaded mods, the first error and advice for known problems.
+command!(crash_log(_context, message) {
+    let _ = message.channel_id.broadcast_typing();
+
+    let attachment = match message.attachments.iter().find(|attachment| attachment.filename.ends_with(".log") ||
+                                                                         attachment.filename.ends_with(".txt")) {
+        Some(attachment) => attachment,
+        None => {
+            send_error_embed_or_say(&message, "You must attach a log to the message, it's factorio-current.log in the game's folder.");
+            return Err(CommandError::from("Missing log attachment, failed."));
+        }
+    };
+    if attachment.size > MAX_LOG_SIZE {
+        send_error_embed_or_say(&message, "That log is too big for me to read.");
+        return Err(CommandError::from("Log too big, failed."));
+    }
+    let text = match attachment.download() {
+        Ok(bytes) => String::from_utf8_lossy(&bytes).into_owned(),
+        Err(_) => {
+            send_error_embed_or_say(&message, "Sorry, I couldn't download that log.");
+            return Err(CommandError::from("Couldn't download log."));
+        }
+    };
+
+    let log = CrashLog::parse(&text);
+    let title = match log.version {
+        Some(ref version) => format!("Log from Factorio {}", version),
+        None => String::from("Log from an unknown version of Factorio"),
+    };
+
+    let result = message.channel_id.send_message(|a| a
+                                                 .embed(|b| b
+                                                        .title(&title)
+                                                        .description(log.build.as_ref().map(|build| build.as_str()).unwrap_or("Unknown build."))
+                                                        .field(|c| c
+                                                               .name(&format!("Mods ({})", log.mods.len()))
+                                                               .value(&truncate_for_embed(&serialize_mods(&log), 1024)))
+                                                        .field(|c| c
+                                                               .name("First error")
+                                                               .value(&serialize_error(&log)))
+                                                        .field(|c| c
+                                                               .name("Advice")
+                                                               .value(&truncate_for_embed(&serialize_advice(&log), 1024)))
+                                                        .timestamp(message.timestamp.to_rfc3339())
+                                                        .color(Colour::from_rgb(10, 225, 249))
+                                                       ));
+    if let Err(_) = result {
+        say_into_chat(&message, "Sorry, I couldn't make an embed here. Contact an admin.");
+    }
+});
+
+// Functions {{{1
+/// Serializes the loaded mods, leaving out the base game and core. {{{2
+fn serialize_mods(log: &CrashLog) -> String {
+    let mods: Vec<String> = log.mods
+        .iter()
+        .filter(|&&(ref name, _)| name != "base" && name != "core")
+        .map(|&(ref name, ref version)| format!("{} {}", name, version))
+        .collect();
+    if mods.is_empty() {
+        String::from("None, only the base game.")
+    } else {
+        mods.join(", ")
+    }
+}
+
+/// Serializes the first error in a code block, and which mod it points to. {{{2
+fn serialize_error(log: &CrashLog) -> String {
+    let error = match log.error {
+        Some(ref error) => error,
+        None => return String::from("No errors found. If the game still crashed, make sure it's the log from that run."),
+    };
+    // Leave room for the code block and the culprit
+    let mut result = format!("```{}```", truncate_for_embed(error, 850));
+    if let Some(ref culprit) = log.culprit {
+        match log.mod_version(culprit) {
+            Some(version) => result += &format!("\nThis points to the mod **{}** {}.", culprit, version),
+            None => result += &format!("\nThis points to the mod **{}**.", culprit),
+        }
+    }
+    result
+}
+
+/// Serializes advice for each known problem found. {{{2
+fn serialize_advice(log: &CrashLog) -> String {
+    if log.problems.is_empty() {
+        return String::from("Nothing I recognise. Someone in the help channel should be able to make sense of the error.");
+    }
+    let mut result = String::new();
+    for problem in log.problems.iter() {
+        result += &format!("**{}**: {}\n", problem.name(), problem.advice());
+    }
+    result
+}
+
+// Tests {{{1
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_serialize_log_report() {
+        let log = CrashLog {
+            version: Some(String::from("0.16.28")),
+            build: None,
+            mods: vec![(String::from("base"), String::from("0.16.28")),
+                       (String::from("bobores"), String::from("0.16.0"))],
+            error: Some(String::from("__bobores__/control.lua:42: attempt to index a nil value")),
+            culprit: Some(String::from("bobores")),
+            problems: vec![Problem::ModError],
+        };
+        assert_eq!(serialize_mods(&log), "bobores 0.16.0");
+        assert_eq!(serialize_error(&log),
+                   "```__bobores__/control.lua:42: attempt to index a nil value```\nThis points to the mod **bobores** 0.16.0.");
+        assert!(serialize_advice(&log).starts_with("**Mod error**: "));
+        assert!(serialize_mods(&CrashLog::default()).starts_with("None"));
+    }
+}
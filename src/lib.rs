@@ -1,32 +1,60 @@
 extern crate atom_syndication;
+extern crate base64;
+extern crate byteorder;
 extern crate chrono;
+extern crate flate2;
 extern crate json;
 #[macro_use]
 extern crate lazy_static;
+extern crate png;
 extern crate rand;
 extern crate reqwest;
+extern crate semver;
+extern crate serde_json;
 #[macro_use]
 extern crate serenity;
+extern crate zip;
 
 // log mod must be declared before the others for the macros to work
 #[macro_use]
 pub mod log;
 
+pub mod binary_reader;
+pub mod blueprint;
+pub mod blueprint_render;
+mod blueprint_system;
 pub mod common_funcs;
 pub mod constants;
+pub mod crash_log;
+mod crash_log_system;
 mod faq_system;
 mod levenshtein;
 mod linkmod;
+pub mod map_exchange;
+mod map_exchange_system;
+pub mod mod_dependency;
+mod mod_watch;
 mod prefix_control;
+pub mod recipe_book;
 mod recipe_system;
+pub mod save_file;
 mod simple_commands;
 mod web_requesting;
 
+pub use linkmod::{handle_search_reaction, install_portal_cache};
+pub use mod_watch::{install_mod_watches, watch_mod_updates};
+pub use recipe_system::{install_recipes, watch_recipes};
+
 pub mod commands {
+    pub use blueprint_system::{blueprint, blueprint_book, blueprint_upgrade, blueprint_mirror,
+                               blueprint_rotate};
+    pub use crash_log_system::crash_log;
     pub use faq_system::{faqs, faq_add, faq_get, faq_delete, faq_deleteall, faq_set};
-    pub use linkmod::{linkmod, modder};
+    pub use linkmod::{linkmod, modchangelog, modcompare, moddeps, modder, modlist, save};
+    pub use map_exchange_system::mapstring;
+    pub use mod_watch::{watch_mod, unwatch_mod, mod_watches};
     pub use prefix_control::register_prefix;
-    pub use recipe_system::recipe;
+    pub use recipe_system::{recipe, ratio, uses, tech, recipe_search, reload_data};
     pub use simple_commands::{ping, stop_process, search_api, info, whois, uptime, host, page,
                               fff_old};
     pub use web_requesting::{fff, version};
@@ -3,20 +3,70 @@ use json::{self, JsonValue};
 use reqwest;
 
 use chrono::prelude::*;
+use chrono::Duration;
 
-use serenity::model::Message;
+use serenity::builder::CreateEmbed;
+use serenity::model::{ChannelId, Message, MessageId, Reaction, ReactionType, UserId};
 use serenity::utils::Colour;
 use serenity::framework::standard::CommandError;
 
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
 use std::io::Read;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
 
 use common_funcs::*;
 use constants::*;
 use levenshtein::*;
+use mod_dependency::*;
+use save_file::SaveHeader;
+
+/// The most mods in a mod list looked up on the portal, since each takes a request.
+const MAX_MOD_LOOKUPS: usize = 25;
+/// Attachments bigger than this aren't downloaded, in bytes. Mod lists are only a few kilobytes.
+const MAX_MOD_LIST_SIZE: u64 = 100_000;
+/// Saves bigger than this aren't downloaded, in bytes. Discord doesn't allow bigger uploads anyway.
+const MAX_SAVE_SIZE: u64 = 8_000_000;
+/// The most mods compared at once, which fills two rows of the embed.
+const MAX_COMPARED_MODS: usize = 6;
+
+lazy_static! {
+    /// Responses from the mod portal, so popular mods and modders aren't fetched every time.
+    static ref PORTAL_CACHE: Arc<Mutex<PortalCache>> = Arc::new(Mutex::new(PortalCache::default()));
+    /// Search results that can still be paged through, by the message showing them.
+    static ref SEARCH_PAGES: Arc<Mutex<HashMap<MessageId, SearchPages>>> = Arc::new(Mutex::new(HashMap::new()));
+}
+
+const CACHE_FILE: &'static str = "portal_cache.json";
+/// Written first, then renamed over the cache file, so a crash mid-write can't corrupt it.
+const CACHE_TEMP_FILE: &'static str = "portal_cache.json.tmp";
+/// The least time between backups of the cache, in seconds.
+const CACHE_BACKUP_INTERVAL: i64 = 60;
+/// The most responses kept. When full, expired ones go first, then the oldest.
+const MAX_CACHE_ENTRIES: usize = 500;
+/// How long responses stay fresh, in seconds. Searches change whenever a mod is updated,
+/// while modders rarely appear or disappear.
+const SEARCH_TTL: i64 = 600;
+const FULL_MOD_TTL: i64 = 600;
+const MODDER_TTL: i64 = 3600;
+
+/// The most pages of results fetched for filtered searches, each of up to 100 mods.
+const MAX_SEARCH_PAGES: u64 = 10;
+/// How many search results are shown at once, one for each number reaction.
+const SEARCH_PAGE_SIZE: usize = 10;
+/// How long the reactions on search results work for, in seconds.
+const SEARCH_PAGES_TIMEOUT: u64 = 300;
+const PREVIOUS_PAGE: &'static str = "\u{25c0}";
+const NEXT_PAGE: &'static str = "\u{25b6}";
+const NUMBER_REACTIONS: [&'static str; SEARCH_PAGE_SIZE] = ["1\u{20e3}", "2\u{20e3}", "3\u{20e3}", "4\u{20e3}",
+                                                            "5\u{20e3}", "6\u{20e3}", "7\u{20e3}", "8\u{20e3}",
+                                                            "9\u{20e3}", "\u{1f51f}"];
 
 /// Structs used to hold a bunch of data about a mod/modder, for easy passing {{{1
 #[derive(Debug, Clone)]
-struct Mod {
+pub struct Mod {
     pub creation_date: String,
     pub last_updated: String,
     pub thumb: String, //The thumb image of the mod
@@ -29,10 +79,22 @@ struct Mod {
     pub source_path: String,
     pub homepage: String,
     pub dependencies: String,
+    pub dependency_list: Vec<Dependency>, //Dependencies of the latest version
     pub summary: String,
     pub title: String, //Pretty title of the mod
     pub tag: Option<String>, //What tag the mod has
     pub download_link: String, //A direct download link to the newest version
+    pub releases: Vec<Release>, //Every version, newest first. Only full requests list them
+    pub changelog: String, //Empty unless from a full request
+}
+
+/// A version of a mod that's been released on the portal. {{{1
+#[derive(Debug, Clone, PartialEq)]
+pub struct Release {
+    pub version: String,
+    pub factorio_version: String,
+    pub released_on: String, //Just the date
+    pub download_link: String,
 }
 
 #[derive(Debug)]
@@ -46,71 +108,299 @@ struct Modder {
     pub most_popular_mod: Mod,
 }
 
+/// A response from the portal, and when it was fetched. {{{1
+#[derive(Debug, Clone, PartialEq)]
+struct CacheEntry {
+    pub json: JsonValue,
+    pub fetched: DateTime<Utc>,
+    pub ttl: i64, //Seconds the response stays fresh for
+}
+
+impl CacheEntry {
+    /// Returns true if the response is young enough to use without asking the portal. {{{2
+    fn is_fresh(&self, now: DateTime<Utc>) -> bool {
+        now.signed_duration_since(self.fetched) < Duration::seconds(self.ttl)
+    }
+}
+
+/// Responses from the portal by what was asked, such as search:bobores. {{{1
+/// Expired responses are kept, to fall back on while the portal is down.
+#[derive(Debug, Default)]
+struct PortalCache {
+    pub entries: HashMap<String, CacheEntry>,
+    pub persist: bool, //Whether to back up to the cache file on changes
+    pub backed_up: Option<DateTime<Utc>>,
+}
+
+impl PortalCache {
+    /// Returns true if the cache should be backed up, at most once per CACHE_BACKUP_INTERVAL. {{{2
+    fn backup_due(&self, now: DateTime<Utc>) -> bool {
+        self.persist && match self.backed_up {
+            Some(backed_up) => now.signed_duration_since(backed_up) >= Duration::seconds(CACHE_BACKUP_INTERVAL),
+            None => true,
+        }
+    }
+
+    /// Adds a response, making room for it if the cache is full. {{{2
+    fn insert(&mut self, key: &str, entry: CacheEntry, now: DateTime<Utc>) {
+        if !self.entries.contains_key(key) && self.entries.len() >= MAX_CACHE_ENTRIES {
+            self.entries.retain(|_, entry| entry.is_fresh(now));
+            if self.entries.len() >= MAX_CACHE_ENTRIES {
+                let oldest = self.entries
+                    .iter()
+                    .min_by_key(|&(_, entry)| entry.fetched)
+                    .map(|(key, _)| key.clone());
+                if let Some(oldest) = oldest {
+                    self.entries.remove(&oldest);
+                }
+            }
+        }
+        self.entries.insert(key.to_owned(), entry);
+    }
+
+    /// Turns the cache into json, for the cache file. {{{2
+    fn to_json(&self) -> JsonValue {
+        let mut json = JsonValue::new_object();
+        for (key, entry) in self.entries.iter() {
+            json[key.as_str()]["json"] = entry.json.clone();
+            json[key.as_str()]["fetched"] = entry.fetched.to_rfc3339().into();
+            json[key.as_str()]["ttl"] = entry.ttl.into();
+        }
+        json
+    }
+
+    /// Reads responses from the cache file, skipping any that can't be read. {{{2
+    fn read_json(&mut self, json: &JsonValue) {
+        for (key, value) in json.entries() {
+            let fetched = value["fetched"]
+                .as_str()
+                .and_then(|fetched| DateTime::parse_from_rfc3339(fetched).ok());
+            if let (Some(fetched), Some(ttl)) = (fetched, value["ttl"].as_i64()) {
+                self.entries.insert(key.to_owned(),
+                                    CacheEntry {
+                                        json: value["json"].clone(),
+                                        fetched: fetched.with_timezone(&Utc),
+                                        ttl,
+                                    });
+            }
+        }
+    }
+}
+
+/// Orders search results can be put in, newest or most first. {{{1
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum SearchSort {
+    Downloads,
+    Updated,
+    Created,
+}
+
+/// What search results are narrowed down to, and the order they're put in. {{{1
+#[derive(Debug, Clone, Default, PartialEq)]
+struct SearchFilters {
+    pub version: Option<String>, //Game version the latest release must be for
+    pub tag: Option<String>,
+    pub sort: Option<SearchSort>, //Otherwise the portal's order is kept, with outdated mods last
+}
+
+impl SearchFilters {
+    /// Takes the flags for filters out of a request, returning the rest of it. {{{2
+    /// Returns why if any of them can't be used.
+    fn take_from(request: &str) -> Result<(String, SearchFilters), String> {
+        let (request, version) = take_flag_value(request, "--version");
+        let (request, tag) = take_flag_value(&request, "--tag");
+        let (request, sort) = take_flag_value(&request, "--sort");
+
+        if let Some(ref version) = version {
+            if parse_version(version).is_none() {
+                return Err(String::from("Give a game version after `--version`, like `--version 0.16`."));
+            }
+        }
+        if tag.as_ref().map(String::is_empty) == Some(true) {
+            return Err(String::from("Give a tag after `--tag`, like `--tag logistics`."));
+        }
+        let sort = match sort.map(|sort| sort.to_lowercase()) {
+            None => None,
+            Some(ref sort) if sort == "downloads" => Some(SearchSort::Downloads),
+            Some(ref sort) if sort == "updated" => Some(SearchSort::Updated),
+            Some(ref sort) if sort == "created" => Some(SearchSort::Created),
+            Some(_) => return Err(String::from("Results can be sorted by `downloads`, `updated` or `created`.")),
+        };
+        Ok((request, SearchFilters { version, tag, sort }))
+    }
+
+    fn is_empty(&self) -> bool {
+        *self == SearchFilters::default()
+    }
+
+    /// Checks if a search result fits the filters. {{{2
+    fn matches(&self, entry: &JsonValue) -> bool {
+        if let Some(ref version) = self.version {
+            match entry["latest_release"]["factorio_version"].as_str() {
+                Some(mod_version) if supports_version(mod_version, version) => {}
+                _ => return false,
+            }
+        }
+        if let Some(ref tag) = self.tag {
+            // Tags have both a name like logistics, and a title like Logistics
+            let tagged = entry["tags"].members().any(|entry_tag| {
+                entry_tag["name"].as_str().map(|name| name.eq_ignore_ascii_case(tag)) == Some(true) ||
+                entry_tag["title"].as_str().map(|title| title.eq_ignore_ascii_case(tag)) == Some(true)
+            });
+            if !tagged {
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Keeps the search results that fit the filters, in the order asked for. {{{2
+    fn apply(&self, results: &JsonValue) -> Vec<JsonValue> {
+        let mut kept: Vec<JsonValue> = results.members().filter(|entry| self.matches(entry)).cloned().collect();
+        // Dates are written so they sort as text
+        match self.sort {
+            Some(SearchSort::Downloads) => {
+                kept.sort_by_key(|entry| ::std::cmp::Reverse(entry["downloads_count"].as_u64().unwrap_or(0)))
+            }
+            Some(SearchSort::Updated) => kept.sort_by(|a, b| b["updated_at"].as_str().cmp(&a["updated_at"].as_str())),
+            Some(SearchSort::Created) => kept.sort_by(|a, b| b["created_at"].as_str().cmp(&a["created_at"].as_str())),
+            None => {
+                let (current, outdated): (Vec<JsonValue>, Vec<JsonValue>) =
+                    kept.into_iter().partition(|entry| !is_outdated(entry));
+                kept = current.into_iter().chain(outdated).collect();
+            }
+        }
+        kept
+    }
+
+    /// Describes the filters, such as "for 0.16, most downloaded first". {{{2
+    fn describe(&self) -> String {
+        let mut parts = Vec::new();
+        if let Some(ref version) = self.version {
+            parts.push(format!("for {}", version));
+        }
+        if let Some(ref tag) = self.tag {
+            parts.push(format!("tagged {}", tag));
+        }
+        match self.sort {
+            Some(SearchSort::Downloads) => parts.push(String::from("most downloaded first")),
+            Some(SearchSort::Updated) => parts.push(String::from("last updated first")),
+            Some(SearchSort::Created) => parts.push(String::from("newest first")),
+            None => {}
+        }
+        parts.join(", ")
+    }
+}
+
+/// What a reaction on search results asks for. {{{1
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum PageAction {
+    Previous,
+    Next,
+    Open(usize), //Number of the result on the page, starting at 1
+}
+
+impl PageAction {
+    /// Finds what a reaction asks for, if it's one of the ones added to search results. {{{2
+    fn from_emoji(emoji: &str) -> Option<PageAction> {
+        if emoji == PREVIOUS_PAGE {
+            return Some(PageAction::Previous);
+        }
+        if emoji == NEXT_PAGE {
+            return Some(PageAction::Next);
+        }
+        // Some clients add a variation selector to the keycaps
+        let emoji = emoji.replace("\u{fe0f}", "");
+        NUMBER_REACTIONS
+            .iter()
+            .position(|&number| number == emoji)
+            .map(|index| PageAction::Open(index + 1))
+    }
+}
+
+/// Search results shown a page at a time. {{{1
+#[derive(Debug, Clone)]
+struct SearchPages {
+    pub results: Vec<JsonValue>,
+    pub filters: String, //Description of the filters used
+    pub page: usize, //Starting at 0
+    pub cached_at: Option<DateTime<Utc>>,
+    pub created: Instant,
+    pub bot: UserId, //Whoever sent the results, so its own reactions are ignored
+}
+
+impl SearchPages {
+    fn new(results: Vec<JsonValue>, filters: &SearchFilters, cached_at: Option<DateTime<Utc>>) -> SearchPages {
+        SearchPages {
+            results,
+            filters: filters.describe(),
+            page: 0,
+            cached_at,
+            created: Instant::now(),
+            bot: UserId(0),
+        }
+    }
+
+    fn page_count(&self) -> usize {
+        ((self.results.len() + SEARCH_PAGE_SIZE - 1) / SEARCH_PAGE_SIZE).max(1)
+    }
+
+    /// Moves to the next or previous page. Returns false if there isn't one. {{{2
+    fn turn(&mut self, forward: bool) -> bool {
+        if forward && self.page + 1 < self.page_count() {
+            self.page += 1;
+            true
+        } else if !forward && self.page > 0 {
+            self.page -= 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Finds a result on the current page by its number, starting at 1. {{{2
+    fn pick(&self, number: usize) -> Option<&JsonValue> {
+        if number == 0 || number > SEARCH_PAGE_SIZE {
+            return None;
+        }
+        self.results.get(self.page * SEARCH_PAGE_SIZE + number - 1)
+    }
+
+    fn is_expired(&self, now: Instant) -> bool {
+        now.duration_since(self.created).as_secs() >= SEARCH_PAGES_TIMEOUT
+    }
+
+    /// Fills in an embed with the current page. {{{2
+    fn fill_embed(&self, embed: CreateEmbed) -> CreateEmbed {
+        let footer = format!("Page {} of {}. React with a number to open that mod, for the next {} minutes. {}",
+                             self.page + 1,
+                             self.page_count(),
+                             SEARCH_PAGES_TIMEOUT / 60,
+                             cache_note(self.cached_at));
+        let title = if self.filters.is_empty() {
+            format!("Search results: {} found", self.results.len())
+        } else {
+            format!("Search results: {} found {}", self.results.len(), self.filters)
+        };
+        embed.title(&title)
+            .description(&truncate_for_embed(&serialize_search_page(&self.results, self.page), 2048))
+            .footer(|c| c.text(footer.trim()))
+            .color(Colour::from_rgb(255, 34, 108))
+    }
+}
+
 /// Creates an embed based on the recieved mod data. {{{1
 /// Returns true if successful.
-fn make_mod_embed(modification: Mod, message: &Message) -> bool {
-    let tag_str = modification.tag.as_ref().map(String::as_str).unwrap_or(
-        "Not tagged.",
-    );
-
-    let result = message.channel_id.send_message(|a| {
+fn make_mod_embed(modification: Mod, channel_id: ChannelId, timestamp: &str, cached_at: Option<DateTime<Utc>>) -> bool {
+    let result = channel_id.send_message(|a| {
         a.embed(|b| {
-            b.description(&modification.summary)
-                .author(|c| c.name(&modification.title).url(&modification.link))
-                .thumbnail(&modification.thumb)
-                .color(Colour::from_rgb(255, 34, 108))
-                .timestamp(message.timestamp.to_rfc3339())
-                .field(|c| {
-                    c.name("Author").value(&format!(
-                        "[{a}](https://mods.factorio.com/mods/{a})",
-                        a = modification.author
-                    ).replace(" ", "%20"))
-                })
-                .field(|c| {
-                    c.name("Downloads").value(&format!(
-                        "{} downloads",
-                        modification.download_count
-                    ))
-                })
-                .field(|c| c.name("Source code").value(&modification.source_path))
-                .field(|c| c.name("Homepage").value(&modification.homepage))
-                .field(|c| c.name("Last updated").value(&modification.last_updated))
-                .field(|c| c.name("Dependencies").value(&modification.dependencies))
-                .field(|c| c.name("Tagged").value(&tag_str))
-                .field(|c| c.name("Created on").value(&modification.creation_date))
-                .field(|c| {
-                    c.name("Latest mod version").value(&format!(
-                        "{} - [Download]({})",
-                        modification.latest_version,
-                        modification.download_link
-                    ))
-                })
-                .field(|c| {
-                    c.name("Factorio version").value(
-                        &modification.factorio_version,
-                    )
-                })
-                .field(|c| {
-                    let popularity = match modification.download_count {
-                        0...50 => ":asterisk: New mod! :asterisk:",
-                        50...200 => ":seedling: Gathering a following... :seedling:",
-                        201...1000 => ":third_place: A decent amount of downloads. :third_place:",
-                        1001...2000 => ":second_place: Pretty popular! :second_place:",
-                        2001...5000 => ":first_place: Very very popular! :first_place:",
-                        5001...10_000 => ":heart: Extremely popular! :heart:",
-                        10_001...15_000 => {
-                            ":military_medal: Beloved by the community! :military_medal:"
-                        }
-                        15_001...20_000 => ":medal: Nearing the Hall of Fame! :medal:",
-                        // Anything beyond that
-                        _ => ":trophy: Hall of Fame! :trophy:",
-                    };
-                    c.name("Popularity").value(popularity)
-                })
+            build_mod_embed(b, &modification)
+                .timestamp(timestamp)
                 .footer(|c| {
-                    c.text(
-                        "For info on the author of this mod, use the modder command.",
-                    )
+                    c.text(&format!(
+                        "For info on the author of this mod, use the modder command. {}",
+                        cache_note(cached_at)
+                    ).trim())
                 })
         })
     });
@@ -121,8 +411,67 @@ fn make_mod_embed(modification: Mod, message: &Message) -> bool {
     true
 }
 
+/// Fills in an embed with the data of a mod, so it can be sent anywhere. {{{1
+pub fn build_mod_embed(embed: CreateEmbed, modification: &Mod) -> CreateEmbed {
+    let tag_str = modification.tag.as_ref().map(String::as_str).unwrap_or(
+        "Not tagged.",
+    );
+
+    embed.description(&modification.summary)
+        .author(|c| c.name(&modification.title).url(&modification.link))
+        .thumbnail(&modification.thumb)
+        .color(Colour::from_rgb(255, 34, 108))
+        .field(|c| {
+            c.name("Author").value(&format!(
+                "[{a}](https://mods.factorio.com/mods/{a})",
+                a = modification.author
+            ).replace(" ", "%20"))
+        })
+        .field(|c| {
+            c.name("Downloads").value(&format!(
+                "{} downloads",
+                modification.download_count
+            ))
+        })
+        .field(|c| c.name("Source code").value(&modification.source_path))
+        .field(|c| c.name("Homepage").value(&modification.homepage))
+        .field(|c| c.name("Last updated").value(&modification.last_updated))
+        .field(|c| c.name("Dependencies").value(&modification.dependencies))
+        .field(|c| c.name("Tagged").value(&tag_str))
+        .field(|c| c.name("Created on").value(&modification.creation_date))
+        .field(|c| {
+            c.name("Latest mod version").value(&format!(
+                "{} - [Download]({})",
+                modification.latest_version,
+                modification.download_link
+            ))
+        })
+        .field(|c| {
+            c.name("Factorio version").value(
+                &modification.factorio_version,
+            )
+        })
+        .field(|c| {
+            let popularity = match modification.download_count {
+                0...50 => ":asterisk: New mod! :asterisk:",
+                50...200 => ":seedling: Gathering a following... :seedling:",
+                201...1000 => ":third_place: A decent amount of downloads. :third_place:",
+                1001...2000 => ":second_place: Pretty popular! :second_place:",
+                2001...5000 => ":first_place: Very very popular! :first_place:",
+                5001...10_000 => ":heart: Extremely popular! :heart:",
+                10_001...15_000 => {
+                    ":military_medal: Beloved by the community! :military_medal:"
+                }
+                15_001...20_000 => ":medal: Nearing the Hall of Fame! :medal:",
+                // Anything beyond that
+                _ => ":trophy: Hall of Fame! :trophy:",
+            };
+            c.name("Popularity").value(popularity)
+        })
+}
+
 /// Creates an embed based on the given modder data. {{{1
-fn make_modder_embed(modder: Modder, message: &Message) -> bool {
+fn make_modder_embed(modder: Modder, message: &Message, cached_at: Option<DateTime<Utc>>) -> bool {
     // Figure out the time that the modder has been inactive
     let last_updated_date = NaiveDate::parse_from_str(&modder.last_updated_mod_date, "%Y-%m-%d")
         .unwrap();
@@ -197,6 +546,11 @@ fn make_modder_embed(modder: Modder, message: &Message) -> bool {
                 })
             .color(Colour::from_rgb(200, 250, 160))
                 .timestamp(message.timestamp.to_rfc3339())
+                .footer(|c| c.text(&if cached_at.is_some() {
+                    cache_note(cached_at)
+                } else {
+                    String::from("Fresh from the mod portal.")
+                }))
         })
     })
     .is_ok()
@@ -205,7 +559,7 @@ fn make_modder_embed(modder: Modder, message: &Message) -> bool {
 /// Turns a Jsonvalue into a modder. Should be given an array of results. {{{1
 fn parse_json_into_modder(username: &str, json: &JsonValue) -> Modder {
     // Turn all entries into Mods
-    let mut mods: Vec<Mod> = json.members().map(|a| parse_json_into_mod(&a)).collect();
+    let mut mods: Vec<Mod> = json.members().filter_map(parse_json_into_mod).collect();
 
     // Keep only mods that are owned by the username
     mods.retain(|a| a.author == username);
@@ -267,34 +621,28 @@ fn parse_json_into_modder(username: &str, json: &JsonValue) -> Modder {
 }
 
 /// Checks if a user is valid given a username, returns boolean. {{{1
-fn is_valid_modder(modder: &str) -> bool {
-
-    let response = reqwest::get(
-        format!("https://mods.factorio.com/api/users/{}", modder).as_str(),
-    );
-
-    if let Ok(mut response) = response {
-        if response.status().is_success() {
-            let mut json = String::new();
-            let _ = response.read_to_string(&mut json);
-            if json.contains("username") {
-                true
-            } else {
-                false
-            }
-        } else {
-            false
-        }
-    } else {
-        false
+pub fn is_valid_modder(modder: &str) -> bool {
+    let url = format!("https://mods.factorio.com/api/users/{}", modder);
+    match cached_request(&format!("modder:{}", modder), MODDER_TTL, || fetch_json(&url)) {
+        Some((json, _)) => !json["username"].is_null(),
+        None => false,
     }
 }
 
 /// Turns a jsonValue into a Mod. Assumes this is a direct mod json entry. {{{1
 /// This can be provided either by direct link, or by giving it one of the results
-/// returned by a mod api search.
-fn parse_json_into_mod(json: &JsonValue) -> Mod {
-    let (_, downloads, _) = json["downloads_count"].as_number().unwrap().as_parts();
+/// returned by a mod api search. Returns None if it has no name or download count.
+pub fn parse_json_into_mod(json: &JsonValue) -> Option<Mod> {
+    let downloads = match json["downloads_count"].as_u64() {
+        Some(downloads) if json["name"].is_string() => downloads,
+        _ => return None,
+    };
+    // Full mod info lists every release instead of the latest one, oldest first
+    let latest_release = if json["latest_release"].is_null() {
+        &json["releases"][json["releases"].len().saturating_sub(1)]
+    } else {
+        &json["latest_release"]
+    };
     let thumbnail = if !json["first_media_file"].is_null() {
         format!("{}", json["first_media_file"]["urls"]["thumb"])
     } else {
@@ -332,18 +680,20 @@ fn parse_json_into_mod(json: &JsonValue) -> Mod {
         update_date.truncate(index as usize);
     }
 
+    let dependency_list: Vec<Dependency> = latest_release["info_json"]["dependencies"]
+        .members()
+        .filter_map(|entry| entry.as_str().and_then(Dependency::parse))
+        .collect();
     let mut deps = String::from("No dependencies.");
-    let dependencies_json = &json["latest_release"]["info_json"]["dependencies"];
-    if !dependencies_json.is_null() && !dependencies_json.is_empty() {
+    if !dependency_list.is_empty() {
         deps.clear();
         let mut deps_counter = 0;
-        for entry in dependencies_json.members() {
-            let entry_str = format!("{}\n", entry);
+        for dependency in dependency_list.iter() {
             // If the dependency is optional, just count it
-            if entry_str.starts_with("?") {
+            if dependency.is_optional() {
                 deps_counter += 1;
             } else {
-                deps.push_str(&entry_str);
+                deps.push_str(&format!("{}\n", dependency));
             }
         }
         if deps_counter == 1 {
@@ -356,108 +706,702 @@ fn parse_json_into_mod(json: &JsonValue) -> Mod {
     //Download link
     let download_link = format!(
         "https://mods.factorio.com{}",
-        json["latest_release"]["download_url"]
+        latest_release["download_url"]
     );
 
     // Make and return the mod
-    Mod {
+    Some(Mod {
         creation_date: date,
         last_updated: update_date,
         name: format!("{}", json["name"]),
         summary: format!("{}", json["summary"]),
         author: format!("{}", json["owner"]),
         title: format!("{}", json["title"]),
-        latest_version: format!("{}", json["latest_release"]["version"]),
-        factorio_version: format!("{}", json["latest_release"]["factorio_version"]),
+        latest_version: format!("{}", latest_release["version"]),
+        factorio_version: parse_release(latest_release).factorio_version,
         tag,
         thumb: thumbnail,
         download_count: downloads,
         download_link,
         homepage,
         dependencies: deps,
+        dependency_list,
+        releases: json["releases"].members().rev().map(parse_release).collect(),
+        changelog: json["changelog"].as_str().unwrap_or("").to_owned(),
         source_path: source,
         link: format!(
             "https://mods.factorio.com/mods/{}/{}",
             json["owner"],
             json["name"]
         ).replace(" ", "%20"),
+    })
+}
+
+/// Turns one of the releases in full mod info into a Release. {{{1
+fn parse_release(json: &JsonValue) -> Release {
+    let released_at = format!("{}", json["released_at"]);
+    Release {
+        version: format!("{}", json["version"]),
+        // Releases in full mod info only have it in their info.json
+        factorio_version: if json["factorio_version"].is_null() {
+            format!("{}", json["info_json"]["factorio_version"])
+        } else {
+            format!("{}", json["factorio_version"])
+        },
+        released_on: released_at.split(|c| c == 'T' || c == ' ').next().unwrap_or("").to_owned(),
+        download_link: format!("https://mods.factorio.com{}", json["download_url"]),
     }
 }
 
+/// Checks if a search result is for a version of the game too old to use. {{{1
+fn is_outdated(entry: &JsonValue) -> bool {
+    match entry["latest_release"]["factorio_version"].as_str() {
+        Some(version) => compare_versions(version, "0.15") == Ordering::Less,
+        None => true,
+    }
+}
 
 /// Makes a request, either returning empty, or the successful mod json {{{1
-fn make_request(request: &String) -> JsonValue {
-    let response = reqwest::get(
-        format!("https://mods.factorio.com/api/mods?q={}", request).as_str(),
-    );
+pub fn make_request(request: &String) -> JsonValue {
+    search_portal(request).0
+}
+
+/// Searches the portal, returning the results, and when they were fetched if {{{1
+/// they came from the cache. The results are empty if the portal couldn't be reached.
+fn search_portal(request: &str) -> (JsonValue, Option<DateTime<Utc>>) {
+    let url = search_url(request);
+    // Searches don't care about case, so neither does the cache
+    match cached_request(&format!("search:{}", request.to_lowercase()), SEARCH_TTL, || fetch_json(&url)) {
+        Some(found) => found,
+        None => (JsonValue::new_object(), None),
+    }
+}
+
+/// Searches the portal like search_portal, but keeps going through the pages of {{{1
+/// results, up to MAX_SEARCH_PAGES, since filters may only match mods past the first.
+/// If any page came from the cache, returns when the oldest one was fetched.
+fn search_portal_pages(request: &str) -> (JsonValue, Option<DateTime<Utc>>) {
+    let (mut returned, mut cached_at) = search_portal(request);
+    let page_count = returned["pagination"]["page_count"].as_u64().unwrap_or(1).min(MAX_SEARCH_PAGES);
+    for page in 2..page_count + 1 {
+        let url = format!("{}&page={}", search_url(request), page);
+        let key = format!("search:{}:{}", request.to_lowercase(), page);
+        let (more, more_cached_at) = match cached_request(&key, SEARCH_TTL, || fetch_json(&url)) {
+            Some(found) => found,
+            None => break,
+        };
+        for entry in more["results"].members() {
+            let _ = returned["results"].push(entry.clone());
+        }
+        cached_at = match (cached_at, more_cached_at) {
+            (Some(first), Some(second)) => Some(first.min(second)),
+            (first, second) => first.or(second),
+        };
+    }
+    (returned, cached_at)
+}
+
+/// Gets everything the portal has on a mod, given its internal name, including {{{1
+/// its changelog. Returns empty if the portal couldn't be reached, or has no such mod.
+pub fn make_full_request(name: &str) -> JsonValue {
+    let url = full_mod_url(name);
+    match cached_request(&format!("full:{}", name), FULL_MOD_TTL, || fetch_json(&url)) {
+        Some((ref json, _)) if json.is_object() => json.clone(),
+        _ => JsonValue::new_object(),
+    }
+}
+
+/// Where to search the portal, with big enough pages that every result can be paged through. {{{1
+fn search_url(request: &str) -> String {
+    format!("https://mods.factorio.com/api/mods?q={}&page_size=100", request)
+}
+
+/// Where to get everything the portal has on a mod, given its internal name. {{{1
+fn full_mod_url(name: &str) -> String {
+    format!("https://mods.factorio.com/api/mods/{}/full", name.replace(" ", "%20"))
+}
+
+/// Searches the portal like search_portal, but always asks the portal, for {{{1
+/// checking on watched mods. Returns None if the portal couldn't be reached.
+pub fn fresh_search(request: &str) -> Option<JsonValue> {
+    let url = search_url(request);
+    fresh_request(&format!("search:{}", request.to_lowercase()), SEARCH_TTL, || fetch_json(&url))
+}
+
+/// Gets everything the portal has on a mod like make_full_request, but always {{{1
+/// asks the portal. Returns None if it couldn't be reached, or null if there's no such mod.
+pub fn fresh_full_request(name: &str) -> Option<JsonValue> {
+    let url = full_mod_url(name);
+    fresh_request(&format!("full:{}", name), FULL_MOD_TTL, || fetch_json(&url))
+}
+
+/// Gets a mod's short info from the portal, given its internal name. Returns empty {{{1
+/// if the portal couldn't be reached, or has no such mod.
+fn make_mod_request(name: &str) -> JsonValue {
+    let url = format!("https://mods.factorio.com/api/mods/{}", name.replace(" ", "%20"));
+    match cached_request(&format!("mod:{}", name), FULL_MOD_TTL, || fetch_json(&url)) {
+        Some((ref json, _)) if json.is_object() => json.clone(),
+        _ => JsonValue::new_object(),
+    }
+}
+
+/// Gets json from the portal. Returns null if the portal says there's nothing {{{1
+/// there, or None if it couldn't be reached, so only real answers are cached.
+fn fetch_json(url: &str) -> Option<JsonValue> {
+    let mut response = match reqwest::get(url) {
+        Ok(response) => response,
+        Err(_) => return None,
+    };
+    if response.status() == reqwest::StatusCode::NotFound {
+        return Some(JsonValue::Null);
+    }
+    if !response.status().is_success() {
+        return None;
+    }
+    let mut json = String::new();
+    let _ = response.read_to_string(&mut json);
+    json::parse(&json).ok()
+}
+
+/// Answers a request from the cache while it's fresh, otherwise fetches it. {{{1
+/// If the fetch fails, falls back on the expired answer if there is one.
+/// Returns the answer, and when it was fetched if it came from the cache.
+fn cached_request<F>(key: &str, ttl: i64, fetch: F) -> Option<(JsonValue, Option<DateTime<Utc>>)>
+    where F: FnOnce() -> Option<JsonValue>
+{
+    let now = Utc::now();
+    let cached = PORTAL_CACHE.lock().unwrap().entries.get(key).cloned();
+    if let Some(ref entry) = cached {
+        if entry.is_fresh(now) {
+            return Some((entry.json.clone(), Some(entry.fetched)));
+        }
+    }
+
+    // Not locked while fetching, so other commands aren't held up by the portal
+    match fetch() {
+        Some(json) => {
+            store_response(key, &json, ttl, now);
+            Some((json, None))
+        }
+        None => cached.map(|entry| (entry.json, Some(entry.fetched))),
+    }
+}
 
-    if let Ok(mut response) = response {
-        if response.status().is_success() {
-            let mut json = String::new();
-            let _ = response.read_to_string(&mut json);
-            return json::parse(&json).unwrap_or(JsonValue::new_object());
+/// Fetches a request from the portal, ignoring the cache, but still caching {{{1
+/// the answer. Returns None if the portal couldn't be reached.
+fn fresh_request<F>(key: &str, ttl: i64, fetch: F) -> Option<JsonValue>
+    where F: FnOnce() -> Option<JsonValue>
+{
+    let json = fetch();
+    if let Some(ref json) = json {
+        store_response(key, json, ttl, Utc::now());
+    }
+    json
+}
+
+/// Adds a response to the cache, backing it up if it's been a while. {{{1
+fn store_response(key: &str, json: &JsonValue, ttl: i64, now: DateTime<Utc>) {
+    let backup = {
+        let mut cache = PORTAL_CACHE.lock().unwrap();
+        cache.insert(key,
+                     CacheEntry {
+                         json: json.clone(),
+                         fetched: now,
+                         ttl,
+                     },
+                     now);
+        if cache.backup_due(now) {
+            cache.backed_up = Some(now);
+            Some(cache.to_json())
         } else {
-            return JsonValue::new_object();
+            None
+        }
+    };
+    // Written once unlocked, so other commands aren't held up by the disk
+    if let Some(backup) = backup {
+        write_portal_cache(&backup);
+    }
+}
+
+/// Reads the cache file into the cache, and keeps it backed up from then on. {{{1
+/// Should be called on startup, if the cache should outlast restarts.
+pub fn install_portal_cache() {
+    let mut cache = PORTAL_CACHE.lock().unwrap();
+    cache.persist = true;
+    if let Ok(mut handle) = File::open(CACHE_FILE) {
+        let mut json = String::new();
+        let _ = handle.read_to_string(&mut json);
+        match json::parse(&json) {
+            Ok(parsed) => cache.read_json(&parsed),
+            Err(error) => log_error!("Couldn't parse the portal cache file, starting empty: {:?}", error),
+        }
+        log_init!("Loaded {} cached portal responses.", cache.entries.len());
+    } else {
+        log_init!("Did not find the portal cache file, it will be created when something is cached.");
+    }
+}
+
+/// Backs up the cache, as made by PortalCache::to_json, to the cache file. {{{1
+fn write_portal_cache(json: &JsonValue) {
+    match OpenOptions::new().write(true).create(true).truncate(true).open(CACHE_TEMP_FILE) {
+        Ok(mut file_handle) => {
+            if let Err(error) = json.write(&mut file_handle) {
+                log_error!("Couldn't back up the portal cache to file: {:?}", error);
+                return;
+            }
+        }
+        Err(error) => {
+            log_error!("Couldn't open the portal cache file to back up to: {:?}", error);
+            return;
+        }
+    }
+    if let Err(error) = fs::rename(CACHE_TEMP_FILE, CACHE_FILE) {
+        log_error!("Couldn't replace the portal cache file with its backup: {:?}", error);
+    }
+}
+
+/// Returns a note saying when a cached response was fetched, for embed footers. {{{1
+fn cache_note(cached_at: Option<DateTime<Utc>>) -> String {
+    match cached_at {
+        Some(fetched) => format!("Cached as of {}.", fetched.format("%Y-%m-%d %H:%M UTC")),
+        None => String::new(),
+    }
+}
+
+/// Lists releases one to a line, cutting off the oldest ones to fit in an embed field. {{{1
+fn serialize_releases(releases: &[Release], selected: &str) -> String {
+    let mut lines: Vec<String> = Vec::new();
+    for (index, release) in releases.iter().enumerate() {
+        let line = format!("[{}]({}) for {}, {}", release.version, release.download_link,
+                           release.factorio_version, release.released_on);
+        let line = if release.version == selected { format!("**{}**", line) } else { line };
+        // Leave room for the note on how many were left out
+        if lines.iter().map(|line| line.len() + 1).sum::<usize>() + line.len() > 1000 {
+            lines.push(format!("...and {} older.", releases.len() - index));
+            break;
+        }
+        lines.push(line);
+    }
+    if lines.is_empty() {
+        String::from("No releases.")
+    } else {
+        lines.join("\n")
+    }
+}
+
+/// Finds the changes for one version in a changelog. Changelogs written the way {{{1
+/// the game reads them start each version with a line like "Version: 0.16.3".
+/// Ones that don't are free text, so the whole thing is returned.
+pub fn changes_for_version(changelog: &str, version: &str) -> Option<String> {
+    if changelog.trim().is_empty() {
+        return None;
+    }
+    if !changelog.lines().any(|line| line.trim().starts_with("Version:")) {
+        return Some(changelog.trim().to_owned());
+    }
+
+    let mut changes = Vec::new();
+    let mut in_version = false;
+    for line in changelog.lines() {
+        let trimmed = line.trim();
+        if trimmed.starts_with("Version:") {
+            if in_version {
+                break;
+            }
+            in_version = trimmed["Version:".len()..].trim() == version;
+        } else if in_version && !trimmed.is_empty() && !trimmed.chars().all(|c| c == '-') {
+            changes.push(line.trim_right());
+        }
+    }
+    if changes.is_empty() {
+        None
+    } else {
+        Some(changes.join("\n"))
+    }
+}
+
+/// Looks up a mod on the portal by its internal name. Returns None if the {{{1
+/// portal couldn't be reached, or has no mod with exactly that name.
+pub fn find_mod(name: &str) -> Option<Mod> {
+    let returned = make_request(&name.to_owned());
+    match returned["results"].members().find(|entry| entry["name"] == name) {
+        Some(found) => parse_json_into_mod(found),
+        // Searches only return the first page, which may not have the mod when many match
+        None => parse_json_into_mod(&make_mod_request(name)),
+    }
+}
+
+/// Splits the mods to compare, by commas if there are any since names can have {{{1
+/// spaces, otherwise by spaces. Mods named more than once are only kept once.
+fn split_mod_names(request: &str) -> Vec<String> {
+    let names: Vec<&str> = if request.contains(',') {
+        request.split(',').map(str::trim).filter(|name| !name.is_empty()).collect()
+    } else {
+        request.split_whitespace().collect()
+    };
+    let mut kept: Vec<String> = Vec::new();
+    for name in names {
+        if !kept.iter().any(|other| other.eq_ignore_ascii_case(name)) {
+            kept.push(name.to_owned());
+        }
+    }
+    kept
+}
+
+/// Picks the search result meant by a request, either one with that exact name, {{{1
+/// or the closest one by name or title if it's close enough.
+fn pick_search_result<'a>(results: &'a JsonValue, request: &str) -> Option<&'a JsonValue> {
+    if let Some(exact) = results.members().find(|entry| {
+        entry["name"].as_str().map(|name| name.eq_ignore_ascii_case(request)) == Some(true)
+    }) {
+        return Some(exact);
+    }
+    results
+        .members()
+        .map(|entry| {
+            let name_distance = levenshtein_insensitive(&format!("{}", entry["name"]), request);
+            let title_distance = levenshtein_insensitive(&format!("{}", entry["title"]), request);
+            (name_distance.min(title_distance), entry)
+        })
+        .filter(|&(distance, _)| distance <= DISTANCE_SENSITIVITY)
+        .min_by_key(|&(distance, _)| distance)
+        .map(|(_, entry)| entry)
+}
+
+/// Lists what's compared between mods, for a column of the comparison embed. {{{1
+fn describe_for_comparison(modification: &Mod) -> String {
+    let required: Vec<&str> = modification.dependency_list
+        .iter()
+        .filter(|dependency| dependency.kind == DependencyKind::Required && dependency.name != "base")
+        .map(|dependency| dependency.name.as_str())
+        .collect();
+    let optional = modification.dependency_list.iter().filter(|dependency| dependency.is_optional()).count();
+    let dependencies = match (required.is_empty(), optional) {
+        (true, 0) => String::from("None"),
+        (true, _) => format!("{} optional", optional),
+        (false, 0) => required.join(", "),
+        (false, _) => format!("{}, and {} optional", required.join(", "), optional),
+    };
+    let description = format!(
+        "**Author:** [{a}](https://mods.factorio.com/mods/{b})\n\
+         **Downloads:** {}\n\
+         **Latest version:** {}\n\
+         **Factorio version:** {}\n\
+         **Last updated:** {}\n\
+         **Dependencies:** {}",
+        modification.download_count,
+        modification.latest_version,
+        modification.factorio_version,
+        modification.last_updated,
+        dependencies,
+        a = modification.author,
+        b = modification.author.replace(" ", "%20"),
+    );
+    truncate_for_embed(&description, 1024)
+}
+
+/// Sums up how mods compare, such as which is the most downloaded, and whether {{{1
+/// they can be used together.
+fn summarize_comparison(mods: &[Mod]) -> String {
+    let mut lines = Vec::new();
+    if let Some(most_downloaded) = mods.iter().max_by_key(|modification| modification.download_count) {
+        lines.push(format!("Most downloaded: **{}**", most_downloaded.title));
+    }
+    // Dates are written so they sort as text
+    if let Some(latest) = mods.iter().max_by_key(|modification| modification.last_updated.as_str()) {
+        lines.push(format!("Most recently updated: **{}**", latest.title));
+    }
+    if mods.iter().any(|modification| !supports_version(&modification.factorio_version, &mods[0].factorio_version)) {
+        lines.push(String::from("They aren't all for the same version of the game."));
+    }
+    for modification in mods {
+        for other in mods {
+            let incompatible = modification.dependency_list.iter().any(|dependency| {
+                dependency.kind == DependencyKind::Incompatible && dependency.name == other.name
+            });
+            if incompatible {
+                lines.push(format!("**{}** is incompatible with **{}**.", modification.title, other.title));
+            }
         }
+    }
+    lines.join("\n")
+}
+
+/// Describes how a mod a save was made with compares to its latest version {{{1
+/// on the portal, if it's there.
+fn compare_with_portal(name: &str, version: &str, game_version: &str, portal: Option<&Mod>) -> String {
+    let portal = match portal {
+        Some(portal) => portal,
+        None => return format!("{} {}, not on the mod portal", name, version),
+    };
+    let mut result = if portal.latest_version == version {
+        format!("[{}]({}) {}, up to date", name, portal.link, version)
     } else {
-        return JsonValue::new_object();
+        format!("[{}]({}) {}, latest is {}", name, portal.link, version, portal.latest_version)
+    };
+    if !supports_version(&portal.factorio_version, game_version) {
+        result += &format!(" for {}", portal.factorio_version);
     }
+    result
 }
 
-/// Makes an embed of search results. Takes a json array, and returns true {{{1
-/// if able to make an embed of all the results
-fn make_search_results_embed(message: &Message, results: JsonValue) -> bool {
-    message
-        .channel_id
-        .send_message(|a| {
-            a.embed(|b| {
-                b.title("Search results:")
-                    .description(&serialize_search_results(&results))
-                    .color(Colour::from_rgb(255, 34, 108))
-                    .timestamp(message.timestamp.to_rfc3339())
+/// Reads a mod-list.json into the name of each mod, and whether it's enabled. {{{1
+/// Older versions of the game wrote enabled as a string, rather than a bool.
+fn parse_mod_list(text: &str) -> Result<Vec<(String, bool)>, String> {
+    let parsed = match json::parse(text) {
+        Ok(parsed) => parsed,
+        Err(error) => return Err(format!("That file isn't valid json: {}", error)),
+    };
+    if !parsed["mods"].is_array() {
+        return Err(String::from("That doesn't look like a mod-list.json, it has no list of mods."));
+    }
+    Ok(parsed["mods"]
+        .members()
+        .filter_map(|entry| {
+            entry["name"].as_str().map(|name| {
+                (name.to_owned(), entry["enabled"].as_bool() == Some(true) || entry["enabled"] == "true")
             })
         })
-        .is_ok()
+        .collect())
 }
 
-/// Takes a json array as input, returns a string of the search results serialized. {{{1
-fn serialize_search_results(results: &JsonValue) -> String {
-    let mut final_string = String::new();
-    let mut outdated_counter = 0;
+/// Checks if a mod made for one version of the game works on another. Only {{{1
+/// the first two parts matter, so a mod for 0.16 works on 0.16.51.
+fn supports_version(mod_version: &str, game_version: &str) -> bool {
+    match (parse_version(mod_version), parse_version(game_version)) {
+        (Some(mod_version), Some(game_version)) => {
+            mod_version.major == game_version.major && mod_version.minor == game_version.minor
+        }
+        _ => false,
+    }
+}
+
+/// What was found resolving the dependencies of a mod. {{{1
+#[derive(Debug, Default)]
+struct DependencyTree {
+    pub tree: Vec<String>, //Required dependencies, indented by how deep they are
+    pub optional: Vec<String>,
+    pub incompatible: Vec<String>,
+    pub unmet: Vec<String>, //Versions that are needed, but can't be had
+    pub missing: Vec<String>, //Required dependencies that aren't on the portal
+    pub lookups: usize,
+}
+
+/// Resolves the required dependencies of a mod, then theirs, and so on. {{{1
+/// Mods are found with the lookup given, up to MAX_MOD_LOOKUPS of them. If given
+/// a game version, also checks that the latest version of each is made for it.
+fn resolve_dependencies<F>(root: &Mod, game_version: &str, lookup: &mut F) -> DependencyTree
+    where F: FnMut(&str) -> Option<Mod>
+{
+    let mut tree = DependencyTree::default();
+    let mut seen = HashMap::new();
+    seen.insert(root.name.clone(), Some(root.clone()));
+    check_game_version(root, game_version, &mut tree);
+    add_dependencies(root, 0, game_version, lookup, &mut seen, &mut tree);
+    tree
+}
+
+/// Adds the dependencies of a mod to the tree, looking up the required ones {{{1
+/// that haven't been seen yet, and adding theirs one level deeper. Seen mods are
+/// kept by name, or None if they couldn't be looked up, so every mod needing
+/// one has its version checked.
+fn add_dependencies<F>(parent: &Mod,
+                       depth: usize,
+                       game_version: &str,
+                       lookup: &mut F,
+                       seen: &mut HashMap<String, Option<Mod>>,
+                       tree: &mut DependencyTree)
+    where F: FnMut(&str) -> Option<Mod>
+{
+    for dependency in parent.dependency_list.iter() {
+        // The base game isn't on the portal, but its version can still be checked
+        if dependency.name == "base" {
+            if !game_version.is_empty() && !dependency.is_met_by(game_version) {
+                tree.unmet.push(format!("{} needs {}", parent.name, dependency.requirement()));
+            }
+            continue;
+        }
+        match dependency.kind {
+            DependencyKind::Incompatible => {
+                tree.incompatible.push(format!("{} can't be used with {}", parent.name, dependency.name));
+                continue;
+            }
+            DependencyKind::Optional | DependencyKind::HiddenOptional => {
+                tree.optional.push(format!("{}, for {}", dependency.requirement(), parent.name));
+                continue;
+            }
+            DependencyKind::Required => {}
+        }
+
+        let line = format!("{}- {}", "  ".repeat(depth), dependency.requirement());
+        if let Some(known) = seen.get(&dependency.name) {
+            tree.tree.push(format!("{}, see above", line));
+            if let Some(ref known) = *known {
+                check_requirement(parent, dependency, known, tree);
+            }
+            continue;
+        }
+        if tree.lookups >= MAX_MOD_LOOKUPS {
+            tree.tree.push(format!("{}, not looked up", line));
+            seen.insert(dependency.name.clone(), None);
+            continue;
+        }
+        tree.lookups += 1;
 
-    for entry in results.members().take(10) {
-        let version = format!("{}", entry["latest_release"]["factorio_version"]);
-        if version.len() < 4 {
-            continue; //Version is not complete
+        let found = match lookup(&dependency.name) {
+            Some(found) => found,
+            None => {
+                tree.tree.push(format!("{}, not on the mod portal", line));
+                tree.missing.push(dependency.name.clone());
+                seen.insert(dependency.name.clone(), None);
+                continue;
+            }
+        };
+        seen.insert(dependency.name.clone(), Some(found.clone()));
+        tree.tree.push(format!("{}, latest is {}", line, found.latest_version));
+        check_requirement(parent, dependency, &found, tree);
+        check_game_version(&found, game_version, tree);
+        add_dependencies(&found, depth + 1, game_version, lookup, seen, tree);
+    }
+}
+
+/// Notes a dependency whose version needed isn't met by the latest version of the mod. {{{1
+fn check_requirement(parent: &Mod, dependency: &Dependency, found: &Mod, tree: &mut DependencyTree) {
+    if !dependency.is_met_by(&found.latest_version) {
+        tree.unmet.push(format!("{} needs {}, but the latest is {}",
+                                parent.name,
+                                dependency.requirement(),
+                                found.latest_version));
+    }
+}
+
+/// Notes a mod whose latest version isn't made for the game version, if one was given. {{{1
+fn check_game_version(modification: &Mod, game_version: &str, tree: &mut DependencyTree) {
+    if !game_version.is_empty() && !supports_version(&modification.factorio_version, game_version) {
+        tree.unmet.push(format!("{}'s latest version is for {}", modification.name, modification.factorio_version));
+    }
+}
+
+/// Splits a game version off the end of a request, like "bobores 0.16". {{{1
+/// Returns the rest, and the version, which is empty if there wasn't one.
+fn split_game_version(request: &str) -> (String, String) {
+    let request = request.trim();
+    if let Some(start) = request.rfind(' ') {
+        let last = &request[start + 1..];
+        if last.starts_with(|c: char| c.is_digit(10)) && last.chars().all(|c| c.is_digit(10) || c == '.') {
+            return (request[..start].trim().to_owned(), last.to_owned());
         }
-        let version: f32 = version[..4].parse::<f32>().unwrap(); //only parse the first 4 chars, so x.xx
-        if version < 0.15 {
-            outdated_counter += 1;
-            continue; //Filter mods that aren't for current stable or higher
+    }
+    (request.to_owned(), String::new())
+}
+
+/// Makes an embed of search results, with reactions to page through them and {{{1
+/// open one. Takes a json array, and returns true if able to make the embed.
+fn make_search_results_embed(message: &Message,
+                             results: Vec<JsonValue>,
+                             filters: &SearchFilters,
+                             cached_at: Option<DateTime<Utc>>)
+                             -> bool {
+    let mut pages = SearchPages::new(results, filters, cached_at);
+    let sent = message.channel_id.send_message(|a| {
+        a.embed(|b| pages.fill_embed(b).timestamp(message.timestamp.to_rfc3339()))
+    });
+    let sent = match sent {
+        Ok(sent) => sent,
+        Err(_) => return false,
+    };
+
+    let mut reactions = Vec::new();
+    if pages.page_count() > 1 {
+        reactions.push(PREVIOUS_PAGE);
+        reactions.push(NEXT_PAGE);
+    }
+    reactions.extend(NUMBER_REACTIONS.iter().take(pages.results.len().min(SEARCH_PAGE_SIZE)));
+    pages.bot = sent.author.id;
+    {
+        let mut all_pages = SEARCH_PAGES.lock().unwrap();
+        let now = Instant::now();
+        all_pages.retain(|_, pages| !pages.is_expired(now));
+        all_pages.insert(sent.id, pages);
+    }
+    for reaction in reactions {
+        let _ = sent.react(ReactionType::Unicode(reaction.to_owned()));
+    }
+    true
+}
+
+/// Pages through search results, or opens one, when reacted to. Should be {{{1
+/// given every reaction added, those on other messages are ignored.
+pub fn handle_search_reaction(reaction: &Reaction) {
+    let action = match reaction.emoji {
+        ReactionType::Unicode(ref emoji) => PageAction::from_emoji(emoji),
+        _ => None,
+    };
+    let action = match action {
+        Some(action) => action,
+        None => return,
+    };
+
+    // Copied, so the lock isn't held while talking to discord
+    let (pages, turned) = {
+        let mut all_pages = SEARCH_PAGES.lock().unwrap();
+        let now = Instant::now();
+        all_pages.retain(|_, pages| !pages.is_expired(now));
+        match all_pages.get_mut(&reaction.message_id) {
+            Some(pages) => {
+                if pages.bot == reaction.user_id {
+                    return;
+                }
+                let turned = match action {
+                    PageAction::Previous => pages.turn(false),
+                    PageAction::Next => pages.turn(true),
+                    PageAction::Open(_) => false,
+                };
+                (pages.clone(), turned)
+            }
+            None => return,
         }
+    };
 
-        let mut encoded_name = format!("{}", entry["name"]);
+    match action {
+        PageAction::Previous | PageAction::Next => {
+            if turned {
+                let _ = reaction.channel_id.edit_message(reaction.message_id, |m| {
+                    m.embed(|e| pages.fill_embed(e).timestamp(Utc::now().to_rfc3339()))
+                });
+            }
+            // Taken off, so it can be used again. Needs permission to manage messages
+            let _ = reaction.delete();
+        }
+        PageAction::Open(number) => {
+            if let Some(modification) = pages.pick(number).and_then(parse_json_into_mod) {
+                if !make_mod_embed(modification, reaction.channel_id, &Utc::now().to_rfc3339(), pages.cached_at) {
+                    log_error!("Couldn't open a mod from search results in channel {}.", reaction.channel_id.0);
+                }
+            }
+        }
+    }
+}
+
+/// Serializes a page of search results, numbered to match the reactions. {{{1
+fn serialize_search_page(results: &[JsonValue], page: usize) -> String {
+    let mut final_string = String::new();
+    for (index, entry) in results.iter().enumerate().skip(page * SEARCH_PAGE_SIZE).take(SEARCH_PAGE_SIZE) {
         // URLS can't have spaces
-        encoded_name = encoded_name.replace(" ", "%20");
+        let encoded_name = format!("{}", entry["name"]).replace(" ", "%20");
         final_string += format!(
-            "- [{a}](https://mods.factorio.com/mods/{b}/{c}) by {b}\n",
+            "{n}. [{a}](https://mods.factorio.com/mods/{b}/{c}) by {b}",
+            n = index % SEARCH_PAGE_SIZE + 1,
             a = entry["title"],
             b = entry["owner"],
             c = encoded_name,
         ).as_str();
+        final_string += &format!(", for {}\n", entry["latest_release"]["factorio_version"]);
     }
     if final_string.is_empty() {
-        format!(
-            "No results for current factorio stable version or higher.
-                \n{} outdated mods were found.",
-            outdated_counter
-        )
+        String::from("No results.")
     } else {
-        format!(
-            "{}\n...and {} outdated mod(s).",
-            final_string,
-            outdated_counter
-        )
+        final_string
     }
 }
 
@@ -474,38 +1418,51 @@ command!(linkmod(_context, message) {
 
     let _ = message.channel_id.broadcast_typing();
 
-    // Check arg validity
-    if request.is_empty() {
+    let (request, filters) = match SearchFilters::take_from(&request) {
+        Ok(taken) => taken,
+        Err(reason) => {
+            send_error_embed_or_say(&message, &reason);
+            return Err(CommandError::from("Invalid search filters."));
+        }
+    };
+
+    // Check arg validity, searching for nothing lists every mod, which is only useful filtered
+    if request.is_empty() && filters.is_empty() {
         send_error_embed_or_say(&message, "Expected a mod to search for.");
         return Err(CommandError::from("User didn't provide an argument."));
     }
 
-    // Make the mod api request
-    let returned = make_request(&request);
+    // Make the mod api request, filters need every page to find everything that matches
+    let (returned, cached_at) = if filters.is_empty() {
+        search_portal(&request)
+    } else {
+        search_portal_pages(&request)
+    };
     if !returned.is_empty() {
-        let returned_results = &returned["results"];
+        let returned_results = filters.apply(&returned["results"]);
 
-        if !returned_results.is_null() && !returned_results.is_empty() {
+        if !returned_results.is_empty() {
             // If there's only one search result
             if returned_results.len() == 1 {
-                let modification = parse_json_into_mod(&returned_results[0]);
-                if !make_mod_embed(modification, &message) {
-                    say_into_chat(&message, "Unable to make an embed here.");
-                    return Err(CommandError::from("Couldn't make an embed."));
-                } else {
-                    return Ok(());
+                if let Some(modification) = parse_json_into_mod(&returned_results[0]) {
+                    if !make_mod_embed(modification, message.channel_id, &message.timestamp.to_rfc3339(), cached_at) {
+                        say_into_chat(&message, "Unable to make an embed here.");
+                        return Err(CommandError::from("Couldn't make an embed."));
+                    } else {
+                        return Ok(());
+                    }
                 }
             }
-            // More than one search result, so let's list them
-            for entry in returned_results.members() {
-                let modification = parse_json_into_mod(&entry);
-
+            // More than one search result, so let's list them, unless one is close enough.
+            // Filtered searches list every result, since they're browsing rather than looking one up
+            let candidates = returned_results.iter().filter(|_| filters.is_empty()).filter_map(parse_json_into_mod);
+            for modification in candidates {
                 // Check if the match is close enough, both on the
                 // internal name and the title
                 if levenshtein_insensitive(&modification.name, &request) <= DISTANCE_SENSITIVITY
                     || levenshtein_insensitive(&modification.title, &request) <= DISTANCE_SENSITIVITY {
                         // Got a match on this entry, so let's send it
-                        if !make_mod_embed(modification, &message) {
+                        if !make_mod_embed(modification, message.channel_id, &message.timestamp.to_rfc3339(), cached_at) {
                             say_into_chat(&message, "Unable to make an embed here.");
                             return Err(CommandError::from("Couldn't make an embed."));
                         } else {
@@ -515,11 +1472,16 @@ command!(linkmod(_context, message) {
             }
             // At this point, it hasn't found an exact match,
             // so let's just make an embed with all the results it found
-            if !make_search_results_embed(&message, returned_results.clone()) {
+            if !make_search_results_embed(&message, returned_results, &filters, cached_at) {
                 say_into_chat(&message, "Unable to make an embed of search results here.");
                 return Err(CommandError::from("Couldn't make an embed of search results."));
             }
             return Ok(());
+        } else if !filters.is_empty() {
+            send_error_embed_or_say(&message,
+                                    &format!("The mod portal didn't return any matches {}. :frowning:",
+                                             filters.describe()));
+            return Err(CommandError::from("Didn't find any results for the request."));
         } else {
             send_error_embed_or_say(&message, "The mod portal didn't return any matches for that query. :frowning:");
             return Err(CommandError::from("Didn't find any results for the request."));
@@ -538,13 +1500,13 @@ command!(modder(_context, message) {
 
     // Check if the username provided is valid
     if !modder_username.is_empty() && is_valid_modder(&modder_username) {
-        let results = make_request(&modder_username);
+        let (results, cached_at) = search_portal(&modder_username);
         if !results.is_empty() {
             // This is checked similarly twice, because results may be empty (which indicates a failed
             // connect, while results["results"] is empty if the user hasn't made any mods.
             if !results["results"].is_empty() {
                 let modder = parse_json_into_modder(&modder_username, &results["results"]);
-                if !make_modder_embed(modder, &message) {
+                if !make_modder_embed(modder, &message, cached_at) {
                     say_into_chat(&message, "Couldn't make an embed here.");
                 } else {
                     return Ok(());
@@ -563,6 +1525,398 @@ command!(modder(_context, message) {
     }
 });
 
+/// Reads an attached mod-list.json, and reports which mods are enabled, with {{{1
+/// their latest versions on the portal. If given a game version, also lists the
+/// mods whose latest version isn't made for it.
+command!(modlist(_context, message) {
+    let version = fix_message(message.content_safe(), "modlist");
+    let version = version.trim();
+    let _ = message.channel_id.broadcast_typing();
+
+    let attachment = match message.attachments.iter().find(|attachment| attachment.filename.ends_with(".json")) {
+        Some(attachment) => attachment,
+        None => {
+            send_error_embed_or_say(&message, "You must attach your mod-list.json, it's in the mods folder of the game.");
+            return Err(CommandError::from("Missing mod list attachment, failed."));
+        }
+    };
+    if attachment.size > MAX_MOD_LIST_SIZE {
+        send_error_embed_or_say(&message, "That file is too big to be a mod-list.json.");
+        return Err(CommandError::from("Mod list too big, failed."));
+    }
+    let text = match attachment.download() {
+        Ok(bytes) => String::from_utf8_lossy(&bytes).into_owned(),
+        Err(_) => {
+            send_error_embed_or_say(&message, "Sorry, I couldn't download that file.");
+            return Err(CommandError::from("Couldn't download mod list."));
+        }
+    };
+    let mods = match parse_mod_list(&text) {
+        Ok(mods) => mods,
+        Err(reason) => {
+            send_error_embed_or_say(&message, &reason);
+            return Err(CommandError::from("Couldn't parse mod list."));
+        }
+    };
+
+    // The base game is always listed, but isn't on the portal
+    let enabled: Vec<&str> = mods.iter()
+        .filter(|&&(ref name, enabled)| enabled && name != "base")
+        .map(|&(ref name, _)| name.as_str())
+        .collect();
+    let disabled: Vec<&str> = mods.iter()
+        .filter(|&&(_, enabled)| !enabled)
+        .map(|&(ref name, _)| name.as_str())
+        .collect();
+
+    let mut found = String::new();
+    let mut mismatched = Vec::new();
+    let mut missing = Vec::new();
+    for name in enabled.iter().take(MAX_MOD_LOOKUPS) {
+        match find_mod(name) {
+            Some(modification) => {
+                found += &format!("[{}]({}) {}, for {}\n",
+                                  modification.title,
+                                  modification.link,
+                                  modification.latest_version,
+                                  modification.factorio_version);
+                if !version.is_empty() && !supports_version(&modification.factorio_version, version) {
+                    mismatched.push(format!("{} (for {})", name, modification.factorio_version));
+                }
+            }
+            None => missing.push(*name),
+        }
+    }
+    if enabled.len() > MAX_MOD_LOOKUPS {
+        found += &format!("...and {} more I didn't look up.", enabled.len() - MAX_MOD_LOOKUPS);
+    }
+    if found.is_empty() {
+        found = String::from("None.");
+    }
+
+    let mismatch_note = if version.is_empty() {
+        String::from("Give your game version after the command, like `modlist 0.16`, to check them.")
+    } else if mismatched.is_empty() {
+        format!("All of them have a latest version for {}.", version)
+    } else {
+        mismatched.join(", ")
+    };
+
+    let result = message.channel_id.send_message(|a| a
+                                                 .embed(|b| b
+                                                        .title("Mod list")
+                                                        .description(&format!("{} of {} mods are enabled, not counting the base game.",
+                                                                              enabled.len(),
+                                                                              mods.iter().filter(|&&(ref name, _)| name != "base").count()))
+                                                        .field(|c| c
+                                                               .name("Enabled, with their latest versions")
+                                                               .value(&truncate_for_embed(&found, 1024)))
+                                                        .field(|c| c
+                                                               .name(&if version.is_empty() {
+                                                                   String::from("Not made for your version")
+                                                               } else {
+                                                                   format!("Not made for {}", version)
+                                                               })
+                                                               .value(&truncate_for_embed(&mismatch_note, 1024)))
+                                                        .field(|c| c
+                                                               .name("Not on the mod portal")
+                                                               .value(&if missing.is_empty() {
+                                                                   String::from("None.")
+                                                               } else {
+                                                                   truncate_for_embed(&missing.join(", "), 1024)
+                                                               }))
+                                                        .field(|c| c
+                                                               .name("Disabled")
+                                                               .value(&if disabled.is_empty() {
+                                                                   String::from("None.")
+                                                               } else {
+                                                                   truncate_for_embed(&disabled.join(", "), 1024)
+                                                               }))
+                                                        .color(Colour::from_rgb(255, 34, 108))
+                                                        .timestamp(message.timestamp.to_rfc3339())
+                                                       ));
+    if let Err(_) = result {
+        say_into_chat(&message, "Unable to make an embed here.");
+        return Err(CommandError::from("Couldn't make an embed."));
+    }
+});
+
+/// Reads the header of an attached save, and reports the version and scenario {{{1
+/// it was made with, and its mods compared to the mod portal.
+command!(save(_context, message) {
+    let _ = message.channel_id.broadcast_typing();
+
+    let attachment = match message.attachments.iter().find(|attachment| attachment.filename.ends_with(".zip")) {
+        Some(attachment) => attachment,
+        None => {
+            send_error_embed_or_say(&message, "You must attach a save to the message, it's a .zip in the saves folder of the game.");
+            return Err(CommandError::from("Missing save attachment, failed."));
+        }
+    };
+    if attachment.size > MAX_SAVE_SIZE {
+        send_error_embed_or_say(&message, "That save is too big for me to download.");
+        return Err(CommandError::from("Save too big, failed."));
+    }
+    let data = match attachment.download() {
+        Ok(data) => data,
+        Err(_) => {
+            send_error_embed_or_say(&message, "Sorry, I couldn't download that save.");
+            return Err(CommandError::from("Couldn't download save."));
+        }
+    };
+    let header = match SaveHeader::from_zip(&data) {
+        Ok(header) => header,
+        Err(error) => {
+            send_error_embed_or_say(&message, &format!("Sorry, I couldn't read that save. {}", error));
+            return Err(CommandError::from("Couldn't read save header."));
+        }
+    };
+
+    let game_version = header.version_string();
+    let mut mods = String::new();
+    // The base game is always listed, but isn't on the portal
+    let saved_mods: Vec<_> = header.mods.iter().filter(|saved| saved.name != "base").collect();
+    for saved in saved_mods.iter().take(MAX_MOD_LOOKUPS) {
+        let portal = find_mod(&saved.name);
+        mods += &compare_with_portal(&saved.name, &saved.version_string(), &game_version, portal.as_ref());
+        mods += "\n";
+    }
+    if saved_mods.len() > MAX_MOD_LOOKUPS {
+        mods += &format!("...and {} more I didn't look up.", saved_mods.len() - MAX_MOD_LOOKUPS);
+    }
+    if mods.is_empty() {
+        mods = String::from("None, only the base game.");
+    }
+
+    let multiplayer = if header.saving_replay {
+        "No, it's recording a replay, which only single player games can."
+    } else {
+        "Possibly, it isn't recording a replay, like multiplayer games never do."
+    };
+
+    let result = message.channel_id.send_message(|a| a
+                                                 .embed(|b| b
+                                                        .title(&format!("Save made in Factorio {}", game_version))
+                                                        .field(|c| c
+                                                               .name("Scenario")
+                                                               .value(&format!("{} from {}", header.level_name, header.base_mod)))
+                                                        .field(|c| c
+                                                               .name("Last loaded in")
+                                                               .value(&format!("{}.{}.{} build {}",
+                                                                               header.loaded_from[0],
+                                                                               header.loaded_from[1],
+                                                                               header.loaded_from[2],
+                                                                               header.loaded_from_build)))
+                                                        .field(|c| c
+                                                               .name("Multiplayer")
+                                                               .value(multiplayer))
+                                                        .field(|c| c
+                                                               .name("Commands allowed for")
+                                                               .value(header.allowed_commands_name()))
+                                                        .field(|c| c
+                                                               .name(&format!("Mods ({})", saved_mods.len()))
+                                                               .value(&truncate_for_embed(&mods, 1024)))
+                                                        .footer(|c| c
+                                                                .text("A server needs exactly the same mod versions as the save."))
+                                                        .color(Colour::from_rgb(255, 34, 108))
+                                                        .timestamp(message.timestamp.to_rfc3339())
+                                                       ));
+    if let Err(_) = result {
+        say_into_chat(&message, "Unable to make an embed here.");
+        return Err(CommandError::from("Couldn't make an embed."));
+    }
+});
+
+/// Resolves the dependencies of a mod on the portal, and shows them as a tree, {{{1
+/// with the optional and incompatible ones. If given a game version after the
+/// mod name, also finds the ones that can't be had for it.
+command!(moddeps(_context, message) {
+    let (name, game_version) = split_game_version(&fix_message(message.content_safe(), "moddeps"));
+    let _ = message.channel_id.broadcast_typing();
+
+    if name.is_empty() {
+        send_error_embed_or_say(&message, "Expected the name of a mod to resolve the dependencies of.");
+        return Err(CommandError::from("User didn't provide an argument."));
+    }
+    let root = match find_mod(&name) {
+        Some(root) => root,
+        None => {
+            send_error_embed_or_say(&message, "No mod on the portal has exactly that name. \
+                                               It needs the internal name of the mod, use `linkmod` to find it.");
+            return Err(CommandError::from("Couldn't find the mod."));
+        }
+    };
+
+    let tree = resolve_dependencies(&root, &game_version, &mut find_mod);
+    let description = if tree.tree.is_empty() {
+        String::from("It doesn't need any other mods.")
+    } else {
+        // Code blocks keep the indents
+        format!("```{}```", truncate_for_embed(&tree.tree.join("\n"), 2000))
+    };
+    let list_or_none = |list: &Vec<String>| if list.is_empty() {
+        String::from("None.")
+    } else {
+        truncate_for_embed(&list.join("\n"), 1024)
+    };
+
+    let result = message.channel_id.send_message(|a| a
+                                                 .embed(|b| b
+                                                        .author(|c| c
+                                                                .name(&format!("Dependencies of {} {}", root.title, root.latest_version))
+                                                                .url(&root.link))
+                                                        .description(&description)
+                                                        .field(|c| c
+                                                               .name("Optional")
+                                                               .value(&list_or_none(&tree.optional)))
+                                                        .field(|c| c
+                                                               .name("Incompatible")
+                                                               .value(&list_or_none(&tree.incompatible)))
+                                                        .field(|c| c
+                                                               .name(&if game_version.is_empty() {
+                                                                   String::from("Can't be met")
+                                                               } else {
+                                                                   format!("Can't be met on {}", game_version)
+                                                               })
+                                                               .value(&list_or_none(&tree.unmet)))
+                                                        .field(|c| c
+                                                               .name("Not on the mod portal")
+                                                               .value(&list_or_none(&tree.missing)))
+                                                        .footer(|c| c
+                                                                .text("Only the latest version of each mod is checked."))
+                                                        .color(Colour::from_rgb(255, 34, 108))
+                                                        .timestamp(message.timestamp.to_rfc3339())
+                                                       ));
+    if let Err(_) = result {
+        say_into_chat(&message, "Unable to make an embed here.");
+        return Err(CommandError::from("Couldn't make an embed."));
+    }
+});
+
+/// Compares up to MAX_COMPARED_MODS mods side by side, such as their downloads, {{{1
+/// latest versions and the game versions they're made for.
+command!(modcompare(_context, message) {
+    let names = split_mod_names(&fix_message(message.content_safe(), "modcompare"));
+    let _ = message.channel_id.broadcast_typing();
+
+    if names.len() < 2 {
+        send_error_embed_or_say(&message, "Expected at least two mods to compare, like `modcompare bobores angelsrefining`. \
+                                           Separate them with commas if their names have spaces.");
+        return Err(CommandError::from("User didn't provide enough arguments."));
+    }
+    if names.len() > MAX_COMPARED_MODS {
+        send_error_embed_or_say(&message, &format!("Only {} mods can be compared at once.", MAX_COMPARED_MODS));
+        return Err(CommandError::from("User provided too many arguments."));
+    }
+
+    let mut mods = Vec::new();
+    let mut not_found = Vec::new();
+    let mut oldest_cached = None;
+    for name in names.iter() {
+        let (returned, cached_at) = search_portal(name);
+        match pick_search_result(&returned["results"], name).and_then(parse_json_into_mod) {
+            Some(modification) => mods.push(modification),
+            None => not_found.push(name.as_str()),
+        }
+        // The footer has to cover the oldest response used
+        oldest_cached = match (oldest_cached, cached_at) {
+            (Some(oldest), Some(cached_at)) => Some(::std::cmp::min(oldest, cached_at)),
+            (oldest, cached_at) => oldest.or(cached_at),
+        };
+    }
+    if !not_found.is_empty() {
+        send_error_embed_or_say(&message, &format!("Couldn't find {} on the mod portal. Use `linkmod` to search for them.",
+                                                   not_found.join(", ")));
+        return Err(CommandError::from("Couldn't find a mod."));
+    }
+
+    let titles: Vec<&str> = mods.iter().map(|modification| modification.title.as_str()).collect();
+    let result = message.channel_id.send_message(|a| a
+                                                 .embed(|b| {
+                                                     let embed = b
+                                                         .title(&truncate_for_embed(&format!("Comparing {}", titles.join(" and ")), 256))
+                                                         .description(&summarize_comparison(&mods))
+                                                         .color(Colour::from_rgb(255, 34, 108))
+                                                         .timestamp(message.timestamp.to_rfc3339());
+                                                     let embed = mods.iter().fold(embed, |embed, modification| embed
+                                                                                  .field(|c| c
+                                                                                         .name(&modification.title)
+                                                                                         .value(&describe_for_comparison(modification))
+                                                                                         .inline(true)));
+                                                     match oldest_cached {
+                                                         Some(_) => embed.footer(|c| c.text(&cache_note(oldest_cached))),
+                                                         None => embed,
+                                                     }
+                                                 }));
+    if let Err(_) = result {
+        say_into_chat(&message, "Unable to make an embed here.");
+        return Err(CommandError::from("Couldn't make an embed."));
+    }
+});
+
+/// Shows the releases of a mod, and the changes in its latest one, or the {{{1
+/// version given after the mod name, from its changelog.
+command!(modchangelog(_context, message) {
+    let (name, version) = split_game_version(&fix_message(message.content_safe(), "modchangelog"));
+    let _ = message.channel_id.broadcast_typing();
+
+    if name.is_empty() {
+        send_error_embed_or_say(&message, "Expected a mod to show the changelog of.");
+        return Err(CommandError::from("User didn't provide an argument."));
+    }
+
+    // The full info needs the internal name, so search for it if that wasn't given
+    let mut full = make_full_request(&name);
+    if full.is_empty() {
+        let returned = make_request(&name);
+        if let Some(entry) = pick_search_result(&returned["results"], &name) {
+            full = make_full_request(&format!("{}", entry["name"]));
+        }
+    }
+    if full.is_empty() {
+        send_error_embed_or_say(&message, "Couldn't find that mod on the mod portal. Use `linkmod` to search for it.");
+        return Err(CommandError::from("Couldn't find the mod."));
+    }
+    let modification = match parse_json_into_mod(&full) {
+        Some(modification) => modification,
+        None => {
+            send_error_embed_or_say(&message, "The mod portal sent back something I couldn't read. :thinking:");
+            return Err(CommandError::from("Couldn't parse the mod."));
+        }
+    };
+
+    let version = if version.is_empty() { modification.latest_version.clone() } else { version };
+    if !modification.releases.iter().any(|release| release.version == version) {
+        send_error_embed_or_say(&message, &format!("{} doesn't have a release {}.", modification.title, version));
+        return Err(CommandError::from("Couldn't find the version."));
+    }
+    let changes = match changes_for_version(&modification.changelog, &version) {
+        Some(changes) => truncate_for_embed(&changes, 2000),
+        None => String::from("The changelog doesn't say what changed in this version."),
+    };
+
+    let result = message.channel_id.send_message(|a| a
+                                                 .embed(|b| b
+                                                        .author(|c| c
+                                                                .name(&format!("Changelog of {}", modification.title))
+                                                                .url(&modification.link))
+                                                        .thumbnail(&modification.thumb)
+                                                        .title(&format!("Changes in {}", version))
+                                                        .description(&changes)
+                                                        .field(|c| c
+                                                               .name("Releases")
+                                                               .value(&serialize_releases(&modification.releases, &version)))
+                                                        .footer(|c| c
+                                                                .text("Give a version after the mod name to see what changed in it."))
+                                                        .color(Colour::from_rgb(255, 34, 108))
+                                                        .timestamp(message.timestamp.to_rfc3339())
+                                                       ));
+    if let Err(_) = result {
+        say_into_chat(&message, "Unable to make an embed here.");
+        return Err(CommandError::from("Couldn't make an embed."));
+    }
+});
+
 // Tests {{{1
 #[cfg(test)]
 mod tests {
@@ -579,4 +1933,320 @@ mod tests {
         let modder = "soeinfosaesebfiasouebfaisb";
         assert!(!is_valid_modder(modder))
     }
+
+    #[test]
+    fn can_parse_mod_list() {
+        let mods = parse_mod_list(r#"{"mods": [
+            {"name": "base", "enabled": true},
+            {"name": "bobores", "enabled": false},
+            {"name": "FARL", "enabled": "true"}
+        ]}"#).unwrap();
+        assert_eq!(mods, vec![(String::from("base"), true),
+                              (String::from("bobores"), false),
+                              (String::from("FARL"), true)]);
+        assert!(parse_mod_list("{}").is_err());
+        assert!(parse_mod_list("not json").is_err());
+    }
+
+    #[test]
+    fn can_compare_saved_mods_with_portal() {
+        let portal = parse_json_into_mod(&json::parse(r#"{
+            "name": "bobores", "title": "Bob's Ores", "owner": "Bobingabout", "downloads_count": 100,
+            "latest_release": {"version": "0.16.3", "factorio_version": "0.16"}
+        }"#).unwrap()).unwrap();
+        assert_eq!(compare_with_portal("bobores", "0.16.3", "0.16.51", Some(&portal)),
+                   "[bobores](https://mods.factorio.com/mods/Bobingabout/bobores) 0.16.3, up to date");
+        assert_eq!(compare_with_portal("bobores", "0.15.1", "0.15.40", Some(&portal)),
+                   "[bobores](https://mods.factorio.com/mods/Bobingabout/bobores) 0.15.1, latest is 0.16.3 for 0.16");
+        assert_eq!(compare_with_portal("private", "1.0.0", "0.16.51", None), "private 1.0.0, not on the mod portal");
+    }
+
+    /// Makes a mod as the portal would return it, with the dependencies given.
+    fn test_mod(name: &str, version: &str, factorio_version: &str, dependencies: &[&str]) -> Mod {
+        let mut json = JsonValue::new_object();
+        json["name"] = name.into();
+        json["owner"] = "Tester".into();
+        json["downloads_count"] = 0.into();
+        json["latest_release"]["version"] = version.into();
+        json["latest_release"]["factorio_version"] = factorio_version.into();
+        json["latest_release"]["info_json"]["dependencies"] =
+            JsonValue::Array(dependencies.iter().map(|&dependency| dependency.into()).collect());
+        parse_json_into_mod(&json).unwrap()
+    }
+
+    #[test]
+    fn can_resolve_dependencies() {
+        let root = test_mod("bobwarfare", "0.16.5", "0.16",
+                            &["base >= 0.16", "boblibrary >= 0.16.2", "bobplates", "? bobores", "! FARL"]);
+        assert_eq!(root.dependencies, "base >= 0.16\nboblibrary >= 0.16.2\nbobplates\n! FARL\n...and 1 optional dependency.");
+
+        let mut lookups = Vec::new();
+        let tree = resolve_dependencies(&root, "0.16.51", &mut |name| {
+            lookups.push(name.to_owned());
+            match name {
+                "boblibrary" => Some(test_mod("boblibrary", "0.16.1", "0.16", &["base >= 0.16"])),
+                "bobplates" => Some(test_mod("bobplates", "0.16.3", "0.15", &["boblibrary", "private"])),
+                _ => None,
+            }
+        });
+        assert_eq!(tree.tree, vec!["- boblibrary >= 0.16.2, latest is 0.16.1",
+                                   "- bobplates, latest is 0.16.3",
+                                   "  - boblibrary, see above",
+                                   "  - private, not on the mod portal"]);
+        assert_eq!(lookups, vec!["boblibrary", "bobplates", "private"]);
+        assert_eq!(tree.optional, vec!["bobores, for bobwarfare"]);
+        assert_eq!(tree.incompatible, vec!["bobwarfare can't be used with FARL"]);
+        assert_eq!(tree.unmet, vec!["bobwarfare needs boblibrary >= 0.16.2, but the latest is 0.16.1",
+                                    "bobplates's latest version is for 0.15"]);
+        assert_eq!(tree.missing, vec!["private"]);
+
+        let tree = resolve_dependencies(&root, "0.15.40", &mut |_| None);
+        assert_eq!(tree.unmet[0], "bobwarfare's latest version is for 0.16");
+        assert_eq!(tree.unmet[1], "bobwarfare needs base >= 0.16");
+
+        // Mods needed by more than one are only looked up once, but checked for each of them
+        let root = test_mod("bobwarfare", "0.16.5", "0.16", &["boblibrary", "bobplates"]);
+        let mut lookups = Vec::new();
+        let tree = resolve_dependencies(&root, "", &mut |name| {
+            lookups.push(name.to_owned());
+            match name {
+                "boblibrary" => Some(test_mod("boblibrary", "0.16.1", "0.16", &[])),
+                "bobplates" => Some(test_mod("bobplates", "0.16.3", "0.16", &["boblibrary >= 0.16.2"])),
+                _ => None,
+            }
+        });
+        assert_eq!(lookups, vec!["boblibrary", "bobplates"]);
+        assert_eq!(tree.tree[2], "  - boblibrary >= 0.16.2, see above");
+        assert_eq!(tree.unmet, vec!["bobplates needs boblibrary >= 0.16.2, but the latest is 0.16.1"]);
+    }
+
+    #[test]
+    fn can_split_game_versions() {
+        assert_eq!(split_game_version("bobores 0.16"), (String::from("bobores"), String::from("0.16")));
+        assert_eq!(split_game_version("Bob's Ores"), (String::from("Bob's Ores"), String::new()));
+        assert_eq!(split_game_version("0.16"), (String::from("0.16"), String::new()));
+    }
+
+    #[test]
+    fn can_find_changes_for_version() {
+        let changelog = "---------------------------------------------------------------------------------------------------
+Version: 0.16.3
+Date: 2018-03-04
+  Bugfixes:
+    - Fixed a crash when mining ore.
+---------------------------------------------------------------------------------------------------
+Version: 0.16.2
+  Features:
+    - Added gems.";
+        assert_eq!(changes_for_version(changelog, "0.16.3"),
+                   Some(String::from("Date: 2018-03-04\n  Bugfixes:\n    - Fixed a crash when mining ore.")));
+        assert_eq!(changes_for_version(changelog, "0.16.2"), Some(String::from("  Features:\n    - Added gems.")));
+        assert_eq!(changes_for_version(changelog, "0.15.0"), None);
+        assert_eq!(changes_for_version("Fixed things.\n", "0.16.3"), Some(String::from("Fixed things.")));
+        assert_eq!(changes_for_version("", "0.16.3"), None);
+    }
+
+    #[test]
+    fn cache_entries_expire() {
+        let now = Utc::now();
+        let entry = CacheEntry {
+            json: JsonValue::Null,
+            fetched: now - Duration::seconds(30),
+            ttl: 60,
+        };
+        assert!(entry.is_fresh(now));
+        assert!(!entry.is_fresh(now + Duration::seconds(30)));
+    }
+
+    #[test]
+    fn cached_requests_fall_back_when_portal_is_down() {
+        let (_, cached_at) = cached_request("test:fresh", 60, || Some(json::parse(r#"{"results": []}"#).unwrap())).unwrap();
+        assert_eq!(cached_at, None);
+        let (json, cached_at) = cached_request("test:fresh", 60, || panic!("Asked the portal for a fresh response")).unwrap();
+        assert!(json["results"].is_array());
+        assert!(cached_at.is_some());
+
+        // Expires straight away, so the portal is asked again, but the old answer is still there
+        let _ = cached_request("test:expired", 0, || Some(JsonValue::Null));
+        let (_, cached_at) = cached_request("test:expired", 0, || None).unwrap();
+        assert!(cached_at.is_some());
+        assert_eq!(cached_request("test:missing", 60, || None), None);
+    }
+
+    #[test]
+    fn cache_makes_room_and_saves() {
+        let now = Utc::now();
+        let mut cache = PortalCache::default();
+        for index in 0..MAX_CACHE_ENTRIES + 1 {
+            let entry = CacheEntry {
+                json: index.into(),
+                fetched: now - Duration::seconds((MAX_CACHE_ENTRIES - index) as i64),
+                ttl: 3600,
+            };
+            cache.insert(&format!("search:{}", index), entry, now);
+        }
+        assert_eq!(cache.entries.len(), MAX_CACHE_ENTRIES);
+        assert!(!cache.entries.contains_key("search:0"));
+
+        let mut loaded = PortalCache::default();
+        loaded.read_json(&json::parse(&cache.to_json().dump()).unwrap());
+        assert_eq!(loaded.entries, cache.entries);
+
+        // Backed up at most once a minute, and only if it should outlast restarts
+        assert!(!cache.backup_due(now));
+        cache.persist = true;
+        assert!(cache.backup_due(now));
+        cache.backed_up = Some(now - Duration::seconds(30));
+        assert!(!cache.backup_due(now));
+        assert!(cache.backup_due(now + Duration::seconds(30)));
+    }
+
+    fn search_results(count: usize) -> JsonValue {
+        let mut results = JsonValue::new_array();
+        for index in 0..count {
+            let mut entry = JsonValue::new_object();
+            entry["name"] = format!("mod{}", index).into();
+            entry["title"] = format!("Mod {}", index).into();
+            entry["owner"] = "Tester".into();
+            entry["downloads_count"] = (index * 7 % 10).into();
+            entry["updated_at"] = format!("2018-06-{:02}T12:00:00.000000Z", index + 1).into();
+            // Every third one is too old
+            entry["latest_release"]["factorio_version"] = if index % 3 == 0 { "0.14" } else { "0.16" }.into();
+            if index % 2 == 0 {
+                entry["tags"][0]["name"] = "logistics".into();
+                entry["tags"][0]["title"] = "Logistics".into();
+            }
+            let _ = results.push(entry);
+        }
+        results
+    }
+
+    #[test]
+    fn can_page_through_search_results() {
+        let results = SearchFilters::default().apply(&search_results(25));
+        let mut pages = SearchPages::new(results, &SearchFilters::default(), None);
+        assert_eq!(pages.page_count(), 3);
+        // The 9 outdated ones are last
+        assert_eq!(pages.pick(1).unwrap()["name"], "mod1");
+        assert!(!pages.turn(false));
+        assert!(pages.turn(true));
+        assert!(pages.turn(true));
+        assert!(!pages.turn(true));
+        assert_eq!(pages.pick(5).unwrap()["name"], "mod24");
+        assert_eq!(pages.pick(6), None);
+        assert_eq!(pages.pick(0), None);
+
+        let page = serialize_search_page(&pages.results, 0);
+        assert!(page.starts_with("1. [Mod 1](https://mods.factorio.com/mods/Tester/mod1) by Tester, for 0.16\n"));
+        assert_eq!(page.lines().count(), SEARCH_PAGE_SIZE);
+        assert!(serialize_search_page(&pages.results, 2).ends_with("by Tester, for 0.14\n"));
+        assert_eq!(serialize_search_page(&[], 0), "No results.");
+
+        let later = pages.created + ::std::time::Duration::from_secs(SEARCH_PAGES_TIMEOUT);
+        assert!(!pages.is_expired(pages.created));
+        assert!(pages.is_expired(later));
+    }
+
+    #[test]
+    fn can_filter_search_results() {
+        let (request, filters) = SearchFilters::take_from("belts --version 0.16 --tag Logistics --sort downloads").unwrap();
+        assert_eq!(request, "belts");
+        assert_eq!(filters.describe(), "for 0.16, tagged Logistics, most downloaded first");
+        let results = filters.apply(&search_results(25));
+        // Even, and not a multiple of 3
+        assert_eq!(results.len(), 8);
+        assert!(results.windows(2).all(|pair| pair[0]["downloads_count"].as_u64() >= pair[1]["downloads_count"].as_u64()));
+
+        let (_, filters) = SearchFilters::take_from("--sort updated").unwrap();
+        assert_eq!(filters.apply(&search_results(25))[0]["name"], "mod24");
+        assert!(SearchFilters::take_from("belts").unwrap().1.is_empty());
+        assert!(SearchFilters::take_from("belts --version latest").is_err());
+        assert!(SearchFilters::take_from("belts --tag").is_err());
+        assert!(SearchFilters::take_from("belts --sort name").is_err());
+    }
+
+    #[test]
+    fn can_compare_mods() {
+        assert_eq!(split_mod_names("bobores angelsrefining bobores"), vec!["bobores", "angelsrefining"]);
+        assert_eq!(split_mod_names("Bob's Ores, angelsrefining,"), vec!["Bob's Ores", "angelsrefining"]);
+
+        let results = search_results(5);
+        assert_eq!(pick_search_result(&results, "MOD3").unwrap()["name"], "mod3");
+        assert_eq!(pick_search_result(&results, "Mod 4").unwrap()["name"], "mod4");
+        assert_eq!(pick_search_result(&results, "something else entirely"), None);
+
+        let mut first = test_mod("first", "1.0.0", "0.16", &["base >= 0.16", "second", "? third", "! other"]);
+        first.title = String::from("first");
+        first.download_count = 500;
+        first.last_updated = String::from("2018-06-01");
+        let mut other = test_mod("other", "1.0.0", "0.15", &[]);
+        other.title = String::from("other");
+        other.download_count = 20;
+        other.last_updated = String::from("2018-07-01");
+        let description = describe_for_comparison(&first);
+        assert!(description.contains("**Downloads:** 500\n"));
+        assert!(description.ends_with("**Dependencies:** second, and 1 optional"));
+        assert!(describe_for_comparison(&other).ends_with("**Dependencies:** None"));
+
+        let summary = summarize_comparison(&[first, other]);
+        assert!(summary.contains("Most downloaded: **first**"));
+        assert!(summary.contains("Most recently updated: **other**"));
+        assert!(summary.contains("aren't all for the same version"));
+        assert!(summary.contains("**first** is incompatible with **other**."));
+    }
+
+    #[test]
+    fn can_read_releases() {
+        let mut json = JsonValue::new_object();
+        json["name"] = "bobores".into();
+        json["owner"] = "Bobingabout".into();
+        json["downloads_count"] = 100.into();
+        json["changelog"] = "Version: 0.16.1\n  Bugfixes:\n    - Fixed ores.\nVersion: 0.16.0\n  - Updated for 0.16.".into();
+        for (index, &(version, factorio_version)) in [("0.15.9", "0.15"), ("0.16.0", "0.16"), ("0.16.1", "0.16")].iter().enumerate() {
+            json["releases"][index]["version"] = version.into();
+            json["releases"][index]["info_json"]["factorio_version"] = factorio_version.into();
+            json["releases"][index]["released_at"] = format!("2018-0{}-01T12:00:00.000000Z", index + 1).into();
+            json["releases"][index]["download_url"] = format!("/download/bobores/{}", index).into();
+        }
+        let modification = parse_json_into_mod(&json).unwrap();
+        assert!(parse_json_into_mod(&json::parse(r#"{"name": "broken", "downloads_count": "many"}"#).unwrap()).is_none());
+        assert_eq!(modification.latest_version, "0.16.1");
+        assert_eq!(modification.factorio_version, "0.16");
+        assert_eq!(modification.releases[0],
+                   Release {
+                       version: String::from("0.16.1"),
+                       factorio_version: String::from("0.16"),
+                       released_on: String::from("2018-03-01"),
+                       download_link: String::from("https://mods.factorio.com/download/bobores/2"),
+                   });
+        assert_eq!(changes_for_version(&modification.changelog, "0.16.1").unwrap(), "  Bugfixes:\n    - Fixed ores.");
+
+        let releases = serialize_releases(&modification.releases, "0.16.0");
+        assert_eq!(releases.lines().nth(1).unwrap(),
+                   "**[0.16.0](https://mods.factorio.com/download/bobores/1) for 0.16, 2018-02-01**");
+        let many: Vec<Release> = (0..50).map(|_| modification.releases[0].clone()).collect();
+        let releases = serialize_releases(&many, "");
+        assert!(releases.len() <= 1024);
+        assert!(releases.ends_with("older."));
+        assert_eq!(serialize_releases(&[], ""), "No releases.");
+        assert!(test_mod("bobores", "0.16.1", "0.16", &[]).releases.is_empty());
+    }
+
+    #[test]
+    fn can_read_page_reactions() {
+        assert_eq!(PageAction::from_emoji(NEXT_PAGE), Some(PageAction::Next));
+        assert_eq!(PageAction::from_emoji("3\u{20e3}"), Some(PageAction::Open(3)));
+        assert_eq!(PageAction::from_emoji("3\u{fe0f}\u{20e3}"), Some(PageAction::Open(3)));
+        assert_eq!(PageAction::from_emoji("\u{1f51f}"), Some(PageAction::Open(10)));
+        assert_eq!(PageAction::from_emoji("\u{1f44d}"), None);
+    }
+
+    #[test]
+    fn can_check_mod_versions() {
+        assert!(supports_version("0.16", "0.16.51"));
+        assert!(supports_version("0.16", "0.16"));
+        assert!(!supports_version("0.15", "0.16.51"));
+        assert!(!supports_version("0.1", "0.16"));
+        assert!(!supports_version("latest", "0.16"));
+    }
 }
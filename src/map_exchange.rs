@@ -0,0 +1,314 @@
+/// Holds the decoding of Factorio map exchange strings, which hold the settings a map was made with.
+/// A map exchange string is base64 of zlib compressed binary data, written little endian,
+/// between `>>>` and `<<<`. Only the map generation settings and the pollution, evolution
+/// and expansion settings are read, the rest of the map settings are left alone.
+
+use base64;
+use flate2::read::ZlibDecoder;
+
+use binary_reader::BinaryReader;
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io::Read;
+
+/// Marks the start of a map exchange string.
+const START_MARKER: &'static str = ">>>";
+/// Marks the end of a map exchange string.
+const END_MARKER: &'static str = "<<<";
+
+/// How often, how big and how rich a resource, or anything else placed {{{1
+/// on the map, is. Each is a setting from none to very high, see size_name.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AutoplaceControl {
+    pub frequency: u8,
+    pub size: u8,
+    pub richness: u8,
+}
+
+/// The shape of the cliffs on the map. {{{1
+#[derive(Debug, Clone, PartialEq)]
+pub struct CliffSettings {
+    pub name: String, //Entity used for cliffs
+    pub elevation_0: f32, //Height of the first row of cliffs
+    pub elevation_interval: f32, //Height between rows of cliffs, higher means fewer
+    pub richness: u8,
+}
+
+/// How pollution spreads and harms trees. {{{1
+#[derive(Debug, Clone, PartialEq)]
+pub struct PollutionSettings {
+    pub enabled: bool,
+    pub diffusion_ratio: f64, //Share of pollution spreading to neighbouring chunks each 64 ticks
+    pub min_to_diffuse: f64,
+    pub ageing: f64,
+    pub expected_max_per_chunk: f64,
+    pub min_to_show_per_chunk: f64,
+    pub min_pollution_to_damage_trees: f64,
+    pub pollution_with_max_forest_damage: f64,
+    pub pollution_per_tree_damage: f64,
+    pub pollution_restored_per_tree_damage: f64,
+    pub max_pollution_to_restore_trees: f64,
+}
+
+/// How quickly enemies evolve, from time, killed spawners and pollution. {{{1
+#[derive(Debug, Clone, PartialEq)]
+pub struct EvolutionSettings {
+    pub enabled: bool,
+    pub time_factor: f64,
+    pub destroy_factor: f64,
+    pub pollution_factor: f64,
+}
+
+/// How enemies build new bases. Cooldowns are in ticks, distances in chunks. {{{1
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExpansionSettings {
+    pub enabled: bool,
+    pub max_expansion_distance: u32,
+    pub friendly_base_influence_radius: u32,
+    pub enemy_building_influence_radius: u32,
+    pub building_coefficient: f64,
+    pub other_base_coefficient: f64,
+    pub neighbouring_chunk_coefficient: f64,
+    pub neighbouring_base_chunk_coefficient: f64,
+    pub max_colliding_tiles_coefficient: f64,
+    pub settler_group_min_size: u32,
+    pub settler_group_max_size: u32,
+    pub min_expansion_cooldown: u32,
+    pub max_expansion_cooldown: u32,
+}
+
+/// A decoded map exchange string. {{{1
+#[derive(Debug, Clone, PartialEq)]
+pub struct MapExchange {
+    pub version: [u16; 4], //Game version it was made in, like 0.16.51.0
+    pub terrain_segmentation: u8,
+    pub water: u8,
+    pub autoplace_controls: BTreeMap<String, AutoplaceControl>, //Resources, trees and enemy bases
+    pub seed: u32,
+    pub width: u32, //0 for unlimited
+    pub height: u32, //0 for unlimited
+    pub starting_area: u8,
+    pub peaceful_mode: bool,
+    pub starting_points: Vec<(f64, f64)>,
+    pub cliffs: CliffSettings,
+    pub pollution: PollutionSettings,
+    pub evolution: EvolutionSettings,
+    pub expansion: ExpansionSettings,
+}
+
+/// Reasons a map exchange string couldn't be decoded. {{{1
+#[derive(Debug, Clone, PartialEq)]
+pub enum MapStringError {
+    Empty,
+    Base64(String),
+    Zlib(String),
+    Truncated,
+}
+
+impl fmt::Display for MapStringError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            MapStringError::Empty => write!(f, "The map exchange string is empty."),
+            MapStringError::Base64(ref reason) => write!(f, "The map exchange string isn't valid base64: {}", reason),
+            MapStringError::Zlib(ref reason) => write!(f, "The map exchange string couldn't be decompressed: {}", reason),
+            MapStringError::Truncated => write!(f, "The map exchange string ends too early, it may be from a different version of the game."),
+        }
+    }
+}
+
+impl MapExchange {
+    /// Decodes a map exchange string, with or without the markers around it. {{{2
+    pub fn decode(string: &str) -> Result<MapExchange, MapStringError> {
+        // Strings pasted into chat often pick up line breaks
+        let string: String = string.chars().filter(|c| !c.is_whitespace()).collect();
+        let string = string.trim_left_matches(START_MARKER).trim_right_matches(END_MARKER);
+        if string.is_empty() {
+            return Err(MapStringError::Empty);
+        }
+
+        let compressed = match base64::decode(string) {
+            Ok(compressed) => compressed,
+            Err(error) => return Err(MapStringError::Base64(format!("{}", error))),
+        };
+        let mut data = Vec::new();
+        if let Err(error) = ZlibDecoder::new(&compressed[..]).read_to_end(&mut data) {
+            return Err(MapStringError::Zlib(format!("{}", error)));
+        }
+        MapExchange::read(&mut BinaryReader::new(&data))
+    }
+
+    /// Reads the decompressed data of a map exchange string. {{{2
+    pub fn read(reader: &mut BinaryReader) -> Result<MapExchange, MapStringError> {
+        let version = [reader.u16(), reader.u16(), reader.u16(), reader.u16()];
+        // Always 0 so far
+        reader.u8();
+
+        // Map generation settings
+        let terrain_segmentation = reader.u8();
+        let water = reader.u8();
+        let mut autoplace_controls = BTreeMap::new();
+        for _ in 0..reader.count() {
+            let name = reader.string();
+            let control = AutoplaceControl {
+                frequency: reader.u8(),
+                size: reader.u8(),
+                richness: reader.u8(),
+            };
+            if reader.is_truncated() {
+                break;
+            }
+            autoplace_controls.insert(name, control);
+        }
+        let seed = reader.u32();
+        let width = reader.u32();
+        let height = reader.u32();
+        let starting_area = reader.u8();
+        let peaceful_mode = reader.bool();
+        let mut starting_points = Vec::new();
+        for _ in 0..reader.count() {
+            // Positions are fixed point, in 256ths of a tile
+            let x = reader.i32() as f64 / 256.0;
+            let y = reader.i32() as f64 / 256.0;
+            if reader.is_truncated() {
+                break;
+            }
+            starting_points.push((x, y));
+        }
+        let cliffs = CliffSettings {
+            name: reader.string(),
+            elevation_0: reader.f32(),
+            elevation_interval: reader.f32(),
+            richness: reader.u8(),
+        };
+
+        // Map settings, in the order the game writes them
+        let pollution = PollutionSettings {
+            enabled: reader.bool(),
+            diffusion_ratio: reader.f64(),
+            min_to_diffuse: reader.f64(),
+            ageing: reader.f64(),
+            expected_max_per_chunk: reader.f64(),
+            min_to_show_per_chunk: reader.f64(),
+            min_pollution_to_damage_trees: reader.f64(),
+            pollution_with_max_forest_damage: reader.f64(),
+            pollution_per_tree_damage: reader.f64(),
+            pollution_restored_per_tree_damage: reader.f64(),
+            max_pollution_to_restore_trees: reader.f64(),
+        };
+        let evolution = EvolutionSettings {
+            enabled: reader.bool(),
+            time_factor: reader.f64(),
+            destroy_factor: reader.f64(),
+            pollution_factor: reader.f64(),
+        };
+        let expansion = ExpansionSettings {
+            enabled: reader.bool(),
+            max_expansion_distance: reader.u32(),
+            friendly_base_influence_radius: reader.u32(),
+            enemy_building_influence_radius: reader.u32(),
+            building_coefficient: reader.f64(),
+            other_base_coefficient: reader.f64(),
+            neighbouring_chunk_coefficient: reader.f64(),
+            neighbouring_base_chunk_coefficient: reader.f64(),
+            max_colliding_tiles_coefficient: reader.f64(),
+            settler_group_min_size: reader.u32(),
+            settler_group_max_size: reader.u32(),
+            min_expansion_cooldown: reader.u32(),
+            max_expansion_cooldown: reader.u32(),
+        };
+
+        if reader.is_truncated() {
+            return Err(MapStringError::Truncated);
+        }
+        Ok(MapExchange {
+            version,
+            terrain_segmentation,
+            water,
+            autoplace_controls,
+            seed,
+            width,
+            height,
+            starting_area,
+            peaceful_mode,
+            starting_points,
+            cliffs,
+            pollution,
+            evolution,
+            expansion,
+        })
+    }
+
+    /// Formats the game version the string was made in, such as 0.16.51. {{{2
+    pub fn version_string(&self) -> String {
+        format!("{}.{}.{}", self.version[0], self.version[1], self.version[2])
+    }
+}
+
+// Functions {{{1
+/// Returns the name of a map generation setting, as shown in the game. {{{2
+pub fn size_name(size: u8) -> &'static str {
+    match size {
+        0 => "None",
+        1 => "Very low",
+        2 => "Low",
+        3 => "Normal",
+        4 => "High",
+        5 => "Very high",
+        _ => "Unknown",
+    }
+}
+
+// Tests {{{1
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Made in 0.16.51 with seed 123456789, an unlimited map, high water, very high
+    // iron ore, no uranium and low enemy bases
+    const TEST_MAP: &'static str = ">>>eNpjYBBgMGYAAmYWDpbk/MQcZmZmruT8goLUIt38olQgjzO5qDQlVTc/M4eJhZUrNS81t1I3KbE4lZGRmSOzKD8PpIwVCIpL8vNA6llLilJTi4EM7tKixLzM0lyQAgZmZtGz0ewMUMDCwAhjsibnZKalMTAoOAKxEzNjtcg694dVU+whsnoOEPoDhH8gCcr3hNARqyF0x2EI7TAfStdD1Zk4MPa+3brg+7ELdn9Wfrzkm5Rgnykb6itQ+t6OEeQaJiieNRMEdkJtZYDqfmAPEb9pf/YMCLyxZwWKioAUWAAd483MgA6i/uesBQChaExW<<<";
+    // Made with seed 42, a 2000 by 1500 map, peaceful mode and no expansion
+    const SMALL_MAP: &'static str = ">>>eNpjYBBgMGYAAmYWDpbk/MQcZmZmruT8goLUIt38olQgjzO5qDQlVTc/M4eJhZUrNS81t1I3KbE4lZGRmSOzKD8PpIwVCIpL8vNA6llLilJTi4EM7tKixLzM0lyQAgZmZi2gJRfYGRjusDIwsDAyMkABa3JOZloaA4OCIxA7MTNWi6xzf1g1xR4iq+cAoT9A+AeSoHxPCB2xGkJ3HIbQDvOhdD1UnYkDY+/brQu+H7tg92flx0u+SQn2mbKhvgKl7+0YgG5hYILiWTNBYCfUVgao7gf2EPGb9mfPgMAbe6DbGURACiyAjvFmZkAHPVIpHgAmlEvU<<<";
+
+    #[test]
+    fn can_decode_map_string() {
+        let map = MapExchange::decode(TEST_MAP).unwrap();
+        assert_eq!(map.version_string(), "0.16.51");
+        assert_eq!(map.seed, 123456789);
+        assert_eq!((map.width, map.height), (0, 0));
+        assert_eq!(size_name(map.water), "High");
+        assert!(!map.peaceful_mode);
+        assert_eq!(map.starting_points, vec![(0.0, 0.0)]);
+        assert_eq!(map.autoplace_controls.len(), 8);
+        assert_eq!(map.autoplace_controls["iron-ore"],
+                   AutoplaceControl { frequency: 5, size: 5, richness: 5 });
+        assert_eq!(map.autoplace_controls["uranium-ore"].frequency, 0);
+        assert_eq!(map.cliffs.elevation_interval, 40.0);
+        assert_eq!(map.pollution.diffusion_ratio, 0.02);
+        assert_eq!(map.pollution.min_pollution_to_damage_trees, 3500.0);
+        assert_eq!(map.evolution.time_factor, 0.000004);
+        assert!(map.expansion.enabled);
+        assert_eq!(map.expansion.max_expansion_cooldown, 216000);
+
+        let small = MapExchange::decode(SMALL_MAP).unwrap();
+        assert_eq!(small.seed, 42);
+        assert_eq!((small.width, small.height), (2000, 1500));
+        assert!(small.peaceful_mode);
+        assert!(!small.expansion.enabled);
+    }
+
+    #[test]
+    fn rejects_bad_strings() {
+        assert_eq!(MapExchange::decode(">>><<<"), Err(MapStringError::Empty));
+        match MapExchange::decode(">>>!!!<<<") {
+            Err(MapStringError::Base64(_)) => {}
+            other => panic!("Expected a base64 error, got {:?}", other),
+        }
+        // Cut off in the middle of the data
+        let mut data = Vec::new();
+        ZlibDecoder::new(&base64::decode(&TEST_MAP[3..TEST_MAP.len() - 3]).unwrap()[..])
+            .read_to_end(&mut data)
+            .unwrap();
+        assert_eq!(MapExchange::read(&mut BinaryReader::new(&data[..100])), Err(MapStringError::Truncated));
+    }
+}
@@ -0,0 +1,194 @@
+use serenity::model::Message;
+use serenity::utils::Colour;
+use serenity::framework::standard::CommandError;
+
+use std::collections::BTreeMap;
+
+use common_funcs::*;
+use map_exchange::*;
+
+/// Game ticks in a minute, for turning cooldowns into something readable.
+const TICKS_PER_MINUTE: f64 = 3600.0;
+
+/// Decodes a map exchange string, and lists the settings the map was made with. {{{1
+/// Should be called with the map exchange string, the markers around it are optional.
+command!(mapstring(_context, message) {
+    let _ = message.channel_id.broadcast_typing();
+
+    let request = fix_message(message.content_safe(), "mapstring");
+    if request.trim().is_empty() {
+        send_error_embed_or_say(&message, "You must provide a map exchange string after the command.");
+        return Err(CommandError::from("Missing map string, failed."));
+    }
+
+    let map = match MapExchange::decode(&request) {
+        Ok(map) => map,
+        Err(error) => {
+            send_error_embed_or_say(&message, &format!("Sorry, I couldn't read that map exchange string. {}", error));
+            return Err(CommandError::from("Couldn't decode map string."));
+        }
+    };
+
+    let result = message.channel_id.send_message(|a| a
+                                                 .embed(|b| b
+                                                        .title("Map exchange string")
+                                                        .field(|c| c
+                                                               .name("Map")
+                                                               .value(&serialize_map_overview(&map)))
+                                                        .field(|c| c
+                                                               .name("Resources and enemies")
+                                                               .value(&truncate_for_embed(&serialize_autoplace_controls(&map.autoplace_controls), 1024)))
+                                                        .field(|c| c
+                                                               .name("Pollution")
+                                                               .value(&serialize_pollution(&map.pollution))
+                                                               .inline(true))
+                                                        .field(|c| c
+                                                               .name("Evolution")
+                                                               .value(&serialize_evolution(&map.evolution))
+                                                               .inline(true))
+                                                        .field(|c| c
+                                                               .name("Expansion")
+                                                               .value(&serialize_expansion(&map.expansion))
+                                                               .inline(true))
+                                                        .footer(|c| c
+                                                                .text(&format!("Made in Factorio {}.", map.version_string())))
+                                                        .timestamp(message.timestamp.to_rfc3339())
+                                                        .color(Colour::from_rgb(10, 225, 249))
+                                                       ));
+    if let Err(_) = result {
+        say_into_chat(&message, "Sorry, I couldn't make an embed here. Contact an admin.");
+    }
+});
+
+// Functions {{{1
+/// Serializes the seed, size, and terrain of the map. {{{2
+fn serialize_map_overview(map: &MapExchange) -> String {
+    let size = if map.width == 0 && map.height == 0 {
+        String::from("Unlimited")
+    } else {
+        format!("{} by {} tiles", dimension(map.width), dimension(map.height))
+    };
+    let cliffs = if map.cliffs.richness == 0 {
+        String::from("None")
+    } else {
+        format!("{} continuity, {} elevation apart", size_name(map.cliffs.richness), map.cliffs.elevation_interval)
+    };
+    format!("Seed: {}\nSize: {}\nStarting area: {}\nWater: {}\nTerrain segmentation: {}\nCliffs: {}\nPeaceful mode: {}",
+            map.seed,
+            size,
+            size_name(map.starting_area),
+            size_name(map.water),
+            size_name(map.terrain_segmentation),
+            cliffs,
+            if map.peaceful_mode { "On" } else { "Off" })
+}
+
+/// Formats one side of the map, where 0 means it has no limit. {{{2
+fn dimension(tiles: u32) -> String {
+    if tiles == 0 {
+        String::from("unlimited")
+    } else {
+        tiles.to_string()
+    }
+}
+
+/// Serializes how often, how big and how rich each resource, and the trees {{{2
+/// and enemy bases, are.
+fn serialize_autoplace_controls(controls: &BTreeMap<String, AutoplaceControl>) -> String {
+    if controls.is_empty() {
+        return String::from("Nothing.");
+    }
+    let mut result = String::new();
+    for (name, control) in controls.iter() {
+        if control.frequency == 0 {
+            result += &format!("**{}**: None\n", control_name(name));
+        } else {
+            result += &format!("**{}**: {} frequency, {} size, {} richness\n",
+                               control_name(name),
+                               size_name(control.frequency),
+                               size_name(control.size),
+                               size_name(control.richness));
+        }
+    }
+    result
+}
+
+/// Turns the name of an autoplace control into something readable, like iron-ore into Iron ore. {{{2
+fn control_name(name: &str) -> String {
+    let name = name.replace("-", " ");
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().collect::<String>() + chars.as_str(),
+        None => name.clone(),
+    }
+}
+
+/// Serializes how pollution spreads. {{{2
+fn serialize_pollution(pollution: &PollutionSettings) -> String {
+    if !pollution.enabled {
+        return String::from("Disabled");
+    }
+    format!("Diffusion ratio: {}%\nMinimum to diffuse: {}\nAgeing: {}\nDamages trees above: {}",
+            pollution.diffusion_ratio * 100.0,
+            pollution.min_to_diffuse,
+            pollution.ageing,
+            pollution.min_pollution_to_damage_trees)
+}
+
+/// Serializes how quickly enemies evolve. {{{2
+fn serialize_evolution(evolution: &EvolutionSettings) -> String {
+    if !evolution.enabled {
+        return String::from("Disabled");
+    }
+    format!("Time factor: {}\nDestroy factor: {}\nPollution factor: {}",
+            evolution.time_factor,
+            evolution.destroy_factor,
+            evolution.pollution_factor)
+}
+
+/// Serializes how often and how far enemies expand. {{{2
+fn serialize_expansion(expansion: &ExpansionSettings) -> String {
+    if !expansion.enabled {
+        return String::from("Disabled");
+    }
+    format!("Every {} to {} minutes\nUp to {} chunks away\nGroups of {} to {}",
+            expansion.min_expansion_cooldown as f64 / TICKS_PER_MINUTE,
+            expansion.max_expansion_cooldown as f64 / TICKS_PER_MINUTE,
+            expansion.max_expansion_distance,
+            expansion.settler_group_min_size,
+            expansion.settler_group_max_size)
+}
+
+// Tests {{{1
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_serialize_map_settings() {
+        let mut controls = BTreeMap::new();
+        controls.insert(String::from("iron-ore"), AutoplaceControl { frequency: 5, size: 3, richness: 4 });
+        controls.insert(String::from("uranium-ore"), AutoplaceControl { frequency: 0, size: 3, richness: 3 });
+        assert_eq!(serialize_autoplace_controls(&controls),
+                   "**Iron ore**: Very high frequency, Normal size, High richness\n**Uranium ore**: None\n");
+
+        let mut expansion = ExpansionSettings {
+            enabled: true,
+            max_expansion_distance: 7,
+            friendly_base_influence_radius: 2,
+            enemy_building_influence_radius: 2,
+            building_coefficient: 0.1,
+            other_base_coefficient: 2.0,
+            neighbouring_chunk_coefficient: 0.5,
+            neighbouring_base_chunk_coefficient: 0.4,
+            max_colliding_tiles_coefficient: 0.9,
+            settler_group_min_size: 5,
+            settler_group_max_size: 20,
+            min_expansion_cooldown: 14400,
+            max_expansion_cooldown: 216000,
+        };
+        assert_eq!(serialize_expansion(&expansion), "Every 4 to 60 minutes\nUp to 7 chunks away\nGroups of 5 to 20");
+        expansion.enabled = false;
+        assert_eq!(serialize_expansion(&expansion), "Disabled");
+    }
+}
@@ -0,0 +1,184 @@
+/// Reads the dependencies mods list in their info.json, as the mod portal gives them.
+/// Each is a mod name, with a prefix saying what kind it is, and optionally a version
+/// it needs, like "? bobores >= 0.16.0".
+
+use semver::Version;
+
+use std::cmp::Ordering;
+use std::fmt;
+
+/// What a mod needs of one of its dependencies. {{{1
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DependencyKind {
+    Required,
+    Optional,
+    HiddenOptional, //Optional, but not shown in the game's mod list
+    Incompatible,
+}
+
+/// A dependency of a mod. {{{1
+#[derive(Debug, Clone, PartialEq)]
+pub struct Dependency {
+    pub kind: DependencyKind,
+    pub name: String,
+    pub constraint: Option<(String, String)>, //Comparison and version, like >= and 0.16.0
+}
+
+impl Dependency {
+    /// Reads a dependency. Returns None if there's no mod name in it. {{{2
+    pub fn parse(text: &str) -> Option<Dependency> {
+        let text = text.trim();
+        let (kind, rest) = if text.starts_with("(?)") {
+            (DependencyKind::HiddenOptional, &text[3..])
+        } else if text.starts_with('?') {
+            (DependencyKind::Optional, &text[1..])
+        } else if text.starts_with('!') {
+            (DependencyKind::Incompatible, &text[1..])
+        } else if text.starts_with('~') {
+            // Only changes the load order, otherwise it's required
+            (DependencyKind::Required, &text[1..])
+        } else {
+            (DependencyKind::Required, text)
+        };
+
+        // Names can have spaces in them, so the comparison is what ends them
+        let (name, constraint) = match rest.find(|c| c == '<' || c == '>' || c == '=') {
+            Some(start) => {
+                let comparison_length = if rest[start + 1..].starts_with('=') { 2 } else { 1 };
+                let comparison = &rest[start..start + comparison_length];
+                let version = rest[start + comparison_length..].trim();
+                (&rest[..start], Some((comparison.to_owned(), version.to_owned())))
+            }
+            None => (rest, None),
+        };
+        let name = name.trim();
+        if name.is_empty() {
+            return None;
+        }
+        Some(Dependency {
+            kind,
+            name: name.to_owned(),
+            constraint,
+        })
+    }
+
+    /// Returns the name, and the version needed if there is one, like bobores >= 0.16.0. {{{2
+    pub fn requirement(&self) -> String {
+        match self.constraint {
+            Some((ref comparison, ref version)) => format!("{} {} {}", self.name, comparison, version),
+            None => self.name.clone(),
+        }
+    }
+
+    /// Returns true if the kind is either of the optional ones. {{{2
+    pub fn is_optional(&self) -> bool {
+        self.kind == DependencyKind::Optional || self.kind == DependencyKind::HiddenOptional
+    }
+
+    /// Checks if a version of the dependency is one the mod accepts. {{{2
+    pub fn is_met_by(&self, version: &str) -> bool {
+        let (comparison, wanted) = match self.constraint {
+            Some((ref comparison, ref wanted)) => (comparison, wanted),
+            None => return true,
+        };
+        let ordering = compare_versions(version, wanted);
+        match comparison.as_str() {
+            "<" => ordering == Ordering::Less,
+            "<=" => ordering != Ordering::Greater,
+            "=" => ordering == Ordering::Equal,
+            ">=" => ordering != Ordering::Less,
+            ">" => ordering == Ordering::Greater,
+            _ => true,
+        }
+    }
+}
+
+impl fmt::Display for Dependency {
+    /// Writes the dependency back out the way mods list it.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let prefix = match self.kind {
+            DependencyKind::Required => "",
+            DependencyKind::Optional => "? ",
+            DependencyKind::HiddenOptional => "(?) ",
+            DependencyKind::Incompatible => "! ",
+        };
+        write!(f, "{}{}", prefix, self.requirement())
+    }
+}
+
+// Functions {{{1
+/// Reads a version, filling in missing parts with 0, so 0.16 reads as 0.16.0. {{{2
+/// Returns None if it isn't a semantic version once filled in.
+pub fn parse_version(text: &str) -> Option<Version> {
+    let text = text.trim();
+    let parts = text.split('.').count();
+    if parts > 3 {
+        return None;
+    }
+    Version::parse(&format!("{}{}", text, ".0".repeat(3 - parts))).ok()
+}
+
+/// Compares two versions as semantic versions, so 0.16.10 comes after 0.16.9, {{{2
+/// and 1.0 after 0.18. Versions that can't be read as one, like 0.16.03, are
+/// compared part by part as numbers instead, with anything else counting as 0.
+pub fn compare_versions(first: &str, second: &str) -> Ordering {
+    if let (Some(first), Some(second)) = (parse_version(first), parse_version(second)) {
+        return first.cmp(&second);
+    }
+    let first: Vec<u32> = first.trim().split('.').map(|part| part.parse().unwrap_or(0)).collect();
+    let second: Vec<u32> = second.trim().split('.').map(|part| part.parse().unwrap_or(0)).collect();
+    for index in 0..first.len().max(second.len()) {
+        let ordering = first.get(index).unwrap_or(&0).cmp(second.get(index).unwrap_or(&0));
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+    Ordering::Equal
+}
+
+// Tests {{{1
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_parse_dependencies() {
+        let dependency = Dependency::parse("? bobores >= 0.16.0").unwrap();
+        assert_eq!(dependency.kind, DependencyKind::Optional);
+        assert_eq!(dependency.name, "bobores");
+        assert_eq!(dependency.constraint, Some((String::from(">="), String::from("0.16.0"))));
+
+        let dependency = Dependency::parse("(?) Bob's Ores").unwrap();
+        assert_eq!(dependency.kind, DependencyKind::HiddenOptional);
+        assert_eq!(dependency.name, "Bob's Ores");
+        assert_eq!(dependency.constraint, None);
+
+        assert_eq!(Dependency::parse("!FARL").unwrap().kind, DependencyKind::Incompatible);
+        assert_eq!(Dependency::parse("base=0.16.51").unwrap().constraint,
+                   Some((String::from("="), String::from("0.16.51"))));
+        assert_eq!(format!("{}", Dependency::parse("?bobores>=0.16.0").unwrap()), "? bobores >= 0.16.0");
+        assert_eq!(Dependency::parse("? "), None);
+    }
+
+    #[test]
+    fn can_check_constraints() {
+        let dependency = Dependency::parse("bobores >= 0.16.9").unwrap();
+        assert!(dependency.is_met_by("0.16.10"));
+        assert!(dependency.is_met_by("0.16.9"));
+        assert!(!dependency.is_met_by("0.15.20"));
+        assert!(Dependency::parse("bobores < 0.17").unwrap().is_met_by("0.16.9"));
+        assert!(Dependency::parse("bobores").unwrap().is_met_by("0.1.0"));
+        assert_eq!(compare_versions("0.16", "0.16.0"), Ordering::Equal);
+    }
+
+    #[test]
+    fn can_compare_versions() {
+        assert_eq!(parse_version("0.16"), Some(Version::new(0, 16, 0)));
+        assert_eq!(parse_version("1"), Some(Version::new(1, 0, 0)));
+        assert_eq!(parse_version("0.16.51.0"), None);
+        assert_eq!(parse_version("latest"), None);
+        assert_eq!(compare_versions("0.9", "0.15"), Ordering::Less);
+        assert_eq!(compare_versions("1.0", "0.18"), Ordering::Greater);
+        assert_eq!(compare_versions("0.16.03", "0.16.2"), Ordering::Greater);
+    }
+}
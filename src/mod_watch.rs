@@ -0,0 +1,464 @@
+/// Lets moderators watch mods, or everything a modder makes, so new versions get
+/// announced in a channel of their choosing. Watches are kept per guild in a json file,
+/// along with the last version seen of each mod, so restarts don't announce them again.
+
+use json::{self, JsonValue};
+
+use chrono::prelude::*;
+
+use serenity::framework::standard::CommandError;
+use serenity::model::{Channel, ChannelId, GuildId};
+use serenity::utils::Colour;
+
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{File, OpenOptions};
+use std::io::prelude::*;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use common_funcs::*;
+use linkmod::*;
+
+lazy_static! {
+    /// What each guild is watching.
+    static ref MOD_WATCHES: Arc<Mutex<HashMap<GuildId, Vec<ModWatch>>>> = Arc::new(Mutex::new(HashMap::new()));
+}
+
+const WATCH_FILE: &'static str = "mod_watches.json";
+/// How often the portal is checked for new versions, in seconds.
+const POLL_INTERVAL: u64 = 600;
+/// The most watches a guild can have, since each takes a request every poll.
+const MAX_WATCHES: usize = 20;
+
+/// A mod or modder being watched for new versions. {{{1
+#[derive(Debug, Clone, PartialEq)]
+pub struct ModWatch {
+    pub name: String, //Internal name of the mod, or username of the modder
+    pub is_modder: bool,
+    pub channel: ChannelId, //Where new versions are announced
+    pub seen: BTreeMap<String, String>, //Last version seen of each mod, by internal name
+}
+
+impl ModWatch {
+    /// Finds the mods with a version that hasn't been seen yet, including {{{2
+    /// mods a modder has made since the last check.
+    fn updated<'a>(&self, mods: &'a [Mod]) -> Vec<&'a Mod> {
+        mods.iter()
+            .filter(|modification| self.seen.get(&modification.name) != Some(&modification.latest_version))
+            .collect()
+    }
+
+    /// Remembers the versions of the mods given as seen. {{{2
+    fn mark_seen(&mut self, mods: &[&Mod]) {
+        for modification in mods.iter() {
+            self.seen.insert(modification.name.clone(), modification.latest_version.clone());
+        }
+    }
+
+    /// Turns the watch into json, for the watch file. {{{2
+    fn to_json(&self) -> JsonValue {
+        let mut json = JsonValue::new_object();
+        json["name"] = self.name.clone().into();
+        json["modder"] = self.is_modder.into();
+        // As a string, since ids are too big for some json readers
+        json["channel"] = self.channel.0.to_string().into();
+        json["seen"] = JsonValue::new_object();
+        for (name, version) in self.seen.iter() {
+            json["seen"][name.as_str()] = version.clone().into();
+        }
+        json
+    }
+
+    /// Reads a watch from the watch file. Returns None if it's missing anything. {{{2
+    fn from_json(json: &JsonValue) -> Option<ModWatch> {
+        let name = match json["name"].as_str() {
+            Some(name) => name.to_owned(),
+            None => return None,
+        };
+        let channel = match json["channel"].as_str().and_then(|channel| channel.parse::<u64>().ok()) {
+            Some(channel) => ChannelId::from(channel),
+            None => return None,
+        };
+        let seen = json["seen"]
+            .entries()
+            .filter_map(|(name, version)| version.as_str().map(|version| (name.to_owned(), version.to_owned())))
+            .collect();
+        Some(ModWatch {
+            name,
+            is_modder: json["modder"].as_bool().unwrap_or(false),
+            channel,
+            seen,
+        })
+    }
+}
+
+/// Starts watching a mod or modder, announcing new versions in the channel mentioned, {{{1
+/// or this one if none is. Can only be used by moderators.
+command!(watch_mod(_context, message) {
+    let guild_id = message.guild_id().unwrap();
+    let content = fix_message(message.content_safe(), "watch-mod");
+    let request = remove_channel_mentions(&content);
+    let channel = find_channel_mention(&content).unwrap_or(message.channel_id);
+    let _ = message.channel_id.broadcast_typing();
+
+    // Announcements may only go to channels of the server the watch was made in
+    let channel_guild = match channel.get() {
+        Ok(Channel::Guild(guild_channel)) => Some(guild_channel.read().unwrap().guild_id),
+        _ => None,
+    };
+    if channel_guild != Some(guild_id) {
+        send_error_embed_or_say(&message, "I can only announce mod updates in a channel of this server.");
+        return Err(CommandError::from("Mentioned channel isn't in this guild."));
+    }
+
+    if request.is_empty() {
+        send_error_embed_or_say(&message, "Expected a mod or modder to watch, and optionally a channel to announce them in.");
+        return Err(CommandError::from("User didn't provide an argument."));
+    }
+    let watch_count = MOD_WATCHES.lock().unwrap().get(&guild_id).map(|watches| watches.len()).unwrap_or(0);
+    if watch_count >= MAX_WATCHES {
+        send_error_embed_or_say(&message, &format!("This server already watches {} mods and modders, \
+                                                    remove some with `unwatch-mod` first.", MAX_WATCHES));
+        return Err(CommandError::from("Too many watches."));
+    }
+
+    let (name, is_modder, mods) = match find_watchable(&request) {
+        Some(found) => found,
+        None => {
+            send_error_embed_or_say(&message, "Couldn't find a mod or modder by that name on the mod portal.");
+            return Err(CommandError::from("Nothing to watch by that name."));
+        }
+    };
+    let mut watch = ModWatch {
+        name: name.clone(),
+        is_modder,
+        channel,
+        seen: BTreeMap::new(),
+    };
+    // Only versions released from now on are announced
+    watch.mark_seen(&mods.iter().collect::<Vec<_>>());
+
+    {
+        let mut watches = MOD_WATCHES.lock().unwrap();
+        let guild_watches = watches.entry(guild_id).or_insert_with(Vec::new);
+        guild_watches.retain(|existing| !(existing.name == name && existing.is_modder == is_modder));
+        guild_watches.push(watch);
+    }
+    backup_mod_watches();
+
+    let watched = if is_modder {
+        format!("the {} mods by {}", mods.len(), name)
+    } else {
+        name.clone()
+    };
+    let reply = format!("Watching {}, new versions will be announced in <#{}>.", watched, channel.0);
+    if let Err(_) = send_success_embed(&message, &reply) {
+        say_into_chat(&message, &reply);
+    }
+});
+
+/// Stops watching a mod or modder. Can only be used by moderators. {{{1
+command!(unwatch_mod(_context, message) {
+    let guild_id = message.guild_id().unwrap();
+    let request = fix_message(message.content_safe(), "unwatch-mod");
+
+    let removed = {
+        let mut watches = MOD_WATCHES.lock().unwrap();
+        let guild_watches = watches.entry(guild_id).or_insert_with(Vec::new);
+        let count = guild_watches.len();
+        guild_watches.retain(|watch| !watch.name.eq_ignore_ascii_case(&request));
+        count - guild_watches.len()
+    };
+    if removed == 0 {
+        send_error_embed_or_say(&message, "This server isn't watching anything by that name, see `mod-watches` for what it is.");
+        return Err(CommandError::from("Nothing watched by that name."));
+    }
+    backup_mod_watches();
+
+    let reply = format!("No longer watching {}.", request);
+    if let Err(_) = send_success_embed(&message, &reply) {
+        say_into_chat(&message, &reply);
+    }
+});
+
+/// Lists the mods and modders this guild is watching. {{{1
+command!(mod_watches(_context, message) {
+    let guild_id = message.guild_id().unwrap();
+    let list = {
+        let watches = MOD_WATCHES.lock().unwrap();
+        match watches.get(&guild_id) {
+            Some(guild_watches) if !guild_watches.is_empty() => serialize_watches(guild_watches),
+            _ => String::from("Nothing yet, moderators can add mods or modders with `watch-mod`."),
+        }
+    };
+
+    let result = message.channel_id.send_message(|a| a
+                                                 .embed(|b| b
+                                                        .title("Watched for new versions")
+                                                        .description(&truncate_for_embed(&list, 2048))
+                                                        .footer(|c| c
+                                                                .text(&format!("The mod portal is checked every {} minutes.", POLL_INTERVAL / 60)))
+                                                        .color(Colour::from_rgb(255, 34, 108))
+                                                        .timestamp(message.timestamp.to_rfc3339())
+                                                       ));
+    if let Err(_) = result {
+        say_into_chat(&message, "Unable to make an embed here.");
+        return Err(CommandError::from("Couldn't make an embed."));
+    }
+});
+
+// Functions {{{1
+/// Reads the watch file into MOD_WATCHES, creating it if it's not there. {{{2
+/// Should be called on startup.
+pub fn install_mod_watches() {
+    if let Ok(mut handle) = File::open(WATCH_FILE) {
+        let mut json = String::new();
+        let _ = handle.read_to_string(&mut json);
+        let parsed_json = json::parse(json.as_str()).expect("Could not parse json read from mod watch file.");
+
+        let mut watches = MOD_WATCHES.lock().unwrap();
+        *watches = watches_from_json(&parsed_json);
+        log_init!("Loaded mod watches for {} guilds.", watches.len());
+    } else {
+        log_init!("Did not find file for mod watches, creating...");
+        backup_mod_watches();
+    }
+}
+
+/// Backs up the watches to the watch file, reading from MOD_WATCHES. {{{2
+pub fn backup_mod_watches() {
+    let json = watches_to_json(&MOD_WATCHES.lock().unwrap());
+    match OpenOptions::new().write(true).create(true).truncate(true).open(WATCH_FILE) {
+        Ok(mut file_handle) => {
+            if let Err(error) = json.write(&mut file_handle) {
+                log_error!("Couldn't back up mod watches to file: {:?}", error);
+            }
+        }
+        Err(error) => log_error!("Couldn't open mod watch file to back up to: {:?}", error),
+    }
+}
+
+/// Starts checking the portal for new versions of watched mods in the background. {{{2
+pub fn watch_mod_updates() {
+    thread::spawn(|| loop {
+        thread::sleep(Duration::from_secs(POLL_INTERVAL));
+        poll_mod_updates();
+    });
+    log_init!("Checking the mod portal for watched mods every {} seconds.", POLL_INTERVAL);
+}
+
+/// Checks every watch once, announcing new versions and remembering them. {{{2
+/// Each mod or modder is only looked up once, however many guilds watch it.
+fn poll_mod_updates() {
+    // Copied, so commands aren't held up while waiting on the portal
+    let watches = MOD_WATCHES.lock().unwrap().clone();
+    let mut lookups: HashMap<(String, bool), Vec<Mod>> = HashMap::new();
+    let mut changed = false;
+
+    for (guild_id, guild_watches) in watches.iter() {
+        for watch in guild_watches.iter() {
+            let mods = lookups
+                .entry((watch.name.clone(), watch.is_modder))
+                .or_insert_with(|| current_mods(&watch.name, watch.is_modder));
+            let updated = watch.updated(mods);
+            if updated.is_empty() {
+                continue;
+            }
+            for modification in updated.iter() {
+                announce(watch.channel, modification, !watch.seen.contains_key(&modification.name));
+            }
+
+            // The watch may have been removed while the portal was being checked
+            let mut stored_watches = MOD_WATCHES.lock().unwrap();
+            if let Some(guild_watches) = stored_watches.get_mut(guild_id) {
+                if let Some(stored) = guild_watches.iter_mut().find(|stored| stored.name == watch.name &&
+                                                                             stored.is_modder == watch.is_modder) {
+                    stored.mark_seen(&updated);
+                    changed = true;
+                }
+            }
+        }
+    }
+    if changed {
+        backup_mod_watches();
+    }
+}
+
+/// Posts the embed of a mod into a channel, with the changes in its latest {{{2
+/// version if the changelog has them.
+fn announce(channel: ChannelId, modification: &Mod, is_new: bool) {
+    // Fresh, since the cached changelog may be from before the update
+    let full = fresh_full_request(&modification.name).unwrap_or(JsonValue::Null);
+    let changes = full["changelog"]
+        .as_str()
+        .and_then(|changelog| changes_for_version(changelog, &modification.latest_version));
+    let heading = if is_new {
+        format!("New mod from {}: **{}**", modification.author, modification.title)
+    } else {
+        format!("**{}** has been updated to {}.", modification.title, modification.latest_version)
+    };
+
+    let result = channel.send_message(|a| {
+        a.content(&heading).embed(|b| {
+            let embed = build_mod_embed(b, modification).timestamp(Utc::now().to_rfc3339());
+            match changes {
+                Some(ref changes) => embed.field(|c| c.name("Changes").value(&truncate_for_embed(changes, 1024))),
+                None => embed,
+            }
+        })
+    });
+    if let Err(error) = result {
+        log_error!("Couldn't announce {} {} in channel {}: {:?}", modification.name, modification.latest_version, channel.0, error);
+    }
+}
+
+/// Looks up what's currently on the portal for a watch, bypassing the cache. {{{2
+/// Returns nothing if the portal couldn't be reached, so nothing is announced.
+fn current_mods(name: &str, is_modder: bool) -> Vec<Mod> {
+    if is_modder {
+        match fresh_search(name) {
+            Some(returned) => returned["results"]
+                .members()
+                .filter_map(parse_json_into_mod)
+                .filter(|modification| modification.author == name)
+                .collect(),
+            None => Vec::new(),
+        }
+    } else {
+        fresh_full_request(name).and_then(|json| parse_json_into_mod(&json)).into_iter().collect()
+    }
+}
+
+/// Finds what a request to watch means, first by the internal name of a mod, {{{2
+/// then its title, then a modder's username. Returns the name to watch, whether
+/// it's a modder, and the mods currently on the portal for it.
+fn find_watchable(request: &str) -> Option<(String, bool, Vec<Mod>)> {
+    if let Some(modification) = find_mod(request) {
+        return Some((modification.name.clone(), false, vec![modification]));
+    }
+    let by_title = make_request(&request.to_owned())["results"]
+        .members()
+        .find(|entry| entry["title"].as_str().map(|title| title.eq_ignore_ascii_case(request)) == Some(true))
+        .and_then(parse_json_into_mod);
+    if let Some(modification) = by_title {
+        return Some((modification.name.clone(), false, vec![modification]));
+    }
+    if is_valid_modder(request) {
+        return Some((request.to_owned(), true, current_mods(request, true)));
+    }
+    None
+}
+
+/// Finds the first channel mentioned in the raw content of a message, like <#1234>. {{{2
+fn find_channel_mention(content: &str) -> Option<ChannelId> {
+    let start = match content.find("<#") {
+        Some(start) => start + 2,
+        None => return None,
+    };
+    let end = match content[start..].find('>') {
+        Some(end) => start + end,
+        None => return None,
+    };
+    content[start..end].parse::<u64>().ok().map(ChannelId::from)
+}
+
+/// Removes channel mentions, which look like <#444444444444444444>, from a request. {{{2
+fn remove_channel_mentions(request: &str) -> String {
+    request
+        .split_whitespace()
+        .filter(|word| !word.starts_with("<#"))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Serializes the watches of a guild into a list. {{{2
+fn serialize_watches(watches: &[ModWatch]) -> String {
+    let mut result = String::new();
+    for watch in watches.iter() {
+        if watch.is_modder {
+            result += &format!("- Mods by {} ({} so far), in <#{}>\n", watch.name, watch.seen.len(), watch.channel.0);
+        } else {
+            result += &format!("- {}, in <#{}>\n", watch.name, watch.channel.0);
+        }
+    }
+    result
+}
+
+/// Turns the watches of every guild into json, keyed by guild id. {{{2
+fn watches_to_json(watches: &HashMap<GuildId, Vec<ModWatch>>) -> JsonValue {
+    let mut json = JsonValue::new_object();
+    for (guild_id, guild_watches) in watches.iter() {
+        json[guild_id.0.to_string()] = JsonValue::Array(guild_watches.iter().map(ModWatch::to_json).collect());
+    }
+    json
+}
+
+/// Reads the watches of every guild from json, skipping anything unreadable. {{{2
+fn watches_from_json(json: &JsonValue) -> HashMap<GuildId, Vec<ModWatch>> {
+    let mut watches = HashMap::new();
+    for (key, value) in json.entries() {
+        if let Ok(id) = key.parse::<u64>() {
+            watches.insert(GuildId::from(id), value.members().filter_map(ModWatch::from_json).collect());
+        }
+    }
+    watches
+}
+
+// Tests {{{1
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_mod(name: &str, version: &str) -> Mod {
+        let mut json = JsonValue::new_object();
+        json["name"] = name.into();
+        json["owner"] = "Bobingabout".into();
+        json["downloads_count"] = 0.into();
+        json["latest_release"]["version"] = version.into();
+        parse_json_into_mod(&json).unwrap()
+    }
+
+    #[test]
+    fn can_find_updated_mods() {
+        let mut watch = ModWatch {
+            name: String::from("Bobingabout"),
+            is_modder: true,
+            channel: ChannelId::from(1234),
+            seen: BTreeMap::new(),
+        };
+        let mods = vec![test_mod("bobores", "0.16.3"), test_mod("boblibrary", "0.16.1")];
+        watch.mark_seen(&mods.iter().collect::<Vec<_>>());
+        assert!(watch.updated(&mods).is_empty());
+
+        let mods = vec![test_mod("bobores", "0.16.4"), test_mod("boblibrary", "0.16.1"), test_mod("bobwarfare", "0.16.0")];
+        let updated: Vec<&str> = watch.updated(&mods).iter().map(|modification| modification.name.as_str()).collect();
+        assert_eq!(updated, vec!["bobores", "bobwarfare"]);
+    }
+
+    #[test]
+    fn can_save_and_load_watches() {
+        let mut seen = BTreeMap::new();
+        seen.insert(String::from("bobores"), String::from("0.16.3"));
+        let mut watches = HashMap::new();
+        watches.insert(GuildId::from(333333333333333333),
+                       vec![ModWatch {
+                                name: String::from("bobores"),
+                                is_modder: false,
+                                channel: ChannelId::from(444444444444444444),
+                                seen,
+                            }]);
+        let json = json::parse(&watches_to_json(&watches).dump()).unwrap();
+        assert_eq!(watches_from_json(&json), watches);
+        assert!(watches_from_json(&json::parse(r#"{"1": [{"name": "no channel"}], "nope": []}"#).unwrap())[&GuildId::from(1)]
+                .is_empty());
+    }
+
+    #[test]
+    fn can_read_channel_mentions() {
+        assert_eq!(find_channel_mention("!watch-mod bobores <#444444444444444444>"),
+                   Some(ChannelId::from(444444444444444444)));
+        assert_eq!(find_channel_mention("!watch-mod bobores"), None);
+        assert_eq!(remove_channel_mentions("Bob's Mods <#444444444444444444>"), "Bob's Mods");
+    }
+}
@@ -0,0 +1,675 @@
+/// Holds the typed recipe model, and the loading and checking of recipes.json
+
+use json::{self, JsonValue};
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::fs::File;
+use std::io::Read;
+
+/// A single recipe, with its ingredients and products. {{{1
+#[derive(Debug, Clone, PartialEq)]
+pub struct Recipe {
+    pub id: String, //Internal name of the recipe
+    pub name: String, //Pretty name, as the wiki has it
+    pub category: String, //Which kind of machine crafts it, such as "smelting"
+    pub energy: f64, //Crafting time in seconds, at crafting speed 1
+    pub inputs: BTreeMap<String, f64>,
+    pub outputs: BTreeMap<String, f64>,
+    pub expensive: Option<RecipeCost>, //Costs on expensive difficulty, if they differ
+}
+
+/// The crafting time, ingredients and products of one difficulty of a recipe. {{{1
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecipeCost {
+    pub energy: f64,
+    pub inputs: BTreeMap<String, f64>,
+    pub outputs: BTreeMap<String, f64>,
+}
+
+/// A technology, with what it costs to research and what it unlocks. {{{1
+#[derive(Debug, Clone, PartialEq)]
+pub struct Technology {
+    pub id: String,
+    pub name: String,
+    pub count: f64, //How many times the unit has to be researched
+    pub time: f64, //Seconds per unit, at lab speed 1
+    pub ingredients: BTreeMap<String, f64>, //Science packs per unit
+    pub prerequisites: Vec<String>,
+    pub unlocks: Vec<String>, //Recipes made available
+}
+
+/// Every recipe and technology in the game, plus the resources that don't come from a recipe. {{{1
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RecipeBook {
+    pub recipes: BTreeMap<String, Recipe>,
+    pub resources: BTreeMap<String, String>, //Internal name to pretty name
+    pub technologies: BTreeMap<String, Technology>,
+}
+
+/// Technologies share names with recipes, so their keys in the file start with this.
+pub const TECHNOLOGY_PREFIX: &'static str = "technology:";
+
+/// Reasons the recipe file couldn't be loaded. {{{1
+#[derive(Debug, Clone, PartialEq)]
+pub enum RecipeError {
+    Io(String),
+    Syntax { line: usize, column: usize, reason: String },
+    Invalid { key: String, line: usize, reason: String },
+}
+
+impl fmt::Display for RecipeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            RecipeError::Io(ref reason) => write!(f, "Couldn't read the recipe file: {}", reason),
+            RecipeError::Syntax {
+                line,
+                column,
+                ref reason,
+            } => write!(f, "The recipe file isn't valid json at line {}, column {}: {}", line, column, reason),
+            RecipeError::Invalid {
+                ref key,
+                line,
+                ref reason,
+            } => write!(f, "Entry `{}` on line {} is invalid: {}", key, line, reason),
+        }
+    }
+}
+
+impl RecipeBook {
+    /// Reads and parses a recipe file. {{{2
+    pub fn load(path: &str) -> Result<RecipeBook, RecipeError> {
+        let mut source = String::new();
+        match File::open(path) {
+            Ok(mut file) => {
+                if let Err(error) = file.read_to_string(&mut source) {
+                    return Err(RecipeError::Io(format!("{}", error)));
+                }
+            }
+            Err(error) => return Err(RecipeError::Io(format!("{}", error))),
+        }
+        RecipeBook::parse(&source)
+    }
+
+    /// Parses the contents of a recipe file. Each top level key is either a {{{2
+    /// recipe, a resource that doesn't come from a recipe, or a technology, depending on its "type".
+    pub fn parse(source: &str) -> Result<RecipeBook, RecipeError> {
+        let parsed = match json::parse(source) {
+            Ok(parsed) => parsed,
+            Err(json::Error::UnexpectedCharacter { ch, line, column }) => {
+                return Err(RecipeError::Syntax {
+                    line,
+                    column,
+                    reason: format!("unexpected character `{}`", ch),
+                })
+            }
+            Err(error) => {
+                return Err(RecipeError::Syntax {
+                    line: source.lines().count(),
+                    column: 0,
+                    reason: format!("{}", error),
+                })
+            }
+        };
+        if !parsed.is_object() {
+            return Err(RecipeError::Syntax {
+                line: 1,
+                column: 1,
+                reason: String::from("expected an object of recipes"),
+            });
+        }
+
+        let mut book = RecipeBook::default();
+        for (key, value) in parsed.entries() {
+            // Wrap up any problem with the line the entry is on
+            let invalid = |reason: &str| {
+                RecipeError::Invalid {
+                    key: key.to_owned(),
+                    line: top_level_key_lines(source).get(key).cloned().unwrap_or(0),
+                    reason: reason.to_owned(),
+                }
+            };
+
+            let name = match value["wiki-name"].as_str() {
+                Some(name) => name.to_owned(),
+                None => return Err(invalid("missing a \"wiki-name\"")),
+            };
+
+            match value["type"].as_str() {
+                Some("recipe") => {
+                    let normal = match parse_recipe_cost(value) {
+                        Ok(normal) => normal,
+                        Err(reason) => return Err(invalid(&reason)),
+                    };
+                    let expensive = if value["expensive"].is_null() {
+                        None
+                    } else if value["expensive"].is_object() {
+                        match parse_recipe_cost(&value["expensive"]) {
+                            Ok(expensive) => Some(expensive),
+                            Err(reason) => return Err(invalid(&format!("in \"expensive\", {}", reason))),
+                        }
+                    } else {
+                        return Err(invalid("\"expensive\" should be an object"));
+                    };
+
+                    book.recipes.insert(key.to_owned(), Recipe {
+                        id: key.to_owned(),
+                        name,
+                        category: value["category"].as_str().unwrap_or("crafting").to_owned(),
+                        energy: normal.energy,
+                        inputs: normal.inputs,
+                        outputs: normal.outputs,
+                        expensive,
+                    });
+                }
+                Some("resource") => {
+                    book.resources.insert(key.to_owned(), name);
+                }
+                Some("technology") => {
+                    let id = if key.starts_with(TECHNOLOGY_PREFIX) {
+                        key[TECHNOLOGY_PREFIX.len()..].to_owned()
+                    } else {
+                        return Err(invalid(&format!("technology keys should start with \"{}\"", TECHNOLOGY_PREFIX)));
+                    };
+                    let count = match value["count"].as_f64() {
+                        Some(count) => count,
+                        None => return Err(invalid("\"count\" should be a number")),
+                    };
+                    let time = match value["time"].as_f64() {
+                        Some(time) => time,
+                        None => return Err(invalid("\"time\" should be a number")),
+                    };
+                    let ingredients = match parse_item_amounts(&value["ingredients"]) {
+                        Some(ingredients) => ingredients,
+                        None => return Err(invalid("\"ingredients\" should be an object of item amounts")),
+                    };
+                    let prerequisites = match parse_string_list(&value["prerequisites"]) {
+                        Some(prerequisites) => prerequisites,
+                        None => return Err(invalid("\"prerequisites\" should be a list of technologies")),
+                    };
+                    let unlocks = match parse_string_list(&value["unlocks"]) {
+                        Some(unlocks) => unlocks,
+                        None => return Err(invalid("\"unlocks\" should be a list of recipes")),
+                    };
+
+                    book.technologies.insert(id.clone(), Technology {
+                        id,
+                        name,
+                        count,
+                        time,
+                        ingredients,
+                        prerequisites,
+                        unlocks,
+                    });
+                }
+                Some(other) => return Err(invalid(&format!("unknown type \"{}\"", other))),
+                None => return Err(invalid("missing a \"type\"")),
+            }
+        }
+        Ok(book)
+    }
+
+    /// Returns true if there are no recipes at all. {{{2
+    pub fn is_empty(&self) -> bool {
+        self.recipes.is_empty()
+    }
+
+    /// Returns the pretty name of a recipe or item, or its internal name if it has none. {{{2
+    pub fn name_of(&self, id: &str) -> String {
+        if let Some(recipe) = self.recipes.get(id) {
+            recipe.name.clone()
+        } else if let Some(name) = self.resources.get(id) {
+            name.clone()
+        } else {
+            id.to_owned()
+        }
+    }
+
+    /// Returns the recipe named after an item, if that recipe makes it. {{{2
+    pub fn recipe_for(&self, item: &str) -> Option<&Recipe> {
+        self.recipes.get(item).and_then(|recipe| if recipe.outputs.get(item).cloned().unwrap_or(0.0) > 0.0 {
+            Some(recipe)
+        } else {
+            None
+        })
+    }
+
+    /// Returns a copy of the book with every recipe that has an expensive {{{2
+    /// variant swapped over to it. Recipes without one are left as they are.
+    pub fn with_expensive_recipes(&self) -> RecipeBook {
+        let mut book = self.clone();
+        for recipe in book.recipes.values_mut() {
+            if let Some(expensive) = recipe.expensive.take() {
+                recipe.energy = expensive.energy;
+                recipe.inputs = expensive.inputs;
+                recipe.outputs = expensive.outputs;
+            }
+        }
+        book
+    }
+
+    /// Returns the pretty name of a technology, or its internal name if there's no such technology. {{{2
+    pub fn technology_name(&self, id: &str) -> String {
+        match self.technologies.get(id) {
+            Some(technology) => technology.name.clone(),
+            None => id.to_owned(),
+        }
+    }
+
+    /// Returns every technology needed before the given one can be researched, {{{2
+    /// each listed after its own prerequisites, so the first ones can be researched from the start.
+    pub fn prerequisite_chain(&self, id: &str) -> Vec<&Technology> {
+        let mut chain = Vec::new();
+        let mut visited = BTreeSet::new();
+        visited.insert(id.to_owned());
+        if let Some(technology) = self.technologies.get(id) {
+            for prerequisite in technology.prerequisites.iter() {
+                self.add_to_prerequisite_chain(prerequisite, &mut visited, &mut chain);
+            }
+        }
+        chain
+    }
+
+    /// Adds a technology to the chain after all of its prerequisites, skipping any already added. {{{2
+    fn add_to_prerequisite_chain<'a>(&'a self, id: &str, visited: &mut BTreeSet<String>, chain: &mut Vec<&'a Technology>) {
+        if !visited.insert(id.to_owned()) {
+            return;
+        }
+        if let Some(technology) = self.technologies.get(id) {
+            for prerequisite in technology.prerequisites.iter() {
+                self.add_to_prerequisite_chain(prerequisite, visited, chain);
+            }
+            chain.push(technology);
+        }
+    }
+
+    /// Returns every item that is a resource, or goes into or comes out of a recipe. {{{2
+    pub fn item_ids(&self) -> BTreeSet<&str> {
+        let mut items: BTreeSet<&str> = self.resources.keys().map(String::as_str).collect();
+        for recipe in self.recipes.values() {
+            items.extend(recipe.inputs.keys().map(String::as_str));
+            items.extend(recipe.outputs.keys().map(String::as_str));
+        }
+        items
+    }
+
+    /// Writes the book out in the layout recipes.json uses, with an entry {{{2
+    /// per recipe or resource, sorted by key.
+    pub fn to_json_string(&self) -> String {
+        let mut entries: BTreeMap<String, String> = BTreeMap::new();
+
+        for (id, name) in self.resources.iter() {
+            entries.insert(id.clone(), format!("  {}: {{\n    \"wiki-name\": {},\n    \"type\": \"resource\"\n  }}",
+                                       JsonValue::from(id.as_str()).dump(),
+                                       JsonValue::from(name.as_str()).dump()));
+        }
+        for (id, recipe) in self.recipes.iter() {
+            let mut entry = format!("  {}: {{\n    \"wiki-name\": {},\n    \"type\": \"recipe\",\n",
+                                    JsonValue::from(id.as_str()).dump(),
+                                    JsonValue::from(recipe.name.as_str()).dump());
+            if recipe.category != "crafting" {
+                entry += &format!("    \"category\": {},\n", JsonValue::from(recipe.category.as_str()).dump());
+            }
+            entry += &format!("    \"energy-required\": {},\n", JsonValue::from(recipe.energy).dump());
+            entry += &format!("    \"inputs\": {},\n", item_amounts_to_json(&recipe.inputs, 4));
+            entry += &format!("    \"outputs\": {}", item_amounts_to_json(&recipe.outputs, 4));
+            if let Some(ref expensive) = recipe.expensive {
+                entry += &format!(",\n    \"expensive\": {{\n      \"energy-required\": {},\n",
+                                  JsonValue::from(expensive.energy).dump());
+                entry += &format!("      \"inputs\": {},\n", item_amounts_to_json(&expensive.inputs, 6));
+                entry += &format!("      \"outputs\": {}\n    }}", item_amounts_to_json(&expensive.outputs, 6));
+            }
+            entry += "\n  }";
+            entries.insert(id.clone(), entry);
+        }
+        for (id, technology) in self.technologies.iter() {
+            let key = format!("{}{}", TECHNOLOGY_PREFIX, id);
+            let mut entry = format!("  {}: {{\n    \"wiki-name\": {},\n    \"type\": \"technology\",\n",
+                                    JsonValue::from(key.as_str()).dump(),
+                                    JsonValue::from(technology.name.as_str()).dump());
+            entry += &format!("    \"count\": {},\n", JsonValue::from(technology.count).dump());
+            entry += &format!("    \"time\": {},\n", JsonValue::from(technology.time).dump());
+            entry += &format!("    \"ingredients\": {},\n", item_amounts_to_json(&technology.ingredients, 4));
+            entry += &format!("    \"prerequisites\": {},\n", string_list_to_json(&technology.prerequisites));
+            entry += &format!("    \"unlocks\": {}\n  }}", string_list_to_json(&technology.unlocks));
+            entries.insert(key, entry);
+        }
+
+        let entries: Vec<String> = entries.into_iter().map(|(_, entry)| entry).collect();
+        format!("{{\n{}\n}}\n", entries.join(",\n"))
+    }
+
+    /// Checks that every ingredient is either a resource, or made by some recipe, {{{2
+    /// and that technologies only refer to technologies and recipes that exist.
+    /// Returns a description of each problem found.
+    pub fn validate(&self) -> Vec<String> {
+        let mut known: BTreeSet<&str> = self.resources.keys().map(String::as_str).collect();
+        for recipe in self.recipes.values() {
+            known.extend(recipe.outputs.keys().map(String::as_str));
+            if let Some(ref expensive) = recipe.expensive {
+                known.extend(expensive.outputs.keys().map(String::as_str));
+            }
+        }
+
+        let mut problems = Vec::new();
+        for recipe in self.recipes.values() {
+            let mut costs = vec![("", &recipe.inputs, &recipe.outputs)];
+            if let Some(ref expensive) = recipe.expensive {
+                costs.push((" on expensive", &expensive.inputs, &expensive.outputs));
+            }
+
+            for (difficulty, inputs, outputs) in costs {
+                if outputs.is_empty() {
+                    problems.push(format!("`{}` doesn't make anything{}.", recipe.id, difficulty));
+                }
+                for input in inputs.keys() {
+                    if !known.contains(input.as_str()) {
+                        problems.push(format!("`{}` takes `{}`{}, which isn't a resource or made by any recipe.",
+                                              recipe.id,
+                                              input,
+                                              difficulty));
+                    }
+                }
+            }
+        }
+
+        for technology in self.technologies.values() {
+            for prerequisite in technology.prerequisites.iter() {
+                if !self.technologies.contains_key(prerequisite) {
+                    problems.push(format!("Technology `{}` needs `{}`, which isn't a technology.", technology.id, prerequisite));
+                }
+            }
+            for recipe in technology.unlocks.iter() {
+                if !self.recipes.contains_key(recipe) {
+                    problems.push(format!("Technology `{}` unlocks `{}`, which isn't a recipe.", technology.id, recipe));
+                }
+            }
+            for pack in technology.ingredients.keys() {
+                if !known.contains(pack.as_str()) {
+                    problems.push(format!("Technology `{}` takes `{}`, which isn't made by any recipe.", technology.id, pack));
+                }
+            }
+        }
+        problems
+    }
+}
+
+// Functions {{{1
+/// Reads the crafting time, inputs and outputs of a recipe, or of its {{{2
+/// "expensive" object. Returns the reason if any of them are missing or malformed.
+fn parse_recipe_cost(value: &JsonValue) -> Result<RecipeCost, String> {
+    let energy = match value["energy-required"].as_f64() {
+        Some(energy) => energy,
+        None => return Err(String::from("\"energy-required\" should be a number")),
+    };
+    let inputs = match parse_item_amounts(&value["inputs"]) {
+        Some(inputs) => inputs,
+        None => return Err(String::from("\"inputs\" should be an object of item amounts")),
+    };
+    let outputs = match parse_item_amounts(&value["outputs"]) {
+        Some(outputs) => outputs,
+        None => return Err(String::from("\"outputs\" should be an object of item amounts")),
+    };
+    Ok(RecipeCost {
+        energy,
+        inputs,
+        outputs,
+    })
+}
+
+/// Turns a json object of item names to amounts into a map. {{{2
+/// Returns None if it isn't an object, or any amount isn't a number.
+fn parse_item_amounts(value: &JsonValue) -> Option<BTreeMap<String, f64>> {
+    if !value.is_object() {
+        return None;
+    }
+    let mut amounts = BTreeMap::new();
+    for (item, amount) in value.entries() {
+        match amount.as_f64() {
+            Some(amount) => amounts.insert(item.to_owned(), amount),
+            None => return None,
+        };
+    }
+    Some(amounts)
+}
+
+/// Turns a json array of strings into a list. {{{2
+/// Returns None if it isn't an array, or anything in it isn't a string.
+fn parse_string_list(value: &JsonValue) -> Option<Vec<String>> {
+    if !value.is_array() {
+        return None;
+    }
+    let mut list = Vec::new();
+    for member in value.members() {
+        match member.as_str() {
+            Some(member) => list.push(member.to_owned()),
+            None => return None,
+        }
+    }
+    Some(list)
+}
+
+/// Writes a list of strings as a json array, on one line. {{{2
+fn string_list_to_json(list: &[String]) -> String {
+    let members: Vec<String> = list.iter().map(|member| JsonValue::from(member.as_str()).dump()).collect();
+    format!("[{}]", members.join(", "))
+}
+
+/// Writes a map of item amounts as a json object, on one line if it only {{{2
+/// has one item, otherwise with an item per line, indented past the key.
+fn item_amounts_to_json(amounts: &BTreeMap<String, f64>, indent: usize) -> String {
+    let lines: Vec<String> = amounts
+        .iter()
+        .map(|(item, amount)| format!("{}: {}", JsonValue::from(item.as_str()).dump(), JsonValue::from(*amount).dump()))
+        .collect();
+    match lines.len() {
+        0 => String::from("{}"),
+        1 => format!("{{{}}}", lines[0]),
+        _ => {
+            let item_indent = " ".repeat(indent + 2);
+            format!("{{\n{}{}\n{}}}",
+                    item_indent,
+                    lines.join(&format!(",\n{}", item_indent)),
+                    " ".repeat(indent))
+        }
+    }
+}
+
+/// Finds the line that each top level key of a json object is on. {{{2
+fn top_level_key_lines(source: &str) -> BTreeMap<String, usize> {
+    let mut lines = BTreeMap::new();
+    let mut depth = 0;
+    let mut line = 1;
+    let mut in_string = false;
+    let mut escaped = false;
+    let mut current = String::new();
+    let mut last_string: Option<(String, usize)> = None;
+
+    for ch in source.chars() {
+        if ch == '\n' {
+            line += 1;
+        }
+        if in_string {
+            if escaped {
+                escaped = false;
+                current.push(ch);
+            } else if ch == '\\' {
+                escaped = true;
+            } else if ch == '"' {
+                in_string = false;
+                if depth == 1 {
+                    last_string = Some((current.clone(), line));
+                }
+            } else {
+                current.push(ch);
+            }
+            continue;
+        }
+        match ch {
+            '"' => {
+                in_string = true;
+                current.clear();
+            }
+            '{' | '[' => depth += 1,
+            '}' | ']' => depth -= 1,
+            // A string followed by a colon is a key
+            ':' => {
+                if let Some((key, key_line)) = last_string.take() {
+                    lines.entry(key).or_insert(key_line);
+                }
+            }
+            ',' => last_string = None,
+            _ => {}
+        }
+    }
+    lines
+}
+
+// Tests {{{1
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEST_BOOK: &'static str = r#"{
+  "iron-ore": {
+    "wiki-name": "Iron ore",
+    "type": "resource"
+  },
+  "iron-plate": {
+    "wiki-name": "Iron plate",
+    "type": "recipe",
+    "category": "smelting",
+    "energy-required": 3.5,
+    "inputs": {"iron-ore": 1},
+    "outputs": {"iron-plate": 1}
+  },
+  "technology:automation": {
+    "wiki-name": "Automation",
+    "type": "technology",
+    "count": 10,
+    "time": 10,
+    "ingredients": {"iron-plate": 1},
+    "prerequisites": [],
+    "unlocks": ["wood"]
+  },
+  "technology:electronics": {
+    "wiki-name": "Electronics",
+    "type": "technology",
+    "count": 30,
+    "time": 15,
+    "ingredients": {"iron-plate": 1},
+    "prerequisites": ["automation"],
+    "unlocks": []
+  },
+  "technology:automation-2": {
+    "wiki-name": "Automation 2",
+    "type": "technology",
+    "count": 40,
+    "time": 15,
+    "ingredients": {"iron-plate": 1},
+    "prerequisites": ["electronics", "automation"],
+    "unlocks": ["iron-plate"]
+  },
+  "wood": {
+    "wiki-name": "Wood",
+    "type": "recipe",
+    "energy-required": 0.5,
+    "inputs": {"raw-wood": 1},
+    "outputs": {"wood": 2},
+    "expensive": {
+      "energy-required": 1,
+      "inputs": {"raw-wood": 2},
+      "outputs": {"wood": 2}
+    }
+  }
+}"#;
+
+    #[test]
+    fn can_parse_recipe_book() {
+        let book = RecipeBook::parse(TEST_BOOK).unwrap();
+        let plate = &book.recipes["iron-plate"];
+        assert_eq!(plate.name, "Iron plate");
+        assert_eq!(plate.category, "smelting");
+        assert_eq!(plate.energy, 3.5);
+        assert_eq!(plate.inputs["iron-ore"], 1.0);
+
+        assert_eq!(book.recipes["wood"].category, "crafting");
+        assert_eq!(book.name_of("iron-ore"), "Iron ore");
+        assert_eq!(book.name_of("raw-wood"), "raw-wood");
+        assert!(book.recipe_for("iron-plate").is_some());
+        assert!(book.recipe_for("iron-ore").is_none());
+
+        assert_eq!(plate.expensive, None);
+        assert_eq!(book.recipes["wood"].expensive.as_ref().unwrap().inputs["raw-wood"], 2.0);
+    }
+
+    #[test]
+    fn can_swap_to_expensive_recipes() {
+        let book = RecipeBook::parse(TEST_BOOK).unwrap().with_expensive_recipes();
+        let wood = &book.recipes["wood"];
+        assert_eq!(wood.energy, 1.0);
+        assert_eq!(wood.inputs["raw-wood"], 2.0);
+        assert_eq!(wood.expensive, None);
+        assert_eq!(book.recipes["iron-plate"].inputs["iron-ore"], 1.0);
+    }
+
+    #[test]
+    fn can_follow_prerequisite_chains() {
+        let book = RecipeBook::parse(TEST_BOOK).unwrap();
+        let automation = &book.technologies["automation-2"];
+        assert_eq!(automation.name, "Automation 2");
+        assert_eq!(automation.count, 40.0);
+        assert_eq!(automation.unlocks, vec![String::from("iron-plate")]);
+
+        let chain: Vec<&str> = book.prerequisite_chain("automation-2").iter().map(|technology| technology.id.as_str()).collect();
+        assert_eq!(chain, vec!["automation", "electronics"]);
+        assert!(book.prerequisite_chain("automation").is_empty());
+    }
+
+    #[test]
+    fn validation_flags_unknown_ingredients() {
+        let book = RecipeBook::parse(TEST_BOOK).unwrap();
+        assert_eq!(book.validate(),
+                   vec![String::from("`wood` takes `raw-wood`, which isn't a resource or made by any recipe."),
+                        String::from("`wood` takes `raw-wood` on expensive, which isn't a resource or made by any recipe.")]);
+
+        let source = TEST_BOOK.replace("[\"automation\"]", "[\"logistics\"]");
+        let problems = RecipeBook::parse(&source).unwrap().validate();
+        assert!(problems.contains(&String::from("Technology `electronics` needs `logistics`, which isn't a technology.")));
+    }
+
+    #[test]
+    fn invalid_entries_report_their_line() {
+        let source = TEST_BOOK.replace("\"energy-required\": 0.5", "\"energy-required\": \"fast\"");
+        assert_eq!(RecipeBook::parse(&source),
+                   Err(RecipeError::Invalid {
+                       key: String::from("wood"),
+                       line: 41,
+                       reason: String::from("\"energy-required\" should be a number"),
+                   }));
+    }
+
+    #[test]
+    fn syntax_errors_report_their_line() {
+        let source = TEST_BOOK.replace("\"type\": \"resource\"", "\"type\" \"resource\"");
+        match RecipeBook::parse(&source) {
+            Err(RecipeError::Syntax { line, .. }) => assert_eq!(line, 4),
+            other => panic!("Expected a syntax error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn writing_matches_shipped_layout() {
+        let mut source = String::new();
+        File::open("recipes.json").unwrap().read_to_string(&mut source).unwrap();
+        let book = RecipeBook::parse(&source).unwrap();
+        assert_eq!(book.to_json_string(), source);
+    }
+
+    #[test]
+    fn shipped_recipes_are_valid() {
+        let book = RecipeBook::load("recipes.json").unwrap();
+        assert!(!book.is_empty());
+        assert_eq!(book.validate(), Vec::<String>::new());
+    }
+}
@@ -1,122 +1,1307 @@
-use json;
-use json::JsonValue;
-
+use serenity::model::Message;
 use serenity::utils::Colour;
 use serenity::framework::standard::CommandError;
 
-use std::fs::File;
-use std::io::Read;
+use std::collections::BTreeMap;
+use std::fs;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, SystemTime};
 
 use common_funcs::*;
 use constants::*;
+use recipe_book::*;
 
 lazy_static! {
-    static ref RECIPES: JsonValue = {
-        if let Ok(mut file_result) = File::open("recipes.json") {
-            let mut dest = String::new();
-            if let Err(_) = file_result.read_to_string(&mut dest) {
-                return JsonValue::new_object();
-            }
-            if let Ok(value) = json::parse(dest.as_str()) {
-                return value;
-            } else {
-                return JsonValue::new_object();
-            }
-        } else {
-            return JsonValue::new_object();
+    /// The recipes in use, or why they couldn't be loaded if there's never been a good copy.
+    /// Commands take their own handle, so a reload doesn't pull the book out from under them.
+    static ref RECIPES: Arc<Mutex<Result<Arc<RecipeBook>, RecipeError>>> =
+        Arc::new(Mutex::new(RecipeBook::load(RECIPE_FILE).map(Arc::new)));
+}
+
+const RECIPE_FILE: &'static str = "recipes.json";
+
+/// How often the recipe file is checked for changes, in seconds, when it's being watched.
+const WATCH_INTERVAL: u64 = 10;
+
+/// A crafting machine, with what the calculators need to know about it. {{{1
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Machine {
+    name: &'static str,
+    speed: f64,
+    power: f64, //Kilowatts drawn while crafting
+    module_slots: usize,
+    burner: bool, //Burns fuel rather than drawing electricity
+}
+
+/// Each tier of assembler and furnace, used by the ratio and crafting calculators.
+const ASSEMBLER_TIERS: [Machine; 3] = [
+    Machine { name: "Assembling machine 1", speed: 0.5, power: 90.0, module_slots: 0, burner: false },
+    Machine { name: "Assembling machine 2", speed: 0.75, power: 150.0, module_slots: 2, burner: false },
+    Machine { name: "Assembling machine 3", speed: 1.25, power: 210.0, module_slots: 4, burner: false },
+];
+const FURNACE_TIERS: [Machine; 3] = [
+    Machine { name: "Stone furnace", speed: 1.0, power: 90.0, module_slots: 0, burner: true },
+    Machine { name: "Steel furnace", speed: 2.0, power: 90.0, module_slots: 0, burner: true },
+    Machine { name: "Electric furnace", speed: 2.0, power: 180.0, module_slots: 2, burner: false },
+];
+const CHEMICAL_PLANT: Machine = Machine { name: "Chemical plant", speed: 1.25, power: 210.0, module_slots: 3, burner: false };
+const OIL_REFINERY: Machine = Machine { name: "Oil refinery", speed: 1.0, power: 420.0, module_slots: 3, burner: false };
+const CENTRIFUGE: Machine = Machine { name: "Centrifuge", speed: 0.75, power: 350.0, module_slots: 2, burner: false };
+const ROCKET_SILO: Machine = Machine { name: "Rocket silo", speed: 1.0, power: 4000.0, module_slots: 4, burner: false };
+
+/// A module, and how much it changes the speed, productivity and power use {{{1
+/// of a machine it's in. Each is a bonus, so 0.5 is +50%.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Module {
+    id: &'static str,
+    speed: f64,
+    productivity: f64,
+    consumption: f64,
+}
+
+const MODULES: [Module; 9] = [
+    Module { id: "speed-module", speed: 0.2, productivity: 0.0, consumption: 0.5 },
+    Module { id: "speed-module-2", speed: 0.3, productivity: 0.0, consumption: 0.6 },
+    Module { id: "speed-module-3", speed: 0.5, productivity: 0.0, consumption: 0.7 },
+    Module { id: "effectivity-module", speed: 0.0, productivity: 0.0, consumption: -0.3 },
+    Module { id: "effectivity-module-2", speed: 0.0, productivity: 0.0, consumption: -0.4 },
+    Module { id: "effectivity-module-3", speed: 0.0, productivity: 0.0, consumption: -0.5 },
+    Module { id: "productivity-module", speed: -0.15, productivity: 0.04, consumption: 0.4 },
+    Module { id: "productivity-module-2", speed: -0.15, productivity: 0.06, consumption: 0.6 },
+    Module { id: "productivity-module-3", speed: -0.15, productivity: 0.1, consumption: 0.8 },
+];
+
+/// The recipes productivity modules can be used on, which are the intermediate
+/// products, as the game limits them.
+const PRODUCTIVITY_RECIPES: [&'static str; 42] = [
+    "sulfuric-acid", "basic-oil-processing", "advanced-oil-processing", "coal-liquefaction",
+    "heavy-oil-cracking", "light-oil-cracking", "solid-fuel-from-light-oil", "solid-fuel-from-heavy-oil",
+    "solid-fuel-from-petroleum-gas", "lubricant", "iron-gear-wheel", "iron-stick", "copper-cable",
+    "electronic-circuit", "advanced-circuit", "processing-unit", "engine-unit", "electric-engine-unit",
+    "uranium-processing", "explosives", "battery", "flying-robot-frame", "low-density-structure",
+    "rocket-fuel", "nuclear-fuel", "nuclear-fuel-reprocessing", "rocket-control-unit", "rocket-part",
+    "steel-plate", "empty-barrel", "uranium-fuel-cell", "science-pack-1", "science-pack-2",
+    "science-pack-3", "military-science-pack", "production-science-pack", "high-tech-science-pack",
+    "plastic-bar", "sulfur", "iron-plate", "copper-plate", "stone-brick",
+];
+
+/// Beacons hold two modules, and pass on half of their effect.
+const BEACON_MODULE_SLOTS: f64 = 2.0;
+const BEACON_EFFECTIVITY: f64 = 0.5;
+
+/// Speed and power use can't be lowered past -80%.
+const MINIMUM_BONUS: f64 = -0.8;
+
+/// How many recipes are shown on each page of search results.
+const SEARCH_PAGE_SIZE: usize = 15;
+
+/// How deep recipes are followed before the rest is counted as raw, in case of loops.
+const MAX_RECIPE_DEPTH: usize = 20;
+
+/// Nicknames players commonly use, paired with the internal name they mean.
+const ITEM_ALIASES: [(&'static str, &'static str); 13] = [
+    ("red-science", "science-pack-1"),
+    ("green-science", "science-pack-2"),
+    ("blue-science", "science-pack-3"),
+    ("military-science", "military-science-pack"),
+    ("purple-science", "production-science-pack"),
+    ("yellow-science", "high-tech-science-pack"),
+    ("green-circuit", "electronic-circuit"),
+    ("red-circuit", "advanced-circuit"),
+    ("blue-circuit", "processing-unit"),
+    ("yellow-belt", "transport-belt"),
+    ("red-belt", "fast-transport-belt"),
+    ("blue-belt", "express-transport-belt"),
+    ("gear", "iron-gear-wheel"),
+];
+
+/// Which tier of assembler and furnace the ratio calculator assumes. {{{1
+/// Each field is an index into its tier list.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct MachineTiers {
+    assembler: usize,
+    furnace: usize,
+}
+
+impl Default for MachineTiers {
+    fn default() -> MachineTiers {
+        MachineTiers {
+            assembler: 1,
+            furnace: 1,
         }
-    };
+    }
+}
+
+impl MachineTiers {
+    /// Returns the machine that crafts a recipe category.
+    fn machine_for(&self, category: &str) -> Machine {
+        match category {
+            "smelting" => FURNACE_TIERS[self.furnace],
+            "chemistry" => CHEMICAL_PLANT,
+            "oil-processing" => OIL_REFINERY,
+            "centrifuging" => CENTRIFUGE,
+            "rocket-building" => ROCKET_SILO,
+            // Assembling machine 1s can't take fluids
+            "crafting-with-fluid" if self.assembler == 0 => ASSEMBLER_TIERS[1],
+            _ => ASSEMBLER_TIERS[self.assembler],
+        }
+    }
+}
+
+/// One recipe in a production chain, with how many machines it needs. {{{1
+#[derive(Debug, Clone, PartialEq)]
+struct ProductionStep {
+    machine: &'static str,
+    machine_count: f64,
+    items_per_second: f64,
+}
+
+/// The machine, modules and beacons the crafting calculator assumes. {{{1
+/// Each beacon is listed by the module it's filled with.
+#[derive(Debug, Clone, Default, PartialEq)]
+struct CraftingSetup {
+    tiers: MachineTiers,
+    modules: Vec<Module>,
+    beacons: Vec<Module>,
+}
+
+/// How a recipe runs in a given setup. {{{1
+#[derive(Debug, Clone, PartialEq)]
+struct CraftingStats {
+    machine: &'static str,
+    craft_time: f64,
+    outputs_per_second: BTreeMap<String, f64>,
+    power: f64,
+    burner: bool, //The power is burned as fuel
+    speed_bonus: f64,
+    productivity_bonus: f64,
+}
+
+/// What a recipe search asked for, before item names are matched up. {{{1
+#[derive(Debug, Clone, Default, PartialEq)]
+struct SearchCriteria {
+    uses: Vec<String>,
+    makes: Vec<String>,
+    machine: Option<String>,
+    longer_than: Option<f64>,
+    shorter_than: Option<f64>,
+    page: usize, //Counted from 1
+}
+
+/// Every step needed to make an item, plus the raw resources consumed per second.
+#[derive(Debug, Default)]
+struct ProductionChain {
+    steps: BTreeMap<String, ProductionStep>,
+    raw_resources: BTreeMap<String, f64>,
 }
 
 /// Returns a recipe from the game, either an item, machine, or tech. {{{1
 /// Should be called with the name of the thing to get
 command!(recipe(_context, message) {
     // Fail out if RECIPES failed to init
-    if RECIPES.is_empty() {
-        say_into_chat(&message, "Sorry, I couldn't get a list of recipes in the game correctly. This command isn't going to work.");
-    } else {
-        let _ = message.channel_id.broadcast_typing();
-        let request = fix_message(message.content_safe(), "recipe");
+    let loaded = match get_recipes(&message) {
+        Some(loaded) => loaded,
+        None => return Err(CommandError::from("Recipes failed to load.")),
+    };
+    let recipes = &*loaded;
 
-        //Bail out if there's no argument
-        if request.is_empty() {
-            send_error_embed_or_say(&message, "You must provide the name of an item, process or entity here, it will be autocorrected if it's slightly off.");
-            return Err(CommandError::from("Missing argument, failed."));
+    let _ = message.channel_id.broadcast_typing();
+    let (request, tree_mode) = take_flag(&fix_message(message.content_safe(), "recipe"), "--tree");
+    let (request, expensive_mode) = take_flag(&request, "--expensive");
+    let (request, setup) = match parse_crafting_request(&request) {
+        Some(parsed) => parsed,
+        None => {
+            send_error_embed_or_say(&message, "Sorry, I didn't understand that. Machines are set with `--assembler 1/2/3` or `--furnace stone/steel/electric`, modules with `--modules speed3,prod3` and beacons with `--beacons 8xspeed3`.");
+            return Err(CommandError::from("Couldn't parse recipe request."));
         }
-        // Create a list of just keys
-        let recipe_key_list: Vec<&str> = RECIPES.entries().map(|(key, _value)| {
-            key
-        }).collect();
+    };
 
-        // Find the closest match to what they asked for
-        let (dist, closest_match) = get_closest_match(&recipe_key_list, &request);
+    // Trees don't use machines, so a setup for them would be ignored
+    if tree_mode && setup != CraftingSetup::default() {
+        send_error_embed_or_say(&message, "Recipe trees don't use machines, so `--tree` can't be used with `--assembler`, `--furnace`, `--modules` or `--beacons`.");
+        return Err(CommandError::from("Crafting setup given with a tree."));
+    }
 
-        // Bail out if the distance is too great
-        if dist >= DISTANCE_SENSITIVITY {
-            send_error_embed_or_say(&message, "Sorry, I couldn't find any recipe for that request.
-                                    Does the object you're asking for go by any other name?");
-            return Err(CommandError::from("Missing recipe, failed."));
-        }
-
-        // If it wasn't an exact match
-        if dist != 0 {
-            let result = message.channel_id.send_message(|a| a
-                                                         .embed(|b| b
-                                                                .title(&format!("Recipe for {}: (closest, distance {})", RECIPES[closest_match]["wiki-name"], dist))
-                                                                .field(|c| c
-                                                                       .name("Crafting time")
-                                                                       .value(&format!("{}", RECIPES[closest_match]["energy-required"])))
-                                                                .field(|c| c
-                                                                       .name("Inputs")
-                                                                       .value(&serialize_recipe_io(&RECIPES[closest_match]["inputs"])))
-                                                                .field(|c| c
-                                                                       .name("Outputs")
-                                                                       .value(&serialize_recipe_io(&RECIPES[closest_match]["outputs"])))
-                                                                .timestamp(message.timestamp.to_rfc3339())
-                                                                .color(Colour::from_rgb(10, 225, 249))
-                                                               ));
-            if let Err(_) = result {
-                say_into_chat(&message, "Sorry, I couldn't make an embed here. Contact an admin.");
+    //Bail out if there's no argument
+    if request.is_empty() {
+        send_error_embed_or_say(&message, "You must provide the name of an item, process or entity here, it will be autocorrected if it's slightly off.");
+        return Err(CommandError::from("Missing argument, failed."));
+    }
+
+    // Find the closest match to what they asked for
+    let (dist, closest_match) = find_recipe_key(recipes, &request);
+
+    // Bail out if the distance is too great, pointing them at a technology if that's what they meant
+    if dist >= DISTANCE_SENSITIVITY {
+        match find_technology_key(recipes, &request) {
+            Some((tech_dist, technology)) if tech_dist < DISTANCE_SENSITIVITY => {
+                send_error_embed_or_say(&message, &format!("Sorry, I couldn't find any recipe for that request, but there's a technology called {}. Try the tech command for it.",
+                                                           recipes.technology_name(technology)));
+            }
+            _ => {
+                send_error_embed_or_say(&message, "Sorry, I couldn't find any recipe for that request.
+                                        Does the object you're asking for go by any other name?");
+            }
+        }
+        return Err(CommandError::from("Missing recipe, failed."));
+    }
+
+    let mut closest_note = if dist != 0 {
+        format!(" (closest, distance {})", dist)
+    } else {
+        String::new()
+    };
+    let difficulty_note = if expensive_mode {
+        closest_note = format!(" (expensive){}", closest_note);
+        expensive_note(recipes, closest_match)
+    } else {
+        String::new()
+    };
+    let expensive_recipes;
+    let recipes = if expensive_mode {
+        expensive_recipes = recipes.with_expensive_recipes();
+        &expensive_recipes
+    } else {
+        recipes
+    };
+
+    if tree_mode {
+        // Expand every ingredient down to raw materials
+        let mut tree = String::new();
+        let mut raw_materials = BTreeMap::new();
+        build_recipe_tree(recipes, &recipes.recipes[closest_match].inputs, 1.0, 0, &mut tree, &mut raw_materials);
+
+        let result = message.channel_id.send_message(|a| a
+                                                     .embed(|b| b
+                                                            .title(&format!("Recipe tree for {}:{}", recipes.name_of(closest_match), closest_note))
+                                                            .description(&format!("```\n{}```", truncate_for_embed(&tree, 2000)))
+                                                            .field(|c| c
+                                                                   .name("Total raw materials")
+                                                                   .value(&truncate_for_embed(&serialize_item_amounts(recipes, &raw_materials, ""), 1024)))
+                                                            .footer(|c| c
+                                                                    .text(&format!("Oil products are counted as-is, since refineries make several at once. {}", difficulty_note)))
+                                                            .timestamp(message.timestamp.to_rfc3339())
+                                                            .color(Colour::from_rgb(10, 225, 249))
+                                                           ));
+        if let Err(_) = result {
+            say_into_chat(&message, "Sorry, I couldn't make an embed here. Contact an admin.");
+        }
+    } else {
+        let stats = match calculate_crafting_stats(&recipes.recipes[closest_match], &setup) {
+            Ok(stats) => stats,
+            Err(reason) => {
+                send_error_embed_or_say(&message, &format!("Sorry, that setup won't work. {}", reason));
+                return Err(CommandError::from("Invalid crafting setup."));
             }
+        };
 
-        } else {
-            let result = message.channel_id.send_message(|a| a
-                                                         .embed(|b| b
-                                                                .title(&format!("Recipe for {}:", RECIPES[closest_match]["wiki-name"]))
-                                                                .field(|c| c
-                                                                       .name("Crafting time")
-                                                                       .value(&format!("{}", RECIPES[closest_match]["energy-required"])))
-                                                                .field(|c| c
-                                                                       .name("Inputs")
-                                                                       .value(&serialize_recipe_io(&RECIPES[closest_match]["inputs"])))
-                                                                .field(|c| c
-                                                                       .name("Outputs")
-                                                                       .value(&serialize_recipe_io(&RECIPES[closest_match]["outputs"])))
-                                                                .timestamp(message.timestamp.to_rfc3339())
-                                                                .color(Colour::from_rgb(10, 225, 249))
-                                                               ));
-            if let Err(_) = result {
-                say_into_chat(&message, "Sorry, I couldn't make an embed here. Contact an admin.");
+        let result = message.channel_id.send_message(|a| a
+                                                     .embed(|b| {
+                                                         let b = b
+                                                             .title(&format!("Recipe for {}:{}", recipes.name_of(closest_match), closest_note))
+                                                             .field(|c| c
+                                                                    .name(&format!("Crafting in a {}", stats.machine))
+                                                                    .value(&serialize_crafting_stats(recipes, &stats)))
+                                                             .field(|c| c
+                                                                    .name("Inputs")
+                                                                    .value(&serialize_recipe_io(recipes, &recipes.recipes[closest_match].inputs)))
+                                                             .field(|c| c
+                                                                    .name("Outputs")
+                                                                    .value(&serialize_recipe_io(recipes, &recipes.recipes[closest_match].outputs)))
+                                                             .timestamp(message.timestamp.to_rfc3339())
+                                                             .color(Colour::from_rgb(10, 225, 249));
+                                                         if difficulty_note.is_empty() {
+                                                             b
+                                                         } else {
+                                                             b.footer(|c| c.text(&difficulty_note))
+                                                         }
+                                                     }));
+        if let Err(_) = result {
+            say_into_chat(&message, "Sorry, I couldn't make an embed here. Contact an admin.");
+        }
+    }
+});
+
+/// Calculates the machines and raw resources needed to make an item at a given rate. {{{1
+/// Should be called with the name of the item, then the rate.
+command!(ratio(_context, message) {
+    // Fail out if RECIPES failed to init
+    let loaded = match get_recipes(&message) {
+        Some(loaded) => loaded,
+        None => return Err(CommandError::from("Recipes failed to load.")),
+    };
+    let recipes = &*loaded;
+
+    let _ = message.channel_id.broadcast_typing();
+    let (request, expensive_mode) = take_flag(&fix_message(message.content_safe(), "ratio"), "--expensive");
+
+    let (item, per_second, tiers) = match parse_ratio_request(&request) {
+        Some(parsed) => parsed,
+        None => {
+            send_error_embed_or_say(&message, "Sorry, I didn't understand that. Give me an item and a rate, like `red science 1/s` or `gear 30/m`.");
+            return Err(CommandError::from("Couldn't parse ratio request."));
+        }
+    };
+
+    let (dist, closest_match) = find_recipe_key(recipes, &item);
+
+    // Bail out if the distance is too great
+    if dist >= DISTANCE_SENSITIVITY {
+        send_error_embed_or_say(&message, "Sorry, I couldn't find any recipe for that request.
+                                Does the object you're asking for go by any other name?");
+        return Err(CommandError::from("Missing recipe, failed."));
+    }
+
+    let difficulty_note = if expensive_mode {
+        format!(" On expensive difficulty. {}", expensive_note(recipes, closest_match))
+    } else {
+        String::new()
+    };
+    let expensive_recipes;
+    let recipes = if expensive_mode {
+        expensive_recipes = recipes.with_expensive_recipes();
+        &expensive_recipes
+    } else {
+        recipes
+    };
+
+    let chain = calculate_production_chain(recipes, closest_match, per_second, &tiers);
+
+    let result = message.channel_id.send_message(|a| a
+                                                 .embed(|b| b
+                                                        .title(&format!("Production chain for {}/s of {}:",
+                                                                        format_amount(per_second),
+                                                                        recipes.name_of(closest_match)))
+                                                        .description(&truncate_for_embed(&serialize_production_steps(recipes, &chain), 2048))
+                                                        .field(|c| c
+                                                               .name("Raw resources per second")
+                                                               .value(&truncate_for_embed(&serialize_item_amounts(recipes, &chain.raw_resources, "/s"), 1024)))
+                                                        .footer(|c| c
+                                                                .text(&format!("Assuming {}s and {}s.{}",
+                                                                               ASSEMBLER_TIERS[tiers.assembler].name,
+                                                                               FURNACE_TIERS[tiers.furnace].name,
+                                                                               difficulty_note)))
+                                                        .timestamp(message.timestamp.to_rfc3339())
+                                                        .color(Colour::from_rgb(10, 225, 249))
+                                                       ));
+    if let Err(_) = result {
+        say_into_chat(&message, "Sorry, I couldn't make an embed here. Contact an admin.");
+    }
+});
+
+/// Lists every recipe that uses an item, and every recipe that makes it. {{{1
+/// Should be called with the name of the item.
+command!(uses(_context, message) {
+    // Fail out if RECIPES failed to init
+    let loaded = match get_recipes(&message) {
+        Some(loaded) => loaded,
+        None => return Err(CommandError::from("Recipes failed to load.")),
+    };
+    let recipes = &*loaded;
+
+    let _ = message.channel_id.broadcast_typing();
+    // Direct messages have no guild, so no prefix
+    let server_prefix = message.guild_id().map(|id| get_prefix_for_guild(&id)).unwrap_or_default();
+    let mut request = String::new();
+
+    // Correctly fix message based on which alias they use
+    if message.content_safe().starts_with(format!("{}uses", server_prefix).as_str()) {
+        request = fix_message(message.content_safe(), "uses");
+    } else if message.content_safe().starts_with(format!("{}usedin", server_prefix).as_str()) {
+        request = fix_message(message.content_safe(), "usedin");
+    }
+
+    //Bail out if there's no argument
+    if request.is_empty() {
+        send_error_embed_or_say(&message, "You must provide the name of an item here, it will be autocorrected if it's slightly off.");
+        return Err(CommandError::from("Missing argument, failed."));
+    }
+
+    let (dist, closest_match) = find_item_key(recipes, &request);
+
+    // Bail out if the distance is too great
+    if dist >= DISTANCE_SENSITIVITY {
+        send_error_embed_or_say(&message, "Sorry, I couldn't find any item for that request.
+                                Does the item you're asking for go by any other name?");
+        return Err(CommandError::from("Missing item, failed."));
+    }
+
+    let (used_in, made_by) = find_item_uses(recipes, closest_match);
+    let closest_note = if dist != 0 {
+        format!(" (closest, distance {})", dist)
+    } else {
+        String::new()
+    };
+
+    let result = message.channel_id.send_message(|a| a
+                                                 .embed(|b| b
+                                                        .title(&format!("Uses of {}:{}", recipes.name_of(closest_match), closest_note))
+                                                        .field(|c| c
+                                                               .name("Used in")
+                                                               .value(&truncate_for_embed(&serialize_recipe_list(recipes, &used_in, closest_match, "inputs"), 1024)))
+                                                        .field(|c| c
+                                                               .name("Made by")
+                                                               .value(&truncate_for_embed(&serialize_recipe_list(recipes, &made_by, closest_match, "outputs"), 1024)))
+                                                        .timestamp(message.timestamp.to_rfc3339())
+                                                        .color(Colour::from_rgb(10, 225, 249))
+                                                       ));
+    if let Err(_) = result {
+        say_into_chat(&message, "Sorry, I couldn't make an embed here. Contact an admin.");
+    }
+});
+
+/// Shows what a technology costs, what it needs and unlocks, and everything {{{1
+/// that has to be researched before it. Should be called with the name of the technology.
+command!(tech(_context, message) {
+    // Fail out if RECIPES failed to init
+    let loaded = match get_recipes(&message) {
+        Some(loaded) => loaded,
+        None => return Err(CommandError::from("Recipes failed to load.")),
+    };
+    let recipes = &*loaded;
+
+    let _ = message.channel_id.broadcast_typing();
+    let request = fix_message(message.content_safe(), "tech");
+
+    //Bail out if there's no argument
+    if request.is_empty() {
+        send_error_embed_or_say(&message, "You must provide the name of a technology here, it will be autocorrected if it's slightly off.");
+        return Err(CommandError::from("Missing argument, failed."));
+    }
+
+    let (dist, closest_match) = match find_technology_key(recipes, &request) {
+        Some(found) => found,
+        None => {
+            send_error_embed_or_say(&message, "Sorry, the list of technologies in the game is empty. This command isn't going to work.");
+            return Err(CommandError::from("No technologies loaded."));
+        }
+    };
+
+    // Bail out if the distance is too great
+    if dist >= DISTANCE_SENSITIVITY {
+        send_error_embed_or_say(&message, "Sorry, I couldn't find any technology for that request.
+                                Does the technology you're asking for go by any other name?");
+        return Err(CommandError::from("Missing technology, failed."));
+    }
+
+    let technology = &recipes.technologies[closest_match];
+    let chain = recipes.prerequisite_chain(closest_match);
+    let closest_note = if dist != 0 {
+        format!(" (closest, distance {})", dist)
+    } else {
+        String::new()
+    };
+    let chain_names = if chain.is_empty() {
+        String::from("None, it can be researched from the start.")
+    } else {
+        chain.iter().map(|prerequisite| prerequisite.name.clone()).collect::<Vec<String>>().join(" → ")
+    };
+    let unlock_names = if technology.unlocks.is_empty() {
+        String::from("Nothing.")
+    } else {
+        technology.unlocks.iter().map(|recipe| recipes.name_of(recipe)).collect::<Vec<String>>().join(", ")
+    };
+
+    let result = message.channel_id.send_message(|a| a
+                                                 .embed(|b| b
+                                                        .title(&format!("Research for {}:{}", technology.name, closest_note))
+                                                        .field(|c| c
+                                                               .name("Cost")
+                                                               .value(&serialize_technology_cost(recipes, technology)))
+                                                        .field(|c| c
+                                                               .name("Prerequisites")
+                                                               .value(&serialize_technology_names(recipes, &technology.prerequisites)))
+                                                        .field(|c| c
+                                                               .name("Unlocks")
+                                                               .value(&truncate_for_embed(&unlock_names, 1024)))
+                                                        .field(|c| c
+                                                               .name("Full prerequisite chain")
+                                                               .value(&truncate_for_embed(&chain_names, 1024)))
+                                                        .field(|c| c
+                                                               .name("Total science packs, including prerequisites")
+                                                               .value(&serialize_item_amounts(recipes, &total_research_cost(recipes, closest_match), "")))
+                                                        .timestamp(message.timestamp.to_rfc3339())
+                                                        .color(Colour::from_rgb(10, 225, 249))
+                                                       ));
+    if let Err(_) = result {
+        say_into_chat(&message, "Sorry, I couldn't make an embed here. Contact an admin.");
+    }
+});
+
+/// Lists every recipe matching some criteria, a page at a time. {{{1
+/// Should be called with criteria such as "uses plastic bar and copper cable",
+/// "in chemical plant", or "takes more than 10 seconds".
+command!(recipe_search(_context, message) {
+    // Fail out if RECIPES failed to init
+    let loaded = match get_recipes(&message) {
+        Some(loaded) => loaded,
+        None => return Err(CommandError::from("Recipes failed to load.")),
+    };
+    let recipes = &*loaded;
+
+    let _ = message.channel_id.broadcast_typing();
+    let request = fix_message(message.content_safe(), "recipe-search");
+
+    let criteria = match parse_search_criteria(&request) {
+        Some(criteria) => criteria,
+        None => {
+            send_error_embed_or_say(&message, "Sorry, I didn't understand that. Search with things like `uses plastic bar and copper cable`, `in chemical plant`, `takes more than 10 seconds`, and `page 2`.");
+            return Err(CommandError::from("Couldn't parse search criteria."));
+        }
+    };
+
+    // Match up each item they asked about, bailing out on any that can't be found
+    let (uses, makes) = match (find_item_keys(recipes, &criteria.uses), find_item_keys(recipes, &criteria.makes)) {
+        (Ok(uses), Ok(makes)) => (uses, makes),
+        (Err(missing), _) | (_, Err(missing)) => {
+            send_error_embed_or_say(&message, &format!("Sorry, I couldn't find any item called {}.", missing));
+            return Err(CommandError::from("Missing item, failed."));
+        }
+    };
+
+    let results = search_recipes(recipes, &criteria, &uses, &makes);
+    let page_count = ((results.len() + SEARCH_PAGE_SIZE - 1) / SEARCH_PAGE_SIZE).max(1);
+    if criteria.page > page_count {
+        send_error_embed_or_say(&message, &format!("There are only {} pages of results.", page_count));
+        return Err(CommandError::from("Page out of range."));
+    }
+
+    let page = &results[(criteria.page - 1) * SEARCH_PAGE_SIZE..(criteria.page * SEARCH_PAGE_SIZE).min(results.len())];
+    let result = message.channel_id.send_message(|a| a
+                                                 .embed(|b| b
+                                                        .title(&format!("Recipe search: {} found", results.len()))
+                                                        .description(&truncate_for_embed(&serialize_search_results(recipes, page), 2048))
+                                                        .footer(|c| c
+                                                                .text(&format!("Page {} of {}. Add `page <number>` to see another.", criteria.page, page_count)))
+                                                        .timestamp(message.timestamp.to_rfc3339())
+                                                        .color(Colour::from_rgb(10, 225, 249))
+                                                       ));
+    if let Err(_) = result {
+        say_into_chat(&message, "Sorry, I couldn't make an embed here. Contact an admin.");
+    }
+});
+
+/// Reloads the recipe file without restarting. If the new file can't be {{{1
+/// loaded, the previous recipes stay in use, and the error is shown.
+command!(reload_data(_context, message) {
+    let _ = message.channel_id.broadcast_typing();
+
+    match reload_recipes(RECIPE_FILE) {
+        Ok(recipes) => {
+            let problems = recipes.validate();
+            for problem in problems.iter() {
+                log_error!("Recipe file problem: {}", problem);
             }
+            let _ = send_success_embed(&message, &format!("Reloaded {} recipes and {} technologies, with {} problems found.",
+                                                          recipes.recipes.len(),
+                                                          recipes.technologies.len(),
+                                                          problems.len()));
+        }
+        Err(error) => {
+            send_error_embed_or_say(&message, &format!("Couldn't reload the recipes, so the previous ones are still in use.\n{}", error));
+            return Err(CommandError::from("Failed to reload recipes."));
         }
     }
 });
 
 // Functions {{{1
-/// Serializes the inputs or outputs object it recieves into a nice list. {{{2
-fn serialize_recipe_io(value: &JsonValue) -> String {
-    if value.is_empty() {
+/// Returns the loaded recipes, or tells the user why they couldn't be loaded. {{{2
+fn get_recipes(message: &Message) -> Option<Arc<RecipeBook>> {
+    // Don't hold the lock while talking to discord
+    let loaded = RECIPES.lock().unwrap().clone();
+    match loaded {
+        Ok(ref recipes) if !recipes.is_empty() => Some(recipes.clone()),
+        Ok(_) => {
+            send_error_embed_or_say(message, "Sorry, the list of recipes in the game is empty. This command isn't going to work.");
+            None
+        }
+        Err(ref error) => {
+            send_error_embed_or_say(message, &format!("Sorry, I couldn't load the list of recipes in the game. This command isn't going to work.\n{}", error));
+            None
+        }
+    }
+}
+
+/// Returns the loaded recipes without complaining if there aren't any, for {{{2
+/// commands that can get by without them.
+pub fn current_recipes() -> Option<Arc<RecipeBook>> {
+    RECIPES.lock().unwrap().as_ref().ok().cloned()
+}
+
+/// Checks the recipe file on startup, logging anything wrong with it. {{{2
+pub fn install_recipes() {
+    match *RECIPES.lock().unwrap() {
+        Ok(ref recipes) => {
+            log_init!("Loaded {} recipes and {} technologies from file.", recipes.recipes.len(), recipes.technologies.len());
+            for problem in recipes.validate() {
+                log_error!("Recipe file problem: {}", problem);
+            }
+        }
+        Err(ref error) => log_error!("Unable to load recipes: {}", error),
+    }
+}
+
+/// Reads a recipe file, swapping it in if it loads. If it doesn't, {{{2
+/// the last good copy stays in use, and the error is returned.
+fn reload_recipes(path: &str) -> Result<Arc<RecipeBook>, RecipeError> {
+    // Parse before locking, so commands aren't held up by it
+    let reloaded = RecipeBook::load(path).map(Arc::new);
+    let mut recipes = RECIPES.lock().unwrap();
+    match reloaded {
+        Ok(book) => {
+            *recipes = Ok(book.clone());
+            Ok(book)
+        }
+        Err(error) => {
+            // Only worth keeping if there's no good copy to fall back on
+            if recipes.is_err() {
+                *recipes = Err(error.clone());
+            }
+            Err(error)
+        }
+    }
+}
+
+/// Starts a thread that reloads the recipe file whenever it's changed, {{{2
+/// logging how each reload went.
+pub fn watch_recipes() {
+    thread::spawn(|| {
+        let mut last_modified = recipe_file_modified();
+        loop {
+            thread::sleep(Duration::from_secs(WATCH_INTERVAL));
+            let modified = recipe_file_modified();
+            if modified == last_modified {
+                continue;
+            }
+            last_modified = modified;
+
+            match reload_recipes(RECIPE_FILE) {
+                Ok(recipes) => {
+                    log_info!("Recipe file changed, loaded {} recipes and {} technologies.", recipes.recipes.len(), recipes.technologies.len());
+                    for problem in recipes.validate() {
+                        log_error!("Recipe file problem: {}", problem);
+                    }
+                }
+                Err(error) => log_error!("Recipe file changed, but couldn't be loaded, keeping the last good copy: {}", error),
+            }
+        }
+    });
+    log_init!("Watching {} for changes.", RECIPE_FILE);
+}
+
+/// Returns when the recipe file was last modified, if that can be found out. {{{2
+fn recipe_file_modified() -> Option<SystemTime> {
+    fs::metadata(RECIPE_FILE).and_then(|metadata| metadata.modified()).ok()
+}
+
+/// Warns that a recipe is shown on normal difficulty, if it has no expensive variant listed. {{{2
+fn expensive_note(recipes: &RecipeBook, key: &str) -> String {
+    match recipes.recipes.get(key) {
+        Some(recipe) if recipe.expensive.is_none() => {
+            format!("No expensive variant is listed for {}, so its normal recipe is shown.", recipe.name)
+        }
+        _ => String::new(),
+    }
+}
+
+/// Formats a number with up to two decimal places, dropping trailing zeroes. {{{2
+fn format_amount(amount: f64) -> String {
+    let formatted = format!("{:.2}", amount);
+    formatted.trim_right_matches('0').trim_right_matches('.').to_owned()
+}
+
+/// Finds the closest recipe to a request, accepting spaces in place of dashes {{{2
+/// and common nicknames. Returns the distance and the recipe's key.
+fn find_recipe_key<'a>(recipes: &'a RecipeBook, request: &str) -> (usize, &'a str) {
+    let key_list: Vec<&str> = recipes.recipes.keys().map(String::as_str).collect();
+    find_closest_key(key_list, request)
+}
+
+/// Finds the closest item to a request, from every resource and every item {{{2
+/// that goes into or comes out of a recipe. Returns the distance and the item's name.
+fn find_item_key<'a>(recipes: &'a RecipeBook, request: &str) -> (usize, &'a str) {
+    find_closest_key(recipes.item_ids().into_iter().collect(), request)
+}
+
+/// Finds the closest technology to a request. Returns the distance and the {{{2
+/// technology's key, or None if there are no technologies.
+fn find_technology_key<'a>(recipes: &'a RecipeBook, request: &str) -> Option<(usize, &'a str)> {
+    if recipes.technologies.is_empty() {
+        return None;
+    }
+    let key_list: Vec<&str> = recipes.technologies.keys().map(String::as_str).collect();
+    Some(get_closest_match(&key_list, &request.trim().to_lowercase().replace(" ", "-")))
+}
+
+/// Finds the closest item to each request. Returns the first request {{{2
+/// that's too far from any item, if there is one.
+fn find_item_keys<'a>(recipes: &'a RecipeBook, requests: &[String]) -> Result<Vec<&'a str>, String> {
+    let mut keys = Vec::new();
+    for request in requests.iter() {
+        let (dist, closest_match) = find_item_key(recipes, request);
+        if dist >= DISTANCE_SENSITIVITY {
+            return Err(request.clone());
+        }
+        keys.push(closest_match);
+    }
+    Ok(keys)
+}
+
+/// Matches a request against a list of keys and nicknames, returning the {{{2
+/// distance and the closest key.
+fn find_closest_key<'a>(mut key_list: Vec<&'a str>, request: &str) -> (usize, &'a str) {
+    let request = request.trim().to_lowercase().replace(" ", "-");
+    key_list.extend(ITEM_ALIASES.iter().map(|&(alias, _)| alias));
+
+    let (dist, closest_match) = get_closest_match(&key_list, &request);
+
+    // Swap a nickname for the key it stands for
+    if let Some(&(_, internal)) = ITEM_ALIASES.iter().find(|&&(alias, _)| alias == closest_match) {
+        return (dist, internal);
+    }
+    (dist, closest_match)
+}
+
+/// Finds the recipes that take an item as an input, and the recipes {{{2
+/// that output it. Returns both, in that order.
+fn find_item_uses<'a>(recipes: &'a RecipeBook, item: &str) -> (Vec<&'a Recipe>, Vec<&'a Recipe>) {
+    let used_in = recipes
+        .recipes
+        .values()
+        .filter(|recipe| recipe.inputs.contains_key(item))
+        .collect();
+    let made_by = recipes
+        .recipes
+        .values()
+        .filter(|recipe| recipe.outputs.contains_key(item))
+        .collect();
+    (used_in, made_by)
+}
+
+/// Serializes a list of recipes, with how much of an item each takes or gives. {{{2
+/// Side should be either "inputs" or "outputs".
+fn serialize_recipe_list(recipes: &RecipeBook, list: &[&Recipe], item: &str, side: &str) -> String {
+    if list.is_empty() {
+        return String::from("Nothing.");
+    }
+    let mut result = String::new();
+    for recipe in list.iter() {
+        let amounts = if side == "inputs" {
+            &recipe.inputs
+        } else {
+            &recipe.outputs
+        };
+        result += &format!("{} (x{})\n",
+                           recipes.name_of(&recipe.id),
+                           format_amount(amounts.get(item).cloned().unwrap_or(0.0)));
+    }
+    result
+}
+
+/// Parses a rate such as `1/s`, `30/m` or `2`, into items per second. {{{2
+fn parse_rate(word: &str) -> Option<f64> {
+    let (amount, per) = match word.find('/') {
+        Some(index) => (&word[..index], &word[index + 1..]),
+        None => (word, "s"),
+    };
+    let seconds = match per {
+        "s" | "sec" | "second" => 1.0,
+        "m" | "min" | "minute" => 60.0,
+        "h" | "hour" => 3600.0,
+        _ => return None,
+    };
+    match amount.parse::<f64>() {
+        Ok(amount) if amount > 0.0 => Some(amount / seconds),
+        _ => None,
+    }
+}
+
+/// Parses a machine tier, either by number or by the start of its name. {{{2
+/// Returns the index of the tier.
+fn parse_tier(word: Option<&str>, tiers: &[Machine]) -> Option<usize> {
+    let word = match word {
+        Some(word) => word.to_lowercase(),
+        None => return None,
+    };
+    if let Ok(number) = word.parse::<usize>() {
+        if number >= 1 && number <= tiers.len() {
+            return Some(number - 1);
+        }
+        return None;
+    }
+    tiers.iter().position(|machine| machine.name.to_lowercase().starts_with(&word))
+}
+
+/// Splits a ratio request into the item, the rate in items per second, {{{2
+/// and the machine tiers to assume. Returns None if it can't be understood.
+fn parse_ratio_request(request: &str) -> Option<(String, f64, MachineTiers)> {
+    let mut tiers = MachineTiers::default();
+    let mut item_words: Vec<&str> = Vec::new();
+
+    let mut words = request.split_whitespace();
+    while let Some(word) = words.next() {
+        match word {
+            "--assembler" => {
+                match parse_tier(words.next(), &ASSEMBLER_TIERS) {
+                    Some(tier) => tiers.assembler = tier,
+                    None => return None,
+                }
+            }
+            "--furnace" => {
+                match parse_tier(words.next(), &FURNACE_TIERS) {
+                    Some(tier) => tiers.furnace = tier,
+                    None => return None,
+                }
+            }
+            _ => item_words.push(word),
+        }
+    }
+
+    // The rate comes last, so names with numbers in them like "science pack 1" still work
+    let rate = match item_words.pop() {
+        Some(word) => parse_rate(word),
+        None => None,
+    };
+
+    match rate {
+        Some(rate) if !item_words.is_empty() => Some((item_words.join(" "), rate, tiers)),
+        _ => None,
+    }
+}
+
+/// Splits a recipe request into the name of the recipe, and the machine, {{{2
+/// modules and beacons to assume. Returns None if the flags can't be understood.
+fn parse_crafting_request(request: &str) -> Option<(String, CraftingSetup)> {
+    let mut setup = CraftingSetup::default();
+    let mut name_words: Vec<&str> = Vec::new();
+
+    let mut words = request.split_whitespace();
+    while let Some(word) = words.next() {
+        match word {
+            "--assembler" => {
+                match parse_tier(words.next(), &ASSEMBLER_TIERS) {
+                    Some(tier) => setup.tiers.assembler = tier,
+                    None => return None,
+                }
+            }
+            "--furnace" => {
+                match parse_tier(words.next(), &FURNACE_TIERS) {
+                    Some(tier) => setup.tiers.furnace = tier,
+                    None => return None,
+                }
+            }
+            "--modules" => {
+                match words.next().and_then(parse_module_list) {
+                    Some(modules) => setup.modules = modules,
+                    None => return None,
+                }
+            }
+            "--beacons" => {
+                // A bare number of beacons means they're full of the best speed modules
+                let beacons = match words.next() {
+                    Some(word) if word.parse::<usize>().is_ok() => {
+                        parse_module_list(&format!("{}xspeed3", word))
+                    }
+                    Some(word) => parse_module_list(word),
+                    None => None,
+                };
+                match beacons {
+                    // Productivity modules can't go in beacons
+                    Some(ref beacons) if beacons.iter().any(|module| module.productivity > 0.0) => return None,
+                    Some(beacons) => setup.beacons = beacons,
+                    None => return None,
+                }
+            }
+            _ => name_words.push(word),
+        }
+    }
+    Some((name_words.join(" "), setup))
+}
+
+/// Parses a comma separated list of modules, such as `speed3,speed3` or {{{2
+/// `4xprod3`. Returns None if any of them isn't a module.
+fn parse_module_list(list: &str) -> Option<Vec<Module>> {
+    let mut modules = Vec::new();
+    for entry in list.split(',').filter(|entry| !entry.is_empty()) {
+        let entry = entry.to_lowercase();
+        // Allow a count in front, like 4xprod3
+        let (count, name) = match entry.find('x') {
+            Some(index) if entry[..index].parse::<usize>().is_ok() => {
+                (entry[..index].parse::<usize>().unwrap(), &entry[index + 1..])
+            }
+            _ => (1, &entry[..]),
+        };
+        match parse_module(name) {
+            Some(module) => {
+                for _ in 0..count {
+                    modules.push(module);
+                }
+            }
+            None => return None,
+        }
+    }
+    Some(modules)
+}
+
+/// Finds a module by a shorthand such as `speed3`, `prod-2`, `eff1`, or its {{{2
+/// internal name. A missing tier means tier 1.
+fn parse_module(name: &str) -> Option<Module> {
+    let name = name.replace("module", "").replace("-", "");
+    let split = name.find(|c: char| c.is_digit(10)).unwrap_or(name.len());
+    let (kind, tier) = name.split_at(split);
+    let kind = match kind {
+        "s" | "speed" => "speed-module",
+        "p" | "prod" | "productivity" => "productivity-module",
+        "e" | "eff" | "effectivity" | "efficiency" => "effectivity-module",
+        _ => return None,
+    };
+    let id = match tier {
+        "" | "1" => kind.to_owned(),
+        "2" | "3" => format!("{}-{}", kind, tier),
+        _ => return None,
+    };
+    MODULES.iter().find(|module| module.id == id).cloned()
+}
+
+/// Works out how fast a recipe crafts, how much it makes, and how much power {{{2
+/// it draws in the given setup. Returns why if the modules don't fit the machine.
+fn calculate_crafting_stats(recipe: &Recipe, setup: &CraftingSetup) -> Result<CraftingStats, String> {
+    let machine = setup.tiers.machine_for(&recipe.category);
+    if setup.modules.len() > machine.module_slots {
+        return Err(format!("{} only has {} module slots.", machine.name, machine.module_slots));
+    }
+    if !setup.beacons.is_empty() && machine.module_slots == 0 {
+        return Err(format!("{} can't be affected by beacons.", machine.name));
+    }
+    if setup.modules.iter().any(|module| module.productivity > 0.0) &&
+       !PRODUCTIVITY_RECIPES.contains(&recipe.id.as_str()) {
+        return Err(format!("Productivity modules can only be used on intermediate products, which {} isn't.",
+                           recipe.name));
+    }
+
+    let beacon_share = BEACON_MODULE_SLOTS * BEACON_EFFECTIVITY;
+    let mut speed_bonus = 0.0;
+    let mut productivity_bonus = 0.0;
+    let mut consumption_bonus = 0.0;
+    for module in setup.modules.iter() {
+        speed_bonus += module.speed;
+        productivity_bonus += module.productivity;
+        consumption_bonus += module.consumption;
+    }
+    for module in setup.beacons.iter() {
+        speed_bonus += module.speed * beacon_share;
+        consumption_bonus += module.consumption * beacon_share;
+    }
+    let speed_bonus = speed_bonus.max(MINIMUM_BONUS);
+    let consumption_bonus = consumption_bonus.max(MINIMUM_BONUS);
+
+    let craft_time = recipe.energy / (machine.speed * (1.0 + speed_bonus));
+    let outputs_per_second = recipe
+        .outputs
+        .iter()
+        .map(|(item, amount)| (item.clone(), amount * (1.0 + productivity_bonus) / craft_time))
+        .collect();
+
+    Ok(CraftingStats {
+        machine: machine.name,
+        craft_time,
+        outputs_per_second,
+        power: machine.power * (1.0 + consumption_bonus),
+        burner: machine.burner,
+        speed_bonus,
+        productivity_bonus,
+    })
+}
+
+/// Serializes crafting stats into a list for an embed field. {{{2
+fn serialize_crafting_stats(recipes: &RecipeBook, stats: &CraftingStats) -> String {
+    format!("Craft time: {}s\nOutput: {}\n{}: {}kW\nSpeed bonus: {}%\nProductivity bonus: {}%",
+            format_amount(stats.craft_time),
+            serialize_item_amounts(recipes, &stats.outputs_per_second, "/s").trim_right().replace("\n", ", "),
+            if stats.burner { "Fuel" } else { "Power" },
+            format_amount(stats.power),
+            format_bonus(stats.speed_bonus),
+            format_bonus(stats.productivity_bonus))
+}
+
+/// Formats a bonus like 0.5 as +50, or -0.15 as -15. {{{2
+fn format_bonus(bonus: f64) -> String {
+    if bonus < 0.0 {
+        format!("-{}", format_amount(-bonus * 100.0))
+    } else {
+        format!("+{}", format_amount(bonus * 100.0))
+    }
+}
+
+/// Splits a search request into its criteria. Each criterion starts with a {{{2
+/// keyword, "uses", "makes", "in", "takes" or "page", and runs until the next
+/// one. Returns None if there's nothing to search by, or a criterion can't be understood.
+fn parse_search_criteria(request: &str) -> Option<SearchCriteria> {
+    let mut criteria = SearchCriteria::default();
+    let mut clauses: Vec<(&str, Vec<&str>)> = Vec::new();
+
+    for word in request.split_whitespace() {
+        let keyword = match word.to_lowercase().as_str() {
+            "uses" | "use" | "using" | "needs" => Some("uses"),
+            "makes" | "make" | "produces" | "outputs" => Some("makes"),
+            "in" => Some("in"),
+            "takes" => Some("takes"),
+            "page" | "--page" => Some("page"),
+            // Filler, as in "produced in chemical plant"
+            "produced" | "made" | "crafted" | "recipes" | "that" | "which" => continue,
+            _ => None,
+        };
+        match keyword {
+            Some(keyword) => clauses.push((keyword, Vec::new())),
+            None => {
+                match clauses.last_mut() {
+                    Some(&mut (_, ref mut words)) => words.push(word),
+                    None => return None,
+                }
+            }
+        }
+    }
+
+    for (keyword, words) in clauses {
+        if words.is_empty() {
+            return None;
+        }
+        let joined = words.join(" ");
+        match keyword {
+            "uses" => criteria.uses.extend(split_item_list(&joined)),
+            "makes" => criteria.makes.extend(split_item_list(&joined)),
+            "in" => criteria.machine = Some(joined.to_lowercase()),
+            "takes" => {
+                let seconds = match words
+                          .iter()
+                          .filter_map(|word| word.trim_right_matches(|c| c == 's' || c == 'S').parse::<f64>().ok())
+                          .next() {
+                    Some(seconds) => seconds,
+                    None => return None,
+                };
+                match words[0].to_lowercase().as_str() {
+                    "more" | "over" | "longer" => criteria.longer_than = Some(seconds),
+                    "less" | "under" | "shorter" => criteria.shorter_than = Some(seconds),
+                    _ => return None,
+                }
+            }
+            _ => {
+                match joined.parse::<usize>() {
+                    Ok(page) if page >= 1 => criteria.page = page,
+                    _ => return None,
+                }
+            }
+        }
+    }
+
+    if criteria.page == 0 {
+        criteria.page = 1;
+    }
+    if criteria == (SearchCriteria { page: 1, ..SearchCriteria::default() }) {
+        return None;
+    }
+    Some(criteria)
+}
+
+/// Splits a list of items joined by commas or "and". {{{2
+fn split_item_list(list: &str) -> Vec<String> {
+    list.split(',')
+        .flat_map(|part| part.split(" and "))
+        .map(|item| item.trim().to_owned())
+        .filter(|item| !item.is_empty())
+        .collect()
+}
+
+/// Returns every recipe matching the search criteria, with the items already {{{2
+/// matched up to their internal names.
+fn search_recipes<'a>(recipes: &'a RecipeBook, criteria: &SearchCriteria, uses: &[&str], makes: &[&str]) -> Vec<&'a Recipe> {
+    recipes
+        .recipes
+        .values()
+        .filter(|recipe| uses.iter().all(|item| recipe.inputs.contains_key(*item)))
+        .filter(|recipe| makes.iter().all(|item| recipe.outputs.contains_key(*item)))
+        .filter(|recipe| match criteria.machine {
+                    Some(ref machine) => crafted_in(recipe, machine),
+                    None => true,
+                })
+        .filter(|recipe| criteria.longer_than.map(|seconds| recipe.energy > seconds).unwrap_or(true))
+        .filter(|recipe| criteria.shorter_than.map(|seconds| recipe.energy < seconds).unwrap_or(true))
+        .collect()
+}
+
+/// Checks whether a recipe can be crafted in a machine, named either by the {{{2
+/// machine, like "chemical plant" or "furnace", or by the recipe category.
+fn crafted_in(recipe: &Recipe, machine: &str) -> bool {
+    let machine = machine.replace("assembler", "assembling machine");
+    if recipe.category == machine.replace(" ", "-") {
+        return true;
+    }
+    // Try every tier, since any of them might be what they meant
+    for assembler in 0..ASSEMBLER_TIERS.len() {
+        for furnace in 0..FURNACE_TIERS.len() {
+            let tiers = MachineTiers { assembler, furnace };
+            if tiers.machine_for(&recipe.category).name.to_lowercase().contains(&machine) {
+                return true;
+            }
+        }
+    }
+    false
+}
+
+/// Serializes a page of search results, with each recipe's crafting time and inputs. {{{2
+fn serialize_search_results(recipes: &RecipeBook, results: &[&Recipe]) -> String {
+    if results.is_empty() {
+        return String::from("No recipes match that.");
+    }
+    let mut result = String::new();
+    for recipe in results.iter() {
+        result += &format!("**{}** ({}s): {}\n",
+                           recipe.name,
+                           format_amount(recipe.energy),
+                           serialize_recipe_io(recipes, &recipe.inputs));
+    }
+    result
+}
+
+/// Walks the inputs of an item's recipe, adding up the machines and raw {{{2
+/// resources needed to make it at the given rate.
+fn calculate_production_chain(recipes: &RecipeBook,
+                              item: &str,
+                              per_second: f64,
+                              tiers: &MachineTiers)
+                              -> ProductionChain {
+    let mut chain = ProductionChain::default();
+    add_to_production_chain(recipes, item, per_second, tiers, &mut chain, 0);
+    chain
+}
+
+fn add_to_production_chain(recipes: &RecipeBook,
+                           item: &str,
+                           per_second: f64,
+                           tiers: &MachineTiers,
+                           chain: &mut ProductionChain,
+                           depth: usize) {
+    // Anything without a recipe of its own making it counts as raw
+    let recipe = match recipes.recipe_for(item) {
+        Some(recipe) if depth <= MAX_RECIPE_DEPTH => recipe,
+        _ => {
+            *chain.raw_resources.entry(item.to_owned()).or_insert(0.0) += per_second;
+            return;
+        }
+    };
+
+    let crafts_per_second = per_second / recipe.outputs[item];
+    let machine = tiers.machine_for(&recipe.category);
+
+    {
+        let step = chain.steps.entry(item.to_owned()).or_insert(ProductionStep {
+            machine: machine.name,
+            machine_count: 0.0,
+            items_per_second: 0.0,
+        });
+        step.machine_count += crafts_per_second * recipe.energy / machine.speed;
+        step.items_per_second += per_second;
+    }
+
+    for (input, amount) in recipe.inputs.iter() {
+        add_to_production_chain(recipes, input, crafts_per_second * amount, tiers, chain, depth + 1);
+    }
+}
+
+/// Serializes the steps of a production chain into a list of machines. {{{2
+fn serialize_production_steps(recipes: &RecipeBook, chain: &ProductionChain) -> String {
+    let mut result = String::new();
+    for (item, step) in chain.steps.iter() {
+        result += &format!("**{}**: {} {} ({}/s)\n",
+                           recipes.name_of(item),
+                           format_amount(step.machine_count),
+                           step.machine,
+                           format_amount(step.items_per_second));
+    }
+    result
+}
+
+/// Expands a recipe's inputs into an indented tree, following each {{{2
+/// ingredient down to raw materials and adding those up as it goes.
+fn build_recipe_tree(recipes: &RecipeBook,
+                     inputs: &BTreeMap<String, f64>,
+                     multiplier: f64,
+                     depth: usize,
+                     tree: &mut String,
+                     raw_materials: &mut BTreeMap<String, f64>) {
+    for (input, amount) in inputs.iter() {
+        let needed = amount * multiplier;
+        tree.push_str(&format!("{}{} x{}\n", "  ".repeat(depth), recipes.name_of(input), format_amount(needed)));
+
+        match recipes.recipe_for(input) {
+            Some(recipe) if depth < MAX_RECIPE_DEPTH => {
+                build_recipe_tree(recipes, &recipe.inputs, needed / recipe.outputs[input], depth + 1, tree, raw_materials);
+            }
+            _ => *raw_materials.entry(input.to_owned()).or_insert(0.0) += needed,
+        }
+    }
+}
+
+/// Serializes a map of items and amounts into a list, with a unit after each amount. {{{2
+fn serialize_item_amounts(recipes: &RecipeBook, amounts: &BTreeMap<String, f64>, unit: &str) -> String {
+    if amounts.is_empty() {
         return String::from("None");
     }
     let mut result = String::new();
-    for entry in value.entries() {
-        let (ing_key, ingredient) = entry;
-        let mut pretty_name = format!("{}", RECIPES[ing_key]["wiki-name"]);
-        // Check if there is a pretty name for the ingredient, otherwise use internal
-        if pretty_name == "null" {
-            pretty_name = String::from(ing_key);
+    for (item, amount) in amounts.iter() {
+        result += &format!("{}: {}{}\n", recipes.name_of(item), format_amount(*amount), unit);
+    }
+    result
+}
+
+/// Serializes the cost of researching a technology, per unit and in total. {{{2
+fn serialize_technology_cost(recipes: &RecipeBook, technology: &Technology) -> String {
+    let total: BTreeMap<String, f64> = technology
+        .ingredients
+        .iter()
+        .map(|(pack, amount)| (pack.clone(), amount * technology.count))
+        .collect();
+    format!("{} × ({}) × {}s\nIn total: {}, taking {}s at lab speed 1",
+            format_amount(technology.count),
+            serialize_recipe_io(recipes, &technology.ingredients),
+            format_amount(technology.time),
+            serialize_recipe_io(recipes, &total),
+            format_amount(technology.count * technology.time))
+}
+
+/// Serializes a list of technologies by their pretty names. {{{2
+fn serialize_technology_names(recipes: &RecipeBook, ids: &[String]) -> String {
+    if ids.is_empty() {
+        return String::from("None");
+    }
+    ids.iter().map(|id| recipes.technology_name(id)).collect::<Vec<String>>().join(", ")
+}
+
+/// Totals up the science packs needed for a technology and everything before it. {{{2
+fn total_research_cost(recipes: &RecipeBook, id: &str) -> BTreeMap<String, f64> {
+    let mut total = BTreeMap::new();
+    let mut chain = recipes.prerequisite_chain(id);
+    if let Some(technology) = recipes.technologies.get(id) {
+        chain.push(technology);
+    }
+    for technology in chain {
+        for (pack, amount) in technology.ingredients.iter() {
+            *total.entry(pack.clone()).or_insert(0.0) += amount * technology.count;
         }
-        result += &format!("{} x{}, ", pretty_name, ingredient);
+    }
+    total
+}
+
+/// Serializes the inputs or outputs it recieves into a nice list. {{{2
+fn serialize_recipe_io(recipes: &RecipeBook, amounts: &BTreeMap<String, f64>) -> String {
+    if amounts.is_empty() {
+        return String::from("None");
+    }
+    let mut result = String::new();
+    for (ing_key, ingredient) in amounts.iter() {
+        // Use the pretty name for the ingredient if there is one, otherwise use internal
+        result += &format!("{} x{}, ", recipes.name_of(ing_key), format_amount(*ingredient));
     }
     if result.len() > 3 {
         return result[..result.len() - 2].to_owned();
@@ -129,15 +1314,236 @@ fn serialize_recipe_io(value: &JsonValue) -> String {
 mod tests {
     use super::*;
 
+    lazy_static! {
+        static ref SHIPPED_RECIPES: RecipeBook = RecipeBook::load(RECIPE_FILE).unwrap();
+    }
+
+    fn shipped_recipes() -> &'static RecipeBook {
+        &SHIPPED_RECIPES
+    }
+
+    #[test]
+    fn failed_reload_keeps_last_good_copy() {
+        assert!(reload_recipes(RECIPE_FILE).is_ok());
+        match reload_recipes("no-such-recipes.json") {
+            Err(RecipeError::Io(_)) => {}
+            other => panic!("Expected the reload to fail, got {:?}", other.map(|_| ())),
+        }
+        assert!(RECIPES.lock().unwrap().is_ok());
+    }
+
     #[test]
     fn can_serialize_recipe_io() {
-        let mut testing_object = JsonValue::new_object();
+        let recipes = RecipeBook::parse(r#"{
+            "copper-plate": {"wiki-name": "Copper plate", "type": "recipe", "energy-required": 3.5,
+                             "inputs": {"copper-ore": 1}, "outputs": {"copper-plate": 1}},
+            "iron-gear-wheel": {"wiki-name": "Iron gear wheel", "type": "recipe", "energy-required": 0.5,
+                                "inputs": {"iron-plate": 2}, "outputs": {"iron-gear-wheel": 1}}
+        }"#).unwrap();
+        let mut testing_object = BTreeMap::new();
         // Add some values
-        testing_object["copper-plate"] = 2.into();
-        testing_object["iron-gear-wheel"] = 6.into();
-        testing_object["raw-wood"] = 10.into();
+        testing_object.insert(String::from("copper-plate"), 2.0);
+        testing_object.insert(String::from("iron-gear-wheel"), 6.0);
+        testing_object.insert(String::from("raw-wood"), 10.0);
 
-        let result = serialize_recipe_io(&testing_object);
+        let result = serialize_recipe_io(&recipes, &testing_object);
         assert_eq!(result, "Copper plate x2, Iron gear wheel x6, raw-wood x10");
     }
+
+    #[test]
+    fn can_parse_rates() {
+        assert_eq!(parse_rate("1/s"), Some(1.0));
+        assert_eq!(parse_rate("30/m"), Some(0.5));
+        assert_eq!(parse_rate("2"), Some(2.0));
+        assert_eq!(parse_rate("fast"), None);
+        assert_eq!(parse_rate("1/fortnight"), None);
+    }
+
+    #[test]
+    fn can_parse_ratio_request() {
+        let (item, rate, tiers) = parse_ratio_request("red science 1/s --assembler 3 --furnace stone").unwrap();
+        assert_eq!(item, "red science");
+        assert_eq!(rate, 1.0);
+        assert_eq!(tiers, MachineTiers { assembler: 2, furnace: 0 });
+
+        let (item, rate, _) = parse_ratio_request("science pack 1 30/m").unwrap();
+        assert_eq!(item, "science pack 1");
+        assert_eq!(rate, 0.5);
+
+        assert!(parse_ratio_request("red science").is_none());
+        assert!(parse_ratio_request("gear 1/s --assembler 4").is_none());
+    }
+
+    #[test]
+    fn can_parse_crafting_request() {
+        let (name, setup) = parse_crafting_request("electronic circuit --assembler 3 --modules 2xprod3,speed3 --beacons 8").unwrap();
+        assert_eq!(name, "electronic circuit");
+        assert_eq!(setup.tiers.assembler, 2);
+        assert_eq!(setup.modules.len(), 3);
+        assert_eq!(setup.modules[0].id, "productivity-module-3");
+        assert_eq!(setup.modules[2].id, "speed-module-3");
+        assert_eq!(setup.beacons.len(), 8);
+
+        assert_eq!(parse_module("effectivity-module-2").unwrap().id, "effectivity-module-2");
+        assert_eq!(parse_module("speed").unwrap().id, "speed-module");
+        assert!(parse_crafting_request("gear --modules warp9").is_none());
+        assert!(parse_crafting_request("gear --beacons 4xprod3").is_none());
+    }
+
+    #[test]
+    fn can_calculate_crafting_stats() {
+        let gear = &shipped_recipes().recipes["iron-gear-wheel"];
+        let stats = calculate_crafting_stats(gear, &CraftingSetup::default()).unwrap();
+        assert_eq!(stats.machine, "Assembling machine 2");
+        assert!((stats.craft_time - 0.5 / 0.75).abs() < 0.001);
+        assert!((stats.outputs_per_second["iron-gear-wheel"] - 1.5).abs() < 0.001);
+        assert_eq!(stats.power, 150.0);
+
+        // Four productivity 3s, and four beacons of speed 3s
+        let (_, setup) = parse_crafting_request("--assembler 3 --modules 4xprod3 --beacons 4").unwrap();
+        let stats = calculate_crafting_stats(gear, &setup).unwrap();
+        assert!((stats.speed_bonus - 1.4).abs() < 0.001);
+        assert!((stats.productivity_bonus - 0.4).abs() < 0.001);
+        assert!((stats.craft_time - 0.5 / (1.25 * 2.4)).abs() < 0.001);
+        assert!((stats.outputs_per_second["iron-gear-wheel"] - 1.4 / stats.craft_time).abs() < 0.001);
+        assert!((stats.power - 210.0 * (1.0 + 3.2 + 2.8)).abs() < 0.001);
+        assert_eq!(serialize_crafting_stats(shipped_recipes(), &stats),
+                   "Craft time: 0.17s\nOutput: Iron gear wheel: 8.4/s\nPower: 1470kW\nSpeed bonus: +140%\nProductivity bonus: +40%");
+
+        let (_, setup) = parse_crafting_request("--modules speed3,speed3,speed3").unwrap();
+        assert_eq!(calculate_crafting_stats(gear, &setup),
+                   Err(String::from("Assembling machine 2 only has 2 module slots.")));
+
+        let (_, setup) = parse_crafting_request("--modules prod3").unwrap();
+        assert_eq!(calculate_crafting_stats(&shipped_recipes().recipes["inserter"], &setup),
+                   Err(String::from("Productivity modules can only be used on intermediate products, which Inserter isn't.")));
+
+        let stats = calculate_crafting_stats(&shipped_recipes().recipes["iron-plate"], &CraftingSetup::default()).unwrap();
+        assert!(stats.burner);
+        assert!(serialize_crafting_stats(shipped_recipes(), &stats).contains("\nFuel: 90kW\n"));
+    }
+
+    #[test]
+    fn can_parse_search_criteria() {
+        let criteria = parse_search_criteria("uses plastic bar and copper cable produced in chemical plant").unwrap();
+        assert_eq!(criteria.uses, vec!["plastic bar", "copper cable"]);
+        assert_eq!(criteria.machine, Some(String::from("chemical plant")));
+        assert_eq!(criteria.page, 1);
+
+        let criteria = parse_search_criteria("takes more than 10 seconds page 2").unwrap();
+        assert_eq!(criteria.longer_than, Some(10.0));
+        assert_eq!(criteria.page, 2);
+        assert_eq!(parse_search_criteria("takes under 1s").unwrap().shorter_than, Some(1.0));
+
+        assert!(parse_search_criteria("").is_none());
+        assert!(parse_search_criteria("sulfuric acid").is_none());
+        assert!(parse_search_criteria("takes forever").is_none());
+        assert!(parse_search_criteria("page 0").is_none());
+    }
+
+    #[test]
+    fn can_search_recipes() {
+        let recipes = shipped_recipes();
+        let criteria = parse_search_criteria("uses sulfuric acid").unwrap();
+        let uses = find_item_keys(recipes, &criteria.uses).unwrap();
+        let results: Vec<&str> = search_recipes(recipes, &criteria, &uses, &[]).iter().map(|recipe| recipe.id.as_str()).collect();
+        assert!(results.contains(&"processing-unit"));
+        assert!(results.contains(&"battery"));
+        assert!(!results.contains(&"sulfuric-acid"));
+
+        let criteria = parse_search_criteria("in chemical plant takes more than 4 seconds").unwrap();
+        let results = search_recipes(recipes, &criteria, &[], &[]);
+        assert!(!results.is_empty());
+        assert!(results.iter().all(|recipe| recipe.category == "chemistry" && recipe.energy > 4.0));
+
+        let criteria = parse_search_criteria("in assembling machine 1").unwrap();
+        let results = search_recipes(recipes, &criteria, &[], &[]);
+        assert!(results.iter().all(|recipe| recipe.category == "crafting"));
+
+        assert_eq!(find_item_keys(recipes, &[String::from("unobtainium ingot")]), Err(String::from("unobtainium ingot")));
+    }
+
+    #[test]
+    fn finds_recipe_by_nickname() {
+        let (dist, key) = find_recipe_key(shipped_recipes(), "red science");
+        assert_eq!(key, "science-pack-1");
+        assert_eq!(dist, 0);
+
+        let (_, key) = find_recipe_key(shipped_recipes(), "Electronic circuit");
+        assert_eq!(key, "electronic-circuit");
+    }
+
+    #[test]
+    fn finds_items_without_recipes() {
+        let (dist, key) = find_item_key(shipped_recipes(), "heavy oil");
+        assert_eq!(key, "heavy-oil");
+        assert_eq!(dist, 0);
+    }
+
+    #[test]
+    fn can_find_item_uses() {
+        let recipes = shipped_recipes();
+        let (used_in, made_by) = find_item_uses(recipes, "heavy-oil");
+        let used_in: Vec<&str> = used_in.iter().map(|recipe| recipe.id.as_str()).collect();
+        assert!(used_in.contains(&"lubricant"));
+        assert!(used_in.contains(&"heavy-oil-cracking"));
+        assert_eq!(made_by[0].id, "advanced-oil-processing");
+        assert!(made_by.iter().all(|recipe| recipe.id != "lubricant"));
+
+        assert_eq!(serialize_recipe_list(recipes, &made_by[..1], "heavy-oil", "outputs"), "Advanced oil processing (x10)\n");
+    }
+
+    #[test]
+    fn can_calculate_production_chain() {
+        let chain = calculate_production_chain(shipped_recipes(), "science-pack-1", 1.0, &MachineTiers::default());
+
+        let science = &chain.steps["science-pack-1"];
+        assert_eq!(science.machine, "Assembling machine 2");
+        assert!((science.machine_count - 5.0 / 0.75).abs() < 0.001);
+
+        let plates = &chain.steps["iron-plate"];
+        assert_eq!(plates.machine, "Steel furnace");
+        assert!((plates.items_per_second - 2.0).abs() < 0.001);
+        assert!((plates.machine_count - 3.5).abs() < 0.001);
+
+        assert!((chain.raw_resources["iron-ore"] - 2.0).abs() < 0.001);
+        assert!((chain.raw_resources["copper-ore"] - 1.0).abs() < 0.001);
+    }
+
+    #[test]
+    fn expensive_recipes_cost_more() {
+        let recipes = shipped_recipes().with_expensive_recipes();
+        let chain = calculate_production_chain(&recipes, "iron-gear-wheel", 1.0, &MachineTiers::default());
+        assert!((chain.raw_resources["iron-ore"] - 4.0).abs() < 0.001);
+
+        assert_eq!(expensive_note(shipped_recipes(), "iron-gear-wheel"), "");
+        assert_eq!(expensive_note(shipped_recipes(), "iron-plate"),
+                   "No expensive variant is listed for Iron plate, so its normal recipe is shown.");
+    }
+
+    #[test]
+    fn can_look_up_technologies() {
+        let recipes = shipped_recipes();
+        assert_eq!(find_technology_key(recipes, "Automation 2"), Some((0, "automation-2")));
+
+        let automation = &recipes.technologies["automation-2"];
+        assert_eq!(serialize_technology_cost(recipes, automation),
+                   "40 × (Science pack 1 x1, Science pack 2 x1) × 15s\nIn total: Science pack 1 x40, Science pack 2 x40, taking 600s at lab speed 1");
+        assert_eq!(serialize_technology_names(recipes, &automation.prerequisites), "Electronics");
+
+        let total = total_research_cost(recipes, "automation-2");
+        assert_eq!(total["science-pack-1"], 80.0);
+        assert_eq!(total["science-pack-2"], 40.0);
+    }
+
+    #[test]
+    fn can_build_recipe_tree() {
+        let recipes = shipped_recipes();
+        let mut tree = String::new();
+        let mut raw_materials = BTreeMap::new();
+        build_recipe_tree(recipes, &recipes.recipes["science-pack-1"].inputs, 1.0, 0, &mut tree, &mut raw_materials);
+
+        assert_eq!(tree, "Copper plate x1\n  Copper ore x1\nIron gear wheel x1\n  Iron plate x2\n    Iron ore x2\n");
+        assert_eq!(serialize_item_amounts(recipes, &raw_materials, ""), "Copper ore: 1\nIron ore: 2\n");
+    }
 }
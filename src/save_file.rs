@@ -0,0 +1,258 @@
+/// Reads the header at the start of a save's level.dat, which says what made the save and
+/// which mods it needs, without loading the map itself. Saves are zip files, holding a
+/// folder named after the save, with level.dat inside. The header is laid out as the game
+/// writes it since 0.16, little endian, with strings and counts written like in map exchange
+/// strings, see binary_reader.
+
+use zip::ZipArchive;
+
+use binary_reader::BinaryReader;
+
+use std::fmt;
+use std::io::{Cursor, Read};
+
+/// How much of level.dat is read, in bytes. The header is well under it, even with hundreds of mods.
+const HEADER_BYTES: u64 = 64_000;
+
+/// A mod a save was made with. {{{1
+#[derive(Debug, Clone, PartialEq)]
+pub struct SavedMod {
+    pub name: String,
+    pub version: [u16; 3],
+    pub crc: u32, //Checksum of the mod's files, so changed mods can be spotted
+}
+
+impl SavedMod {
+    /// Formats the version of the mod, such as 0.16.0. {{{2
+    pub fn version_string(&self) -> String {
+        format!("{}.{}.{}", self.version[0], self.version[1], self.version[2])
+    }
+}
+
+/// The header of a save. {{{1
+#[derive(Debug, Clone, PartialEq)]
+pub struct SaveHeader {
+    pub version: [u16; 4], //Game version it was saved in, like 0.16.51.0
+    pub campaign: String,
+    pub level_name: String, //Scenario the save was started from
+    pub base_mod: String, //Mod holding the scenario, usually base
+    pub difficulty: u8,
+    pub finished: bool,
+    pub player_won: bool,
+    pub next_level: String,
+    pub can_continue: bool,
+    pub finished_but_continuing: bool,
+    pub saving_replay: bool, //Replays can only be recorded in single player
+    pub allow_non_admin_debug_options: bool,
+    pub loaded_from: [u8; 3], //Version the save was last loaded in, before being saved again
+    pub loaded_from_build: u16,
+    pub allowed_commands: u8,
+    pub mods: Vec<SavedMod>, //Including the base game
+}
+
+/// Reasons a save couldn't be read. {{{1
+#[derive(Debug, Clone, PartialEq)]
+pub enum SaveError {
+    NotZip(String),
+    NoLevel,
+    Truncated,
+}
+
+impl fmt::Display for SaveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            SaveError::NotZip(ref reason) => write!(f, "That isn't a zip file, like saves are: {}", reason),
+            SaveError::NoLevel => write!(f, "That zip file doesn't have a level.dat in it, so it isn't a save."),
+            SaveError::Truncated => {
+                write!(f, "The header of that save ends too early, it may be from a version of the game I can't read.")
+            }
+        }
+    }
+}
+
+impl SaveHeader {
+    /// Reads the header of a save, given the whole zip file. Uses level-init.dat {{{2
+    /// if there's no level.dat, which is the map as it was first made.
+    pub fn from_zip(data: &[u8]) -> Result<SaveHeader, SaveError> {
+        let mut archive = match ZipArchive::new(Cursor::new(data)) {
+            Ok(archive) => archive,
+            Err(error) => return Err(SaveError::NotZip(format!("{}", error))),
+        };
+
+        let mut level = None;
+        for wanted in &["level.dat", "level-init.dat"] {
+            for index in 0..archive.len() {
+                if let Ok(file) = archive.by_index(index) {
+                    if file.name() == *wanted || file.name().ends_with(&format!("/{}", wanted)) {
+                        level = Some(index);
+                        break;
+                    }
+                }
+            }
+            if level.is_some() {
+                break;
+            }
+        }
+        let level = match level {
+            Some(level) => level,
+            None => return Err(SaveError::NoLevel),
+        };
+
+        let mut header = Vec::new();
+        match archive.by_index(level) {
+            Ok(file) => {
+                if let Err(error) = file.take(HEADER_BYTES).read_to_end(&mut header) {
+                    return Err(SaveError::NotZip(format!("{}", error)));
+                }
+            }
+            Err(error) => return Err(SaveError::NotZip(format!("{}", error))),
+        }
+        SaveHeader::read(&mut BinaryReader::new(&header))
+    }
+
+    /// Reads the header from the start of level.dat. {{{2
+    pub fn read(reader: &mut BinaryReader) -> Result<SaveHeader, SaveError> {
+        let version = [reader.u16(), reader.u16(), reader.u16(), reader.u16()];
+        // Always 0 so far
+        reader.u8();
+
+        let campaign = reader.string();
+        let level_name = reader.string();
+        let base_mod = reader.string();
+        let difficulty = reader.u8();
+        let finished = reader.bool();
+        let player_won = reader.bool();
+        let next_level = reader.string();
+        let can_continue = reader.bool();
+        let finished_but_continuing = reader.bool();
+        let saving_replay = reader.bool();
+        let allow_non_admin_debug_options = reader.bool();
+        let loaded_from = [reader.u8(), reader.u8(), reader.u8()];
+        let loaded_from_build = reader.u16();
+        let allowed_commands = reader.u8();
+
+        let mut mods = Vec::new();
+        for _ in 0..reader.count() {
+            let saved = SavedMod {
+                name: reader.string(),
+                version: [reader.optimized_u16(), reader.optimized_u16(), reader.optimized_u16()],
+                crc: reader.u32(),
+            };
+            if reader.is_truncated() {
+                break;
+            }
+            mods.push(saved);
+        }
+
+        if reader.is_truncated() {
+            return Err(SaveError::Truncated);
+        }
+        Ok(SaveHeader {
+            version,
+            campaign,
+            level_name,
+            base_mod,
+            difficulty,
+            finished,
+            player_won,
+            next_level,
+            can_continue,
+            finished_but_continuing,
+            saving_replay,
+            allow_non_admin_debug_options,
+            loaded_from,
+            loaded_from_build,
+            allowed_commands,
+            mods,
+        })
+    }
+
+    /// Formats the game version the save was made in, such as 0.16.51. {{{2
+    pub fn version_string(&self) -> String {
+        format!("{}.{}.{}", self.version[0], self.version[1], self.version[2])
+    }
+
+    /// Returns who may run commands on the save, as shown in the game. {{{2
+    pub fn allowed_commands_name(&self) -> &'static str {
+        match self.allowed_commands {
+            0 => "Everyone",
+            1 => "Nobody",
+            2 => "Admins only",
+            _ => "Unknown",
+        }
+    }
+}
+
+// Tests {{{1
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use zip::ZipWriter;
+    use zip::write::FileOptions;
+
+    use std::io::Write;
+
+    /// Writes the header of a 0.16.51 freeplay save with bobores, as the game would.
+    fn test_header() -> Vec<u8> {
+        let mut data = vec![0, 0, 16, 0, 51, 0, 0, 0, 0];
+        for text in &["", "freeplay", "base"] {
+            data.push(text.len() as u8);
+            data.extend_from_slice(text.as_bytes());
+        }
+        // Difficulty, finished, won, no next level, can continue, finished but continuing,
+        // saving a replay, debug options, loaded from 0.16.51 build 35106, commands for admins
+        data.extend_from_slice(&[0, 0, 0, 0, 1, 0, 0, 0, 0, 16, 51, 0x22, 0x89, 2]);
+        data.push(2);
+        data.extend_from_slice(&[4, b'b', b'a', b's', b'e', 0, 16, 51, 1, 0, 0, 0]);
+        data.extend_from_slice(&[7, b'b', b'o', b'b', b'o', b'r', b'e', b's', 0, 16, 0, 2, 0, 0, 0]);
+        // The map itself would follow
+        data.extend_from_slice(&[0; 32]);
+        data
+    }
+
+    fn zip_save(files: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        for &(name, data) in files.iter() {
+            writer.start_file(name, FileOptions::default()).unwrap();
+            writer.write_all(data).unwrap();
+        }
+        writer.finish().unwrap().into_inner()
+    }
+
+    #[test]
+    fn can_read_save_header() {
+        let header = SaveHeader::read(&mut BinaryReader::new(&test_header())).unwrap();
+        assert_eq!(header.version_string(), "0.16.51");
+        assert_eq!(header.level_name, "freeplay");
+        assert_eq!(header.base_mod, "base");
+        assert!(header.can_continue);
+        assert!(!header.saving_replay);
+        assert_eq!(header.loaded_from, [0, 16, 51]);
+        assert_eq!(header.loaded_from_build, 35106);
+        assert_eq!(header.allowed_commands_name(), "Admins only");
+        assert_eq!(header.mods.len(), 2);
+        assert_eq!(header.mods[1].name, "bobores");
+        assert_eq!(header.mods[1].version_string(), "0.16.0");
+        assert_eq!(header.mods[1].crc, 2);
+
+        let data = test_header();
+        assert_eq!(SaveHeader::read(&mut BinaryReader::new(&data[..40])), Err(SaveError::Truncated));
+    }
+
+    #[test]
+    fn can_read_save_zip() {
+        let header = test_header();
+        let save = zip_save(&[("My base/control.lua", b"-- scenario script"), ("My base/level.dat", &header)]);
+        assert_eq!(SaveHeader::from_zip(&save).unwrap().level_name, "freeplay");
+
+        let save = zip_save(&[("My base/level-init.dat", &header)]);
+        assert_eq!(SaveHeader::from_zip(&save).unwrap().mods.len(), 2);
+
+        let save = zip_save(&[("My base/control.lua", b"-- scenario script")]);
+        assert_eq!(SaveHeader::from_zip(&save), Err(SaveError::NoLevel));
+        match SaveHeader::from_zip(b"not a zip") {
+            Err(SaveError::NotZip(_)) => {}
+            other => panic!("Expected a zip error, got {:?}", other),
+        }
+    }
+}